@@ -0,0 +1,46 @@
+//! Embedding the interpreter through the prelude only.
+//!
+//! Run with `cargo run --example embed`.
+
+use lib::prelude::*;
+
+const SCRIPT: &str = "
+/// price after applying the injected discount
+let discounted = fn(price) { price - price * discount / 100 };
+round_to(discounted(257), 10)
+";
+
+fn main() -> Result<(), MonkeyError> {
+    let mut eval = Evaluator::new();
+    // inject a global the script can read
+    eval.set("discount".to_string(), Object::Integer(20));
+    // and a host function it can call like any builtin
+    eval.register_builtin("round_to", |args| match args.as_slice() {
+        [Object::Integer(n), Object::Integer(step)] if *step > 0 => {
+            Ok(Object::Integer(n / step * step))
+        }
+        _ => Err(MonkeyError::Raised(
+            "round_to expects an INTEGER and a positive step".to_string(),
+        )),
+    });
+    // refuse `if (5)`-style conditions in host-provided scripts
+    eval.set_truthiness(Truthiness::Strict);
+
+    let result: i64 = eval.eval_as(SCRIPT)?;
+    println!("discounted price: {}", result);
+    assert_eq!(result, 200);
+
+    if let Some(doc) = eval.get_doc("discounted") {
+        println!("discounted: {}", doc);
+    }
+    for diagnostic in eval.take_diagnostics() {
+        eprintln!("{}", diagnostic);
+    }
+
+    match interpret("1 + true") {
+        Err(err) => println!("error: {}", err),
+        Ok(obj) => println!("unexpected result: {}", obj),
+    }
+    println!("{} tokens in the script", tokenize(SCRIPT).len());
+    Ok(())
+}
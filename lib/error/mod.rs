@@ -1,36 +1,432 @@
+use std::fmt;
+
 use thiserror::Error;
 
 use crate::{eval::object, lexer::token, parser::ast};
 
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum MonkeyError {
-    #[error("unexpected token")]
+    #[error("expected {expected} but found {actual}")]
     UnexpectedToken {
         expected: token::Token,
         actual: token::Token,
     },
-    #[error("invalid token")]
+    #[error("invalid token: {0}")]
     InvalidToken(token::Token),
     #[error("invalid identifier")]
     InvalidIdentifier,
-    #[error("identifier not found")]
-    IdentifierNotFound,
+    #[error("identifier not found: {0}")]
+    IdentifierNotFound(String),
     #[error("invalid integer")]
     InvalidInteger,
+    #[error("malformed number {0}: `_` must sit between two digits")]
+    MalformedNumber(String),
+    #[error("integer overflow")]
+    IntegerOverflow,
     #[error("unknown error")]
     Unknown,
-    #[error("unknown operator")]
+    #[error("unknown operator: {left} {operator} {right}")]
     UnknownOperator {
         operator: ast::Infix,
         left: object::ObjectType,
         right: object::ObjectType,
     },
-    #[error("type mismatchh")]
+    #[error("type mismatch: {left} {operator} {right}")]
     TypeMismatch {
         operator: ast::Infix,
         left: object::ObjectType,
         right: object::ObjectType,
     },
-    #[error("incorrect number of arguments")]
+    #[error("wrong number of arguments: expected {expected}, got {actual}")]
     IncorrectNumberOfArguments { expected: usize, actual: usize },
+    #[error("break outside of a loop")]
+    BreakOutsideLoop,
+    #[error("continue outside of a loop")]
+    ContinueOutsideLoop,
+    #[error("type mismatch: condition is {0}, expected BOOLEAN")]
+    ConditionTypeMismatch(object::ObjectType),
+    #[error("index operator not supported: {left}[{index}]")]
+    IndexNotSupported {
+        left: object::ObjectType,
+        index: object::ObjectType,
+    },
+    #[error("unusable as hash key: {0}")]
+    UnusableAsHashKey(object::ObjectType),
+    #[error("cannot convert {value} ({object_type}) to {target}")]
+    ConversionFailed {
+        value: String,
+        object_type: object::ObjectType,
+        target: &'static str,
+    },
+    #[error("cannot bind `{0}`: the environment is frozen")]
+    EnvironmentFrozen(String),
+    #[error("cannot parse {0:?} as INTEGER")]
+    NotAnInteger(String),
+    #[error("expression nested too deeply: the limit is {0} levels")]
+    NestingTooDeep(usize),
+    #[error("malformed character '{0}': expected exactly one character between quotes")]
+    MalformedChar(String),
+    #[error("range bounds must be INTEGER, got {start}..{end}")]
+    InvalidRange {
+        start: object::ObjectType,
+        end: object::ObjectType,
+    },
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("index out of range: {index} for length {length}")]
+    IndexOutOfRange { index: i64, length: usize },
+    #[error("negative exponent {0}: integer powers need an exponent of 0 or more")]
+    NegativeExponent(String),
+    #[error("cannot iterate over {0}: expected ARRAY, HASH or STRING")]
+    NotIterable(object::ObjectType),
+    #[error("cannot call {0}: expected FUNCTION")]
+    NotCallable(object::ObjectType),
+    #[error("cannot assign to {0}: expected a name or an index into one")]
+    InvalidAssignmentTarget(String),
+    #[error(
+        "argument {position} to `{function}` must be {}, got {argument}",
+        one_of(expected)
+    )]
+    UnsupportedArgument {
+        function: &'static str,
+        /// counting from 1
+        position: usize,
+        expected: &'static [object::ObjectType],
+        argument: object::ObjectType,
+    },
+    #[error("sort comparator must return INTEGER, got {0}")]
+    InvalidComparator(object::ObjectType),
+    #[error("cannot sort {left} at index {left_index} against {right} at index {right_index}")]
+    Incomparable {
+        left: object::ObjectType,
+        left_index: usize,
+        right: object::ObjectType,
+        right_index: usize,
+    },
+    #[error("argument to `{function}` must hold only INTEGERs, got {element} at index {index}")]
+    NonNumericElement {
+        function: &'static str,
+        index: usize,
+        element: object::ObjectType,
+    },
+    #[error("string of {length} chars would exceed the limit of {limit}")]
+    StringTooLong { length: u128, limit: usize },
+    #[error("range of {length} elements would exceed the limit of {limit}")]
+    RangeTooLong { length: u128, limit: usize },
+    #[error("hash would exceed the limit of {0} pairs")]
+    HashTooLarge(usize),
+    #[error("fill must be a single character, got {0:?}")]
+    InvalidFill(String),
+    #[error("entry {index} must be a [key, value] array with a hashable key, got {entry}")]
+    MalformedEntry { index: usize, entry: String },
+    #[error("decimal places must be from 0 to {limit}, got {digits}")]
+    InvalidDigits { digits: i64, limit: i64 },
+    #[error("chunk size must be positive, got {0}")]
+    InvalidChunkSize(i64),
+    #[error("format string has {placeholders} placeholders, got {arguments} arguments")]
+    FormatArgumentMismatch {
+        placeholders: usize,
+        arguments: usize,
+    },
+    #[error("unmatched brace in format string {0:?}: use {{{{ or }}}} for a literal one")]
+    UnmatchedBrace(String),
+    #[error("cannot write output: {0}")]
+    OutputFailed(String),
+    /// raised by `error(message)` in the program
+    #[error("{0}")]
+    Raised(String),
+    #[error("call stack exceeded: the limit is {0} nested calls")]
+    CallStackExceeded(usize),
+    #[error("aborted by host: {0}")]
+    AbortedByHost(String),
+}
+
+/// `types` as a list for a message, such as "STRING, ARRAY or HASH"
+fn one_of(types: &[object::ObjectType]) -> String {
+    let names = types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    match names.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => "of any type".to_string(),
+    }
+}
+
+/// Non-fatal problem noticed during evaluation, collected by the `Evaluator`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning: {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        let tests = [
+            (
+                MonkeyError::UnexpectedToken {
+                    expected: token::Token::RParen,
+                    actual: token::Token::LBrace,
+                },
+                "expected ')' but found '{'",
+            ),
+            (
+                MonkeyError::UnexpectedToken {
+                    expected: token::Token::Identifier("".to_string()),
+                    actual: token::Token::IntLiteral(5),
+                },
+                "expected identifier but found integer 5",
+            ),
+            (
+                MonkeyError::InvalidToken(token::Token::Let),
+                "invalid token: 'let'",
+            ),
+            (
+                MonkeyError::InvalidToken(token::Token::Identifier("foo".to_string())),
+                "invalid token: identifier 'foo'",
+            ),
+            (
+                MonkeyError::InvalidToken(token::Token::EOF),
+                "invalid token: end of input",
+            ),
+            (MonkeyError::InvalidIdentifier, "invalid identifier"),
+            (
+                MonkeyError::IdentifierNotFound("foobar".to_string()),
+                "identifier not found: foobar",
+            ),
+            (MonkeyError::InvalidInteger, "invalid integer"),
+            (
+                MonkeyError::MalformedNumber("1_".to_string()),
+                "malformed number 1_: `_` must sit between two digits",
+            ),
+            (MonkeyError::IntegerOverflow, "integer overflow"),
+            (MonkeyError::Unknown, "unknown error"),
+            (
+                MonkeyError::UnknownOperator {
+                    operator: ast::Infix::Plus,
+                    left: object::ObjectType::Bool,
+                    right: object::ObjectType::Bool,
+                },
+                "unknown operator: BOOLEAN + BOOLEAN",
+            ),
+            (
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Minus,
+                    left: object::ObjectType::Integer,
+                    right: object::ObjectType::Bool,
+                },
+                "type mismatch: INTEGER - BOOLEAN",
+            ),
+            (
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::In,
+                    left: object::ObjectType::Integer,
+                    right: object::ObjectType::Integer,
+                },
+                "type mismatch: INTEGER in INTEGER",
+            ),
+            (
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 3,
+                },
+                "wrong number of arguments: expected 2, got 3",
+            ),
+            (MonkeyError::BreakOutsideLoop, "break outside of a loop"),
+            (
+                MonkeyError::ContinueOutsideLoop,
+                "continue outside of a loop",
+            ),
+            (
+                MonkeyError::ConditionTypeMismatch(object::ObjectType::String),
+                "type mismatch: condition is STRING, expected BOOLEAN",
+            ),
+            (
+                MonkeyError::IndexNotSupported {
+                    left: object::ObjectType::Array,
+                    index: object::ObjectType::String,
+                },
+                "index operator not supported: ARRAY[STRING]",
+            ),
+            (
+                MonkeyError::UnusableAsHashKey(object::ObjectType::Function),
+                "unusable as hash key: FUNCTION",
+            ),
+            (
+                MonkeyError::ConversionFailed {
+                    value: "[1, 2]".to_string(),
+                    object_type: object::ObjectType::Array,
+                    target: "i64",
+                },
+                "cannot convert [1, 2] (ARRAY) to i64",
+            ),
+            (
+                MonkeyError::EnvironmentFrozen("total".to_string()),
+                "cannot bind `total`: the environment is frozen",
+            ),
+            (
+                MonkeyError::NotAnInteger("12abc".to_string()),
+                r#"cannot parse "12abc" as INTEGER"#,
+            ),
+            (
+                MonkeyError::NestingTooDeep(256),
+                "expression nested too deeply: the limit is 256 levels",
+            ),
+            (
+                MonkeyError::MalformedChar("ab".to_string()),
+                "malformed character 'ab': expected exactly one character between quotes",
+            ),
+            (
+                MonkeyError::InvalidRange {
+                    start: object::ObjectType::Integer,
+                    end: object::ObjectType::String,
+                },
+                "range bounds must be INTEGER, got INTEGER..STRING",
+            ),
+            (MonkeyError::DivisionByZero, "division by zero"),
+            (
+                MonkeyError::IndexOutOfRange {
+                    index: -4,
+                    length: 3,
+                },
+                "index out of range: -4 for length 3",
+            ),
+            (
+                MonkeyError::NegativeExponent("-1".to_string()),
+                "negative exponent -1: integer powers need an exponent of 0 or more",
+            ),
+            (
+                MonkeyError::NotIterable(object::ObjectType::Integer),
+                "cannot iterate over INTEGER: expected ARRAY, HASH or STRING",
+            ),
+            (
+                MonkeyError::NotCallable(object::ObjectType::String),
+                "cannot call STRING: expected FUNCTION",
+            ),
+            (
+                MonkeyError::InvalidAssignmentTarget("(a+b)".to_string()),
+                "cannot assign to (a+b): expected a name or an index into one",
+            ),
+            (
+                MonkeyError::UnsupportedArgument {
+                    function: "len",
+                    position: 1,
+                    expected: &[
+                        object::ObjectType::String,
+                        object::ObjectType::Array,
+                        object::ObjectType::Hash,
+                    ],
+                    argument: object::ObjectType::Integer,
+                },
+                "argument 1 to `len` must be STRING, ARRAY or HASH, got INTEGER",
+            ),
+            (
+                MonkeyError::UnsupportedArgument {
+                    function: "take",
+                    position: 2,
+                    expected: &[object::ObjectType::Integer],
+                    argument: object::ObjectType::String,
+                },
+                "argument 2 to `take` must be INTEGER, got STRING",
+            ),
+            (
+                MonkeyError::InvalidComparator(object::ObjectType::Bool),
+                "sort comparator must return INTEGER, got BOOLEAN",
+            ),
+            (
+                MonkeyError::Incomparable {
+                    left: object::ObjectType::Integer,
+                    left_index: 0,
+                    right: object::ObjectType::String,
+                    right_index: 2,
+                },
+                "cannot sort INTEGER at index 0 against STRING at index 2",
+            ),
+            (
+                MonkeyError::NonNumericElement {
+                    function: "sum",
+                    index: 1,
+                    element: object::ObjectType::String,
+                },
+                "argument to `sum` must hold only INTEGERs, got STRING at index 1",
+            ),
+            (
+                MonkeyError::StringTooLong {
+                    length: 20_000_000,
+                    limit: 16_777_216,
+                },
+                "string of 20000000 chars would exceed the limit of 16777216",
+            ),
+            (
+                MonkeyError::RangeTooLong {
+                    length: 100_000_000,
+                    limit: 1_048_576,
+                },
+                "range of 100000000 elements would exceed the limit of 1048576",
+            ),
+            (
+                MonkeyError::HashTooLarge(1_048_576),
+                "hash would exceed the limit of 1048576 pairs",
+            ),
+            (
+                MonkeyError::InvalidFill("ab".to_string()),
+                r#"fill must be a single character, got "ab""#,
+            ),
+            (
+                MonkeyError::MalformedEntry {
+                    index: 2,
+                    entry: "[1]".to_string(),
+                },
+                "entry 2 must be a [key, value] array with a hashable key, got [1]",
+            ),
+            (
+                MonkeyError::InvalidDigits {
+                    digits: -1,
+                    limit: 100,
+                },
+                "decimal places must be from 0 to 100, got -1",
+            ),
+            (
+                MonkeyError::InvalidChunkSize(0),
+                "chunk size must be positive, got 0",
+            ),
+            (
+                MonkeyError::FormatArgumentMismatch {
+                    placeholders: 2,
+                    arguments: 1,
+                },
+                "format string has 2 placeholders, got 1 arguments",
+            ),
+            (
+                MonkeyError::UnmatchedBrace("{x}".to_string()),
+                r#"unmatched brace in format string "{x}": use {{ or }} for a literal one"#,
+            ),
+            (
+                MonkeyError::OutputFailed("broken pipe".to_string()),
+                "cannot write output: broken pipe",
+            ),
+            (
+                MonkeyError::Raised("out of stock".to_string()),
+                "out of stock",
+            ),
+            (
+                MonkeyError::CallStackExceeded(200),
+                "call stack exceeded: the limit is 200 nested calls",
+            ),
+            (
+                MonkeyError::AbortedByHost("too slow".to_string()),
+                "aborted by host: too slow",
+            ),
+        ];
+        for (error, expected) in tests {
+            assert_eq!(format!("{}", error), expected)
+        }
+    }
 }
@@ -0,0 +1,89 @@
+use num_bigint::{BigInt, Sign};
+
+use crate::{
+    error::MonkeyError,
+    eval::object::{Object, ObjectType},
+    parser::ast,
+};
+
+/// The most bits a power may have, so `3 ** 100000000` fails at once with
+/// `IntegerOverflow` instead of computing for minutes or exhausting memory.
+/// Sums and products stay below it unless a program keeps multiplying.
+pub(crate) const MAX_POW_BITS: u64 = 1 << 20;
+
+/// Values that fit in i64 always come back as `Object::Integer`, so a
+/// promoted result and a small one with the same value compare equal.
+pub fn normalize(int: BigInt) -> Object {
+    match i64::try_from(&int) {
+        Ok(int) => Object::Integer(int),
+        Err(_) => Object::BigInteger(int),
+    }
+}
+
+pub fn to_bigint(obj: &Object) -> Option<BigInt> {
+    match obj {
+        Object::Integer(int) => Some(BigInt::from(*int)),
+        Object::BigInteger(int) => Some(int.clone()),
+        _ => None,
+    }
+}
+
+pub fn parse_literal(digits: &str) -> Result<Object, MonkeyError> {
+    digits
+        .parse::<BigInt>()
+        .map(normalize)
+        .map_err(|_| MonkeyError::InvalidInteger)
+}
+
+/// `base ** exp` for a non-negative `exp`, refused when the bits of `base`
+/// times `exp` pass `MAX_POW_BITS`
+fn pow(base: BigInt, exp: BigInt) -> Result<Object, MonkeyError> {
+    // the only bases whose powers stay small, at any exponent
+    if base == BigInt::from(0) || base == BigInt::from(1) {
+        return Ok(normalize(base));
+    }
+    if base == BigInt::from(-1) {
+        return Ok(Object::Integer(if exp.bit(0) { -1 } else { 1 }));
+    }
+    match u32::try_from(&exp) {
+        Ok(exp) if base.bits().saturating_mul(exp.into()) <= MAX_POW_BITS => {
+            Ok(normalize(base.pow(exp)))
+        }
+        _ => Err(MonkeyError::IntegerOverflow),
+    }
+}
+
+pub fn eval_infix_expression(
+    operator: &ast::Infix,
+    left: BigInt,
+    right: BigInt,
+) -> Result<Object, MonkeyError> {
+    match operator {
+        ast::Infix::Eq => Ok(Object::Bool(left == right)),
+        ast::Infix::NotEq => Ok(Object::Bool(left != right)),
+        ast::Infix::LT => Ok(Object::Bool(left < right)),
+        ast::Infix::GT => Ok(Object::Bool(left > right)),
+        ast::Infix::LTEq => Ok(Object::Bool(left <= right)),
+        ast::Infix::GTEq => Ok(Object::Bool(left >= right)),
+        ast::Infix::Plus => Ok(normalize(left + right)),
+        ast::Infix::Minus => Ok(normalize(left - right)),
+        ast::Infix::Slash | ast::Infix::Percent if right.sign() == Sign::NoSign => {
+            Err(MonkeyError::DivisionByZero)
+        }
+        ast::Infix::Slash => Ok(normalize(left / right)),
+        ast::Infix::Asterisk => Ok(normalize(left * right)),
+        ast::Infix::Percent => Ok(normalize(left % right)),
+        ast::Infix::Pow if right.sign() == Sign::Minus => {
+            Err(MonkeyError::NegativeExponent(right.to_string()))
+        }
+        ast::Infix::Pow => pow(left, right),
+        // `??` and `|>` are handled by `eval_link`, `in` by `eval_in_expression`
+        ast::Infix::Coalesce | ast::Infix::Pipe | ast::Infix::In => {
+            Err(MonkeyError::UnknownOperator {
+                operator: *operator,
+                left: ObjectType::Integer,
+                right: ObjectType::Integer,
+            })
+        }
+    }
+}
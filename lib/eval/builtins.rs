@@ -0,0 +1,1280 @@
+use std::{cmp::Ordering, collections::HashMap, fmt, num::IntErrorKind, rc::Rc, vec};
+
+use crate::{
+    error::MonkeyError,
+    eval::{
+        iterable::Iterable,
+        object::{insert_pair, HashKey, HashState, Object, ObjectType},
+        Evaluator,
+    },
+    parser::ast,
+};
+
+/// the most chars a builtin builds a string of, so a script cannot make
+/// one exhaust memory by accident
+const MAX_STRING_CHARS: usize = 1 << 24;
+
+/// the most decimal places `to_fixed` gives
+const MAX_FIXED_DIGITS: i64 = 100;
+
+type NativeFn = fn(&mut Evaluator, Vec<Object>) -> Result<Object, MonkeyError>;
+
+pub(crate) type HostFn = dyn Fn(Vec<Object>) -> Result<Object, MonkeyError>;
+
+type CharPredicate = fn(char) -> bool;
+
+type Pairs = HashMap<HashKey, Object, HashState>;
+
+#[derive(Clone)]
+enum Function {
+    /// one of the table below
+    Native(NativeFn),
+    /// one of `CHAR_CLASSES`
+    CharClass(&'static str, CharPredicate),
+    /// registered with `Evaluator::register_builtin`
+    Host(Rc<HostFn>),
+}
+
+/// A function implemented in Rust. The ones below are found by name when
+/// no binding has it; a host's are bound like any other value.
+///
+/// It displays as `builtin NAME`. Two builtins are equal, also under `==`,
+/// when they are the same function: the same one of the table, or the same
+/// registration.
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: String,
+    func: Function,
+}
+
+impl Builtin {
+    pub(crate) fn host(name: String, func: Rc<HostFn>) -> Self {
+        Builtin {
+            name,
+            func: Function::Host(func),
+        }
+    }
+
+    pub(crate) fn call(
+        &self,
+        evaluator: &mut Evaluator,
+        args: Vec<Object>,
+    ) -> Result<Object, MonkeyError> {
+        match &self.func {
+            Function::Native(func) => func(evaluator, args),
+            Function::CharClass(name, predicate) => {
+                let class = |_: &mut Evaluator, Text(text): Text| {
+                    Ok(Object::Bool(
+                        !text.is_empty() && text.chars().all(predicate),
+                    ))
+                };
+                Native::call(&class, name, evaluator, args)
+            }
+            Function::Host(func) => func(args),
+        }
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.func, &other.func) {
+            (Function::Native(_), Function::Native(_))
+            | (Function::CharClass(..), Function::CharClass(..)) => self.name == other.name,
+            (Function::Host(left), Function::Host(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Builtin {}
+
+/// The table entry of each function named, under its own name. Each takes
+/// the evaluator and then its parameters, see `Native`.
+macro_rules! builtins {
+    ($($name:ident),* $(,)?) => {
+        &[$((
+            stringify!($name),
+            (|evaluator: &mut Evaluator, args: Vec<Object>| {
+                Native::call(&$name, stringify!($name), evaluator, args)
+            }) as NativeFn,
+        )),*]
+    };
+}
+
+const BUILTINS: &[(&str, NativeFn)] = builtins![
+    error,
+    len,
+    is_empty,
+    first,
+    last,
+    rest,
+    push,
+    with,
+    swap,
+    take,
+    drop,
+    chunk,
+    puts,
+    dbg,
+    map,
+    filter,
+    reduce,
+    sort,
+    sum,
+    product,
+    min_of,
+    max_of,
+    reverse,
+    contains,
+    keys,
+    values,
+    has_key,
+    delete,
+    entries,
+    from_entries,
+    int,
+    str,
+    bool,
+    split,
+    join,
+    upper,
+    lower,
+    trim,
+    trim_start,
+    trim_end,
+    pad_start,
+    pad_end,
+    repeat,
+    index_of,
+    count,
+    split_n,
+    replace,
+    format,
+    to_fixed,
+    to_hex,
+    to_bin,
+    to_oct,
+    format_int,
+];
+
+/// `is_digit(string)` and the like: whether `string` has chars and all of
+/// them are in the class. Digits are only `0` to `9`, which `int` reads;
+/// the other classes are Unicode's.
+const CHAR_CLASSES: &[(&str, CharPredicate)] = &[
+    ("is_digit", |c| c.is_ascii_digit()),
+    ("is_alpha", char::is_alphabetic),
+    ("is_alnum", char::is_alphanumeric),
+    ("is_whitespace", char::is_whitespace),
+    ("is_upper", char::is_uppercase),
+    ("is_lower", char::is_lowercase),
+];
+
+pub(crate) fn lookup(name: &str) -> Option<Builtin> {
+    let func = match BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+        Some((_, func)) => Function::Native(*func),
+        None => {
+            let (name, predicate) = CHAR_CLASSES.iter().find(|(class, _)| *class == name)?;
+            Function::CharClass(name, *predicate)
+        }
+    };
+    Some(Builtin {
+        name: name.to_string(),
+        func,
+    })
+}
+
+/// The Rust function of a builtin: the evaluator, then one parameter per
+/// argument, each a `Param`. Calling it checks the count of arguments and
+/// converts each to its parameter first, so every builtin rejects a call
+/// the same way, with `IncorrectNumberOfArguments` or with an
+/// `UnsupportedArgument` giving the position and the types it takes.
+trait Native<Params> {
+    fn call(
+        &self,
+        function: &'static str,
+        evaluator: &mut Evaluator,
+        args: Vec<Object>,
+    ) -> Result<Object, MonkeyError>;
+}
+
+macro_rules! impl_native {
+    ($($param:ident: $type:ident),+) => {
+        impl<F, $($type: Param),+> Native<($($type,)+)> for F
+        where
+            F: Fn(&mut Evaluator, $($type),+) -> Result<Object, MonkeyError>,
+        {
+            fn call(
+                &self,
+                function: &'static str,
+                evaluator: &mut Evaluator,
+                args: Vec<Object>,
+            ) -> Result<Object, MonkeyError> {
+                let fewest = 0 $(+ $type::ARITY.0)+;
+                let most = [$($type::ARITY.1),+].into_iter().sum();
+                let mut args = Arguments::new(function, args, fewest, most)?;
+                $(let $param = args.take::<$type>()?;)+
+                self(evaluator, $($param),+)
+            }
+        }
+    };
+}
+
+impl_native!(a: A);
+impl_native!(a: A, b: B);
+impl_native!(a: A, b: B, c: C);
+
+/// the arguments of a call that parameters have yet to take
+struct Arguments {
+    function: &'static str,
+    taken: usize,
+    args: vec::IntoIter<Object>,
+}
+
+impl Arguments {
+    /// fails unless there are from `fewest` to `most` of `args`
+    fn new(
+        function: &'static str,
+        args: Vec<Object>,
+        fewest: usize,
+        most: Option<usize>,
+    ) -> Result<Self, MonkeyError> {
+        let actual = args.len();
+        let expected = match most {
+            _ if actual < fewest => Some(fewest),
+            Some(most) if actual > most => Some(most),
+            _ => None,
+        };
+        if let Some(expected) = expected {
+            return Err(MonkeyError::IncorrectNumberOfArguments { expected, actual });
+        }
+        Ok(Arguments {
+            function,
+            taken: 0,
+            args: args.into_iter(),
+        })
+    }
+
+    fn take<P: Param>(&mut self) -> Result<P, MonkeyError> {
+        let position = self.taken + 1;
+        let left = self.args.len();
+        let param = P::take(&mut self.args);
+        self.taken += left - self.args.len();
+        param.map_err(|arg| unsupported(self.function, position, P::EXPECTED, &arg))
+    }
+}
+
+/// What a builtin's parameter is in Rust, and which arguments convert to
+/// it.
+trait Param: Sized {
+    /// the fewest and the most arguments it takes, `None` for no limit
+    const ARITY: (usize, Option<usize>) = (1, Some(1));
+    /// the types of argument it takes, to name when one is not; empty when
+    /// it takes anything
+    const EXPECTED: &'static [ObjectType];
+
+    /// the argument as the parameter, or the argument back when it is not
+    /// of an `EXPECTED` type
+    fn from_object(arg: Object) -> Result<Self, Object>;
+
+    /// Takes what the parameter stands for off the front of `args`. The
+    /// count was checked, so there is an argument for it.
+    fn take(args: &mut vec::IntoIter<Object>) -> Result<Self, Object> {
+        args.next().map_or(Err(Object::Null), Self::from_object)
+    }
+}
+
+/// the argument at `position`, counting from 1, is not of a type in
+/// `expected`
+fn unsupported(
+    function: &'static str,
+    position: usize,
+    expected: &'static [ObjectType],
+    arg: &Object,
+) -> MonkeyError {
+    MonkeyError::UnsupportedArgument {
+        function,
+        position,
+        expected,
+        argument: arg.object_type(),
+    }
+}
+
+impl Param for Object {
+    const EXPECTED: &'static [ObjectType] = &[];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        Ok(arg)
+    }
+}
+
+impl Param for i64 {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Integer];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Integer(int) => Ok(int),
+            arg => Err(arg),
+        }
+    }
+}
+
+impl Param for String {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::String];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::String(string) => Ok(string),
+            arg => Err(arg),
+        }
+    }
+}
+
+impl Param for Vec<Object> {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Array];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Array(elements) => Ok(elements),
+            arg => Err(arg),
+        }
+    }
+}
+
+impl Param for Pairs {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Hash];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Hash(pairs) => Ok(pairs),
+            arg => Err(arg),
+        }
+    }
+}
+
+/// a trailing parameter that may be left out
+impl<P: Param> Param for Option<P> {
+    const ARITY: (usize, Option<usize>) = (0, P::ARITY.1);
+    const EXPECTED: &'static [ObjectType] = P::EXPECTED;
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        P::from_object(arg).map(Some)
+    }
+
+    fn take(args: &mut vec::IntoIter<Object>) -> Result<Self, Object> {
+        match args.len() {
+            0 => Ok(None),
+            _ => P::take(args).map(Some),
+        }
+    }
+}
+
+/// every argument left, whatever they are
+struct Rest(Vec<Object>);
+
+impl Param for Rest {
+    const ARITY: (usize, Option<usize>) = (0, None);
+    const EXPECTED: &'static [ObjectType] = &[];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        Ok(Rest(vec![arg]))
+    }
+
+    fn take(args: &mut vec::IntoIter<Object>) -> Result<Self, Object> {
+        Ok(Rest(args.collect()))
+    }
+}
+
+/// a string, or a char as the string of just that char
+struct Text(String);
+
+impl Param for Text {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::String, ObjectType::Char];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::String(string) => Ok(Text(string)),
+            Object::Char(c) => Ok(Text(c.to_string())),
+            arg => Err(arg),
+        }
+    }
+}
+
+/// a function or a builtin, to call back
+struct Callable(Object);
+
+impl Param for Callable {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Function];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            callable @ (Object::Function { .. } | Object::Builtin(_)) => Ok(Callable(callable)),
+            arg => Err(arg),
+        }
+    }
+}
+
+/// anything `for` can walk, see `Iterable`
+struct Items(Object);
+
+impl Items {
+    fn items(&self) -> impl Iterator<Item = Object> + '_ {
+        self.0.items().into_iter().flatten()
+    }
+}
+
+impl Param for Items {
+    const EXPECTED: &'static [ObjectType] =
+        &[ObjectType::Array, ObjectType::String, ObjectType::Hash];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        if arg.items().is_some() {
+            Ok(Items(arg))
+        } else {
+            Err(arg)
+        }
+    }
+}
+
+/// what `len` counts of a string, an array or a hash
+struct Size(usize);
+
+impl Param for Size {
+    const EXPECTED: &'static [ObjectType] =
+        &[ObjectType::String, ObjectType::Array, ObjectType::Hash];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        arg.size().map(Size).ok_or(arg)
+    }
+}
+
+enum Sequence {
+    Array(Vec<Object>),
+    String(String),
+}
+
+impl Param for Sequence {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Array, ObjectType::String];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Array(elements) => Ok(Sequence::Array(elements)),
+            Object::String(string) => Ok(Sequence::String(string)),
+            arg => Err(arg),
+        }
+    }
+}
+
+enum Container {
+    Array(Vec<Object>),
+    Hash(Pairs),
+}
+
+impl Param for Container {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Array, ObjectType::Hash];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Array(elements) => Ok(Container::Array(elements)),
+            Object::Hash(pairs) => Ok(Container::Hash(pairs)),
+            arg => Err(arg),
+        }
+    }
+}
+
+/// an integer of either representation, for the builtins that format one
+enum Number {
+    Small(i64),
+    #[cfg(feature = "bigint")]
+    Big(num_bigint::BigInt),
+}
+
+impl Param for Number {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Integer];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Integer(n) => Ok(Number::Small(n)),
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(n) => Ok(Number::Big(n)),
+            arg => Err(arg),
+        }
+    }
+}
+
+/// the fill of a pad builtin, a char or a string that must be of one
+enum Fill {
+    Char(char),
+    String(String),
+}
+
+impl Fill {
+    fn char(self) -> Result<char, MonkeyError> {
+        match self {
+            Fill::Char(fill) => Ok(fill),
+            Fill::String(fill) => {
+                let mut chars = fill.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(MonkeyError::InvalidFill(fill)),
+                }
+            }
+        }
+    }
+}
+
+impl Param for Fill {
+    const EXPECTED: &'static [ObjectType] = &[ObjectType::Char, ObjectType::String];
+
+    fn from_object(arg: Object) -> Result<Self, Object> {
+        match arg {
+            Object::Char(fill) => Ok(Fill::Char(fill)),
+            Object::String(fill) => Ok(Fill::String(fill)),
+            arg => Err(arg),
+        }
+    }
+}
+
+/// `error(message)` raises `message`, to be caught by `try`/`catch`
+fn error(_: &mut Evaluator, message: Object) -> Result<Object, MonkeyError> {
+    Err(MonkeyError::Raised(message.to_string()))
+}
+
+/// `len(x)`: chars in a string, elements in an array or pairs in a hash
+fn len(_: &mut Evaluator, Size(size): Size) -> Result<Object, MonkeyError> {
+    Ok(Object::Integer(size as i64))
+}
+
+/// `is_empty(x)`, whether `len(x)` is 0
+fn is_empty(_: &mut Evaluator, Size(size): Size) -> Result<Object, MonkeyError> {
+    Ok(Object::Bool(size == 0))
+}
+
+/// `first(array)`, null when it is empty
+fn first(_: &mut Evaluator, elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    Ok(elements.into_iter().next().unwrap_or(Object::Null))
+}
+
+/// `last(array)`, null when it is empty
+fn last(_: &mut Evaluator, mut elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    Ok(elements.pop().unwrap_or(Object::Null))
+}
+
+/// `rest(array)`, a new array of all but the first element; null when it is
+/// empty
+fn rest(_: &mut Evaluator, mut elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    if elements.is_empty() {
+        return Ok(Object::Null);
+    }
+    elements.remove(0);
+    Ok(Object::Array(elements))
+}
+
+/// `push(array, element)`, a new array with `element` appended
+fn push(
+    _: &mut Evaluator,
+    mut elements: Vec<Object>,
+    element: Object,
+) -> Result<Object, MonkeyError> {
+    elements.push(element);
+    Ok(Object::Array(elements))
+}
+
+/// `with(array, index, value)` or `with(hash, key, value)`, a new one with
+/// the element at `index`, which must exist, or the pair for `key` set to
+/// `value`
+fn with(
+    _: &mut Evaluator,
+    container: Container,
+    key: Object,
+    value: Object,
+) -> Result<Object, MonkeyError> {
+    match (container, key) {
+        (Container::Array(mut elements), Object::Integer(index)) => {
+            let position = position(index, elements.len())?;
+            elements[position] = value;
+            Ok(Object::Array(elements))
+        }
+        (Container::Array(_), index) => Err(unsupported("with", 2, i64::EXPECTED, &index)),
+        (Container::Hash(mut pairs), key) => {
+            insert_pair(&mut pairs, key.hash_key()?, value)?;
+            Ok(Object::Hash(pairs))
+        }
+    }
+}
+
+/// `swap(array, i, j)`, a new array with the elements at `i` and `j`
+/// exchanged
+fn swap(
+    _: &mut Evaluator,
+    mut elements: Vec<Object>,
+    i: i64,
+    j: i64,
+) -> Result<Object, MonkeyError> {
+    let i = position(i, elements.len())?;
+    let j = position(j, elements.len())?;
+    elements.swap(i, j);
+    Ok(Object::Array(elements))
+}
+
+/// where `index` is in an array of `length` elements, counting from the end
+/// when negative as indexing does
+fn position(index: i64, length: usize) -> Result<usize, MonkeyError> {
+    super::resolve_index(index, length).ok_or(MonkeyError::IndexOutOfRange { index, length })
+}
+
+/// `take(array, n)`, a new array of the first `n` elements, like
+/// `array[:n]`
+fn take(_: &mut Evaluator, mut elements: Vec<Object>, n: i64) -> Result<Object, MonkeyError> {
+    let (_, end) = super::slice_bounds(None, Some(n), elements.len());
+    elements.truncate(end);
+    Ok(Object::Array(elements))
+}
+
+/// `drop(array, n)`, a new array of all but the first `n` elements, like
+/// `array[n:]`
+fn drop(_: &mut Evaluator, mut elements: Vec<Object>, n: i64) -> Result<Object, MonkeyError> {
+    let (start, _) = super::slice_bounds(Some(n), None, elements.len());
+    Ok(Object::Array(elements.split_off(start)))
+}
+
+/// `chunk(array, n)`, an array of arrays of `n` elements each, but for the
+/// last that may have fewer
+fn chunk(_: &mut Evaluator, elements: Vec<Object>, n: i64) -> Result<Object, MonkeyError> {
+    let size = usize::try_from(n)
+        .ok()
+        .filter(|size| *size > 0)
+        .ok_or(MonkeyError::InvalidChunkSize(n))?;
+    Ok(Object::Array(
+        elements
+            .chunks(size)
+            .map(|chunk| Object::Array(chunk.to_vec()))
+            .collect(),
+    ))
+}
+
+/// `puts(a, b, ...)` writes each argument on a line of its own to the
+/// evaluator's output
+fn puts(evaluator: &mut Evaluator, Rest(args): Rest) -> Result<Object, MonkeyError> {
+    for arg in args {
+        evaluator.write_output(&format!("{}\n", arg))?;
+    }
+    Ok(Object::Null)
+}
+
+/// `dbg(x)` writes `x` as `inspect` shows it, after `[dbg] `, to the
+/// evaluator's debug output and gives `x` back
+fn dbg(evaluator: &mut Evaluator, arg: Object) -> Result<Object, MonkeyError> {
+    evaluator.write_debug_output(&format!("[dbg] {}\n", arg.inspect()))?;
+    Ok(arg)
+}
+
+/// `map(iterable, f)`, a new array of `f(item)` for each item
+fn map(
+    evaluator: &mut Evaluator,
+    iterable: Items,
+    Callable(f): Callable,
+) -> Result<Object, MonkeyError> {
+    let elements = iterable
+        .items()
+        .map(|item| evaluator.apply_function(f.clone(), vec![item]))
+        .collect::<Result<_, _>>()?;
+    Ok(Object::Array(elements))
+}
+
+/// `filter(iterable, f)`, a new array of the items for which `f(item)`
+/// holds, judged like an `if` condition
+fn filter(
+    evaluator: &mut Evaluator,
+    iterable: Items,
+    Callable(f): Callable,
+) -> Result<Object, MonkeyError> {
+    let mut kept = vec![];
+    for item in iterable.items() {
+        let keep = evaluator.apply_function(f.clone(), vec![item.clone()])?;
+        if evaluator.eval_condition(&keep)? {
+            kept.push(item);
+        }
+    }
+    Ok(Object::Array(kept))
+}
+
+/// `reduce(iterable, initial, f)`, folding each item into the accumulator
+/// with `f(accumulator, item)`, in order
+fn reduce(
+    evaluator: &mut Evaluator,
+    iterable: Items,
+    initial: Object,
+    Callable(f): Callable,
+) -> Result<Object, MonkeyError> {
+    iterable.items().try_fold(initial, |accumulator, item| {
+        evaluator.apply_function(f.clone(), vec![accumulator, item])
+    })
+}
+
+/// `sort(array)` in the order of `<`, or `sort(array, f)` where `f(a, b)`
+/// returns a negative integer when `a` goes first, a positive one when `b`
+/// does and zero when either may. Either way the sort is stable and gives
+/// a new array. Without `f`, elements `<` cannot order fail with both of
+/// their types and their indexes in `array`.
+fn sort(
+    evaluator: &mut Evaluator,
+    elements: Vec<Object>,
+    comparator: Option<Callable>,
+) -> Result<Object, MonkeyError> {
+    let indexed = elements.into_iter().enumerate().collect();
+    let sorted = merge_sort(indexed, &mut |(i, a), (j, b)| match &comparator {
+        None => a.compare(b).ok_or_else(|| MonkeyError::Incomparable {
+            left: a.object_type(),
+            left_index: *i,
+            right: b.object_type(),
+            right_index: *j,
+        }),
+        Some(Callable(comparator)) => {
+            match evaluator.apply_function(comparator.clone(), vec![a.clone(), b.clone()])? {
+                Object::Integer(order) => Ok(order.cmp(&0)),
+                order => Err(MonkeyError::InvalidComparator(order.object_type())),
+            }
+        }
+    })?;
+    Ok(Object::Array(
+        sorted.into_iter().map(|(_, obj)| obj).collect(),
+    ))
+}
+
+/// stable, and unlike `slice::sort_by` it stops at the first failed
+/// comparison and tolerates a comparator that is not a total order; each
+/// element goes with its index in the array being sorted
+fn merge_sort(
+    mut items: Vec<(usize, Object)>,
+    compare: &mut impl FnMut(&(usize, Object), &(usize, Object)) -> Result<Ordering, MonkeyError>,
+) -> Result<Vec<(usize, Object)>, MonkeyError> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let next = if compare(a, b)?.is_gt() {
+            right.next()
+        } else {
+            left.next()
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+/// `sum(array)` of integers, 0 when it is empty; it overflows like `+`
+fn sum(evaluator: &mut Evaluator, elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    integers("sum", elements)?
+        .into_iter()
+        .try_fold(Object::Integer(0), |sum, int| {
+            evaluator.eval_infix_expression(&ast::Infix::Plus, sum, int)
+        })
+}
+
+/// `product(array)` of integers, 1 when it is empty; it overflows like `*`
+fn product(evaluator: &mut Evaluator, elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    integers("product", elements)?
+        .into_iter()
+        .try_fold(Object::Integer(1), |product, int| {
+            evaluator.eval_infix_expression(&ast::Infix::Asterisk, product, int)
+        })
+}
+
+/// `min_of(array)`, its least integer; null when it is empty
+fn min_of(_: &mut Evaluator, elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    Ok(extreme(integers("min_of", elements)?, Ordering::Less))
+}
+
+/// `max_of(array)`, its greatest integer; null when it is empty
+fn max_of(_: &mut Evaluator, elements: Vec<Object>) -> Result<Object, MonkeyError> {
+    Ok(extreme(integers("max_of", elements)?, Ordering::Greater))
+}
+
+/// `elements`, which must all be integers
+fn integers(function: &'static str, elements: Vec<Object>) -> Result<Vec<Object>, MonkeyError> {
+    match elements
+        .iter()
+        .position(|element| element.object_type() != ObjectType::Integer)
+    {
+        Some(index) => Err(MonkeyError::NonNumericElement {
+            function,
+            index,
+            element: elements[index].object_type(),
+        }),
+        None => Ok(elements),
+    }
+}
+
+/// the least of `integers` for `Ordering::Less`, the greatest for
+/// `Ordering::Greater`
+fn extreme(integers: Vec<Object>, ordering: Ordering) -> Object {
+    integers
+        .into_iter()
+        .reduce(|best, int| {
+            if int.compare(&best) == Some(ordering) {
+                int
+            } else {
+                best
+            }
+        })
+        .unwrap_or(Object::Null)
+}
+
+/// `reverse(array)` or `reverse(string)`, a new one with the elements or
+/// chars in reverse order
+fn reverse(_: &mut Evaluator, sequence: Sequence) -> Result<Object, MonkeyError> {
+    match sequence {
+        Sequence::Array(mut elements) => {
+            elements.reverse();
+            Ok(Object::Array(elements))
+        }
+        Sequence::String(string) => Ok(Object::String(string.chars().rev().collect())),
+    }
+}
+
+/// `contains(array, x)`, whether an element of `array` is `== x`, or
+/// `contains(string, substring)`
+fn contains(_: &mut Evaluator, haystack: Sequence, needle: Object) -> Result<Object, MonkeyError> {
+    match (haystack, needle) {
+        (Sequence::String(string), Object::String(substring)) => {
+            Ok(Object::Bool(string.contains(substring.as_str())))
+        }
+        (Sequence::String(_), needle) => Err(unsupported("contains", 2, String::EXPECTED, &needle)),
+        (Sequence::Array(elements), needle) => {
+            for element in &elements {
+                if super::equals(element, &needle)? {
+                    return Ok(Object::Bool(true));
+                }
+            }
+            Ok(Object::Bool(false))
+        }
+    }
+}
+
+/// `keys(hash)`, an array of its keys, sorted like the hash displays
+fn keys(_: &mut Evaluator, pairs: Pairs) -> Result<Object, MonkeyError> {
+    Ok(Object::Array(
+        sorted_pairs(pairs)
+            .into_iter()
+            .map(|(key, _)| key.into())
+            .collect(),
+    ))
+}
+
+/// `values(hash)`, an array of its values, in the order of `keys(hash)`
+fn values(_: &mut Evaluator, pairs: Pairs) -> Result<Object, MonkeyError> {
+    Ok(Object::Array(
+        sorted_pairs(pairs)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect(),
+    ))
+}
+
+/// `has_key(hash, key)`, whether `hash` has a pair for `key`
+fn has_key(_: &mut Evaluator, pairs: Pairs, key: Object) -> Result<Object, MonkeyError> {
+    Ok(Object::Bool(pairs.contains_key(&key.hash_key()?)))
+}
+
+/// `delete(hash, key)`, a new hash without the pair for `key`, if it has one
+fn delete(_: &mut Evaluator, mut pairs: Pairs, key: Object) -> Result<Object, MonkeyError> {
+    pairs.remove(&key.hash_key()?);
+    Ok(Object::Hash(pairs))
+}
+
+/// `entries(hash)`, an array of its `[key, value]` pairs, in the order of
+/// `keys(hash)`
+fn entries(_: &mut Evaluator, pairs: Pairs) -> Result<Object, MonkeyError> {
+    Ok(Object::Array(
+        sorted_pairs(pairs)
+            .into_iter()
+            .map(|(key, value)| Object::Array(vec![key.into(), value]))
+            .collect(),
+    ))
+}
+
+/// `from_entries(array)`, a hash of the `[key, value]` pairs of `array`; a
+/// later pair for a key replaces an earlier one
+fn from_entries(evaluator: &mut Evaluator, entries: Vec<Object>) -> Result<Object, MonkeyError> {
+    let mut pairs = HashMap::with_hasher(evaluator.hash_state);
+    for (index, entry) in entries.into_iter().enumerate() {
+        let malformed = || MonkeyError::MalformedEntry {
+            index,
+            entry: entry.to_string(),
+        };
+        match &entry {
+            Object::Array(pair) if pair.len() == 2 => {
+                let key = pair[0].hash_key().map_err(|_| malformed())?;
+                insert_pair(&mut pairs, key, pair[1].clone())?;
+            }
+            _ => return Err(malformed()),
+        }
+    }
+    Ok(Object::Hash(pairs))
+}
+
+fn sorted_pairs(pairs: Pairs) -> Vec<(HashKey, Object)> {
+    let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    pairs
+}
+
+/// `int(x)` of an integer, a bool (1 or 0) or a string of decimal digits
+/// with an optional sign
+fn int(evaluator: &mut Evaluator, arg: Object) -> Result<Object, MonkeyError> {
+    match arg {
+        int @ Object::Integer(_) => Ok(int),
+        #[cfg(feature = "bigint")]
+        int @ Object::BigInteger(_) => Ok(int),
+        Object::Bool(value) => Ok(Object::Integer(value.into())),
+        Object::String(text) => match text.parse::<i64>() {
+            Ok(int) => Ok(Object::Integer(int)),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+                ) =>
+            {
+                parse_big_integer(evaluator, &text)
+            }
+            Err(_) => Err(MonkeyError::NotAnInteger(text)),
+        },
+        arg => Err(unsupported(
+            "int",
+            1,
+            &[ObjectType::Integer, ObjectType::Bool, ObjectType::String],
+            &arg,
+        )),
+    }
+}
+
+#[cfg(feature = "bigint")]
+fn parse_big_integer(evaluator: &Evaluator, text: &str) -> Result<Object, MonkeyError> {
+    if evaluator.bigint {
+        super::bigint::parse_literal(text)
+    } else {
+        Err(MonkeyError::IntegerOverflow)
+    }
+}
+
+#[cfg(not(feature = "bigint"))]
+fn parse_big_integer(_: &Evaluator, _text: &str) -> Result<Object, MonkeyError> {
+    Err(MonkeyError::IntegerOverflow)
+}
+
+/// `str(x)`, `x` as it displays
+fn str(_: &mut Evaluator, arg: Object) -> Result<Object, MonkeyError> {
+    Ok(Object::String(arg.to_string()))
+}
+
+/// `bool(x)`, whether `x` is truthy
+fn bool(_: &mut Evaluator, arg: Object) -> Result<Object, MonkeyError> {
+    Ok(Object::Bool(arg.is_truthy()))
+}
+
+/// `split(string, separator)`, an array of the strings between each
+/// `separator`, or of the chars `for` would walk when `separator` is `""`
+fn split(_: &mut Evaluator, string: String, separator: String) -> Result<Object, MonkeyError> {
+    if separator.is_empty() {
+        return Ok(Object::Array(string.chars().map(Object::Char).collect()));
+    }
+    Ok(Object::Array(
+        string
+            .split(separator.as_str())
+            .map(|part| Object::String(part.to_owned()))
+            .collect(),
+    ))
+}
+
+/// `join(array, separator)`, one string of the elements as they display,
+/// with `separator` between each
+fn join(
+    _: &mut Evaluator,
+    elements: Vec<Object>,
+    separator: String,
+) -> Result<Object, MonkeyError> {
+    Ok(Object::String(
+        elements
+            .iter()
+            .map(|element| element.to_string())
+            .collect::<Vec<_>>()
+            .join(&separator),
+    ))
+}
+
+/// `upper(string)`, with Unicode's uppercase mapping, so `"ß"` becomes `"SS"`
+fn upper(_: &mut Evaluator, Text(string): Text) -> Result<Object, MonkeyError> {
+    Ok(Object::String(string.to_uppercase()))
+}
+
+/// `lower(string)`, with Unicode's lowercase mapping
+fn lower(_: &mut Evaluator, Text(string): Text) -> Result<Object, MonkeyError> {
+    Ok(Object::String(string.to_lowercase()))
+}
+
+/// `trim(string)`, without leading and trailing whitespace
+fn trim(_: &mut Evaluator, Text(string): Text) -> Result<Object, MonkeyError> {
+    Ok(Object::String(string.trim().to_owned()))
+}
+
+/// `trim_start(string)`, without leading whitespace
+fn trim_start(_: &mut Evaluator, Text(string): Text) -> Result<Object, MonkeyError> {
+    Ok(Object::String(string.trim_start().to_owned()))
+}
+
+/// `trim_end(string)`, without trailing whitespace
+fn trim_end(_: &mut Evaluator, Text(string): Text) -> Result<Object, MonkeyError> {
+    Ok(Object::String(string.trim_end().to_owned()))
+}
+
+/// `index_of(string, substring)`, in chars like string indexing, of the
+/// first occurrence; -1 when there is none. `index_of(string, substring,
+/// start)` looks from char `start` on, a bound like that of `string[start:]`.
+fn index_of(
+    _: &mut Evaluator,
+    string: String,
+    substring: String,
+    start: Option<i64>,
+) -> Result<Object, MonkeyError> {
+    let (start, _) = super::slice_bounds(Some(start.unwrap_or(0)), None, string.chars().count());
+    let offset = string
+        .char_indices()
+        .nth(start)
+        .map_or(string.len(), |(at, _)| at);
+    Ok(Object::Integer(
+        match string[offset..].find(substring.as_str()) {
+            Some(at) => string[..offset + at].chars().count() as i64,
+            None => -1,
+        },
+    ))
+}
+
+/// `count(string, substring)`, how many occurrences of `substring` there
+/// are, scanning like `replace`; `""` occurs around every char
+fn count(_: &mut Evaluator, string: String, substring: String) -> Result<Object, MonkeyError> {
+    Ok(Object::Integer(
+        string.matches(substring.as_str()).count() as i64
+    ))
+}
+
+/// `split_n(string, separator, n)`, like `split` but splitting at most `n`
+/// times, so the last string keeps any further separators; not at all when
+/// `n` is not positive. With `""` the pieces split off are chars, as for
+/// `split`, and whatever is left over stays a string.
+fn split_n(
+    _: &mut Evaluator,
+    string: String,
+    separator: String,
+    n: i64,
+) -> Result<Object, MonkeyError> {
+    let pieces = usize::try_from(n).unwrap_or(0).saturating_add(1);
+    let parts = if separator.is_empty() {
+        let mut chars = string.chars();
+        let mut parts = chars
+            .by_ref()
+            .take(pieces - 1)
+            .map(Object::Char)
+            .collect::<Vec<_>>();
+        let rest = chars.as_str();
+        if !rest.is_empty() || pieces == 1 {
+            parts.push(Object::String(rest.to_owned()));
+        }
+        parts
+    } else {
+        string
+            .splitn(pieces, separator.as_str())
+            .map(|part| Object::String(part.to_owned()))
+            .collect()
+    };
+    Ok(Object::Array(parts))
+}
+
+/// `replace(string, from, to)`, a new string with every occurrence of
+/// `from` replaced by `to`, scanning left to right so occurrences never
+/// overlap. An empty `from` occurs around every char.
+fn replace(
+    _: &mut Evaluator,
+    string: String,
+    from: String,
+    to: String,
+) -> Result<Object, MonkeyError> {
+    Ok(Object::String(string.replace(from.as_str(), &to)))
+}
+
+/// `format(template, a, b, ...)`, `template` with each `{}` replaced by
+/// the next argument as it displays, and `{{` and `}}` by a literal brace
+fn format(_: &mut Evaluator, template: String, Rest(args): Rest) -> Result<Object, MonkeyError> {
+    let mut result = String::new();
+    let mut next = args.iter();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(arg) = next.next() {
+                    result.push_str(&arg.to_string());
+                }
+            }
+            ('{' | '}', _) => return Err(MonkeyError::UnmatchedBrace(template)),
+            _ => result.push(c),
+        }
+    }
+    if placeholders != args.len() {
+        return Err(MonkeyError::FormatArgumentMismatch {
+            placeholders,
+            arguments: args.len(),
+        });
+    }
+    Ok(Object::String(result))
+}
+
+/// `pad_start(string, width)` or `pad_start(string, width, fill)`, with
+/// `fill` (a space by default) prepended until it is `width` chars long
+fn pad_start(
+    _: &mut Evaluator,
+    string: String,
+    width: i64,
+    fill: Option<Fill>,
+) -> Result<Object, MonkeyError> {
+    let padding = padding(&string, width, fill)?;
+    Ok(Object::String(padding + &string))
+}
+
+/// `pad_end(string, width)` or `pad_end(string, width, fill)`, like
+/// `pad_start` but appending
+fn pad_end(
+    _: &mut Evaluator,
+    string: String,
+    width: i64,
+    fill: Option<Fill>,
+) -> Result<Object, MonkeyError> {
+    let padding = padding(&string, width, fill)?;
+    Ok(Object::String(string + &padding))
+}
+
+/// the fill a pad builtin adds to `string`
+fn padding(string: &str, width: i64, fill: Option<Fill>) -> Result<String, MonkeyError> {
+    let fill = fill.unwrap_or(Fill::Char(' ')).char()?;
+    fill_to(fill, width, string.chars().count())
+}
+
+/// as many `fill` as a string of `length` chars lacks to be `width` long
+fn fill_to(fill: char, width: i64, length: usize) -> Result<String, MonkeyError> {
+    let missing = usize::try_from(width).unwrap_or(0).saturating_sub(length);
+    limit_chars(length as u128 + missing as u128)?;
+    Ok(std::iter::repeat_n(fill, missing).collect())
+}
+
+/// `repeat(string, n)`, `n` copies of `string` one after the other; `""`
+/// when `n` is not positive
+fn repeat(_: &mut Evaluator, string: String, n: i64) -> Result<Object, MonkeyError> {
+    let n = usize::try_from(n).unwrap_or(0);
+    limit_chars(string.chars().count() as u128 * n as u128)?;
+    Ok(Object::String(string.repeat(n)))
+}
+
+/// fails when a string of `length` chars would be over `MAX_STRING_CHARS`
+fn limit_chars(length: u128) -> Result<(), MonkeyError> {
+    if length > MAX_STRING_CHARS as u128 {
+        return Err(MonkeyError::StringTooLong {
+            length,
+            limit: MAX_STRING_CHARS,
+        });
+    }
+    Ok(())
+}
+
+/// `to_fixed(x, digits)`, `x` with `digits` decimal places. With only
+/// integers to format, the places are zeros.
+fn to_fixed(_: &mut Evaluator, x: Number, digits: i64) -> Result<Object, MonkeyError> {
+    if !(0..=MAX_FIXED_DIGITS).contains(&digits) {
+        return Err(MonkeyError::InvalidDigits {
+            digits,
+            limit: MAX_FIXED_DIGITS,
+        });
+    }
+    let (sign, integer) = sign_and_digits(&x, 10);
+    Ok(Object::String(match digits {
+        0 => format!("{}{}", sign, integer),
+        _ => format!("{}{}.{}", sign, integer, "0".repeat(digits as usize)),
+    }))
+}
+
+/// `to_hex(n)` in lowercase digits, with a `-` when negative
+fn to_hex(_: &mut Evaluator, n: Number) -> Result<Object, MonkeyError> {
+    Ok(in_radix(&n, 16))
+}
+
+/// `to_bin(n)`, with a `-` when negative
+fn to_bin(_: &mut Evaluator, n: Number) -> Result<Object, MonkeyError> {
+    Ok(in_radix(&n, 2))
+}
+
+/// `to_oct(n)`, with a `-` when negative
+fn to_oct(_: &mut Evaluator, n: Number) -> Result<Object, MonkeyError> {
+    Ok(in_radix(&n, 8))
+}
+
+fn in_radix(n: &Number, radix: u32) -> Object {
+    let (sign, digits) = sign_and_digits(n, radix);
+    Object::String(format!("{}{}", sign, digits))
+}
+
+/// `format_int(n, width)` or `format_int(n, width, fill)`, `n` with `fill`
+/// (`0` by default) before it until it is `width` chars long. Zeros go
+/// after the sign of a negative `n`, other fills before it.
+fn format_int(
+    _: &mut Evaluator,
+    n: Number,
+    width: i64,
+    fill: Option<Fill>,
+) -> Result<Object, MonkeyError> {
+    let (sign, digits) = sign_and_digits(&n, 10);
+    let fill = fill.unwrap_or(Fill::Char('0')).char()?;
+    let padding = fill_to(fill, width, sign.len() + digits.len())?;
+    Ok(Object::String(if fill == '0' {
+        format!("{}{}{}", sign, padding, digits)
+    } else {
+        format!("{}{}{}", padding, sign, digits)
+    }))
+}
+
+/// the sign, `-` or none, and the digits in `radix` of `n`
+fn sign_and_digits(n: &Number, radix: u32) -> (&'static str, String) {
+    let sign = |negative| if negative { "-" } else { "" };
+    match n {
+        Number::Small(n) => {
+            let magnitude = n.unsigned_abs();
+            let digits = match radix {
+                2 => format!("{:b}", magnitude),
+                8 => format!("{:o}", magnitude),
+                16 => format!("{:x}", magnitude),
+                _ => magnitude.to_string(),
+            };
+            (sign(*n < 0), digits)
+        }
+        #[cfg(feature = "bigint")]
+        Number::Big(n) => (
+            sign(n.sign() == num_bigint::Sign::Minus),
+            n.magnitude().to_str_radix(radix),
+        ),
+    }
+}
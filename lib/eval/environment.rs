@@ -1,28 +1,36 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::eval::object::Object;
+use crate::{error::MonkeyError, eval::object::Object};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Environment {
     // innner expand outer scope
     store: HashMap<String, Object>,
+    /// doc comments of documented bindings in this scope
+    docs: HashMap<String, String>,
     /// store references for other environment
     /// outer contains inner scope
     outer: Option<Rc<RefCell<Environment>>>,
+    /// bindings can no longer be defined or assigned by the program
+    frozen: bool,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
             store: HashMap::new(),
+            docs: HashMap::new(),
             outer: None,
+            frozen: false,
         }
     }
 
-    pub fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Self {
+    pub(crate) fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Self {
         Environment {
             store: HashMap::new(),
+            docs: HashMap::new(),
             outer: Some(outer),
+            frozen: false,
         }
     }
 
@@ -39,6 +47,65 @@ impl Environment {
     pub fn set(&mut self, key: String, val: Object) -> Option<Object> {
         self.store.insert(key, val)
     }
+
+    /// Make a `let` or an assignment of a binding of this scope fail with
+    /// `MonkeyError::EnvironmentFrozen`. The scopes of blocks and calls
+    /// evaluated in it are not frozen, and the host can still `set`.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub(crate) fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub(crate) fn outer(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.outer.clone()
+    }
+
+    /// `set` for a `let` of the program
+    pub(crate) fn define(&mut self, key: String, val: Object) -> Result<(), MonkeyError> {
+        if self.frozen {
+            return Err(MonkeyError::EnvironmentFrozen(key));
+        }
+        self.store.insert(key, val);
+        Ok(())
+    }
+
+    /// replaces `key` in the scope that defines it, returning the old value;
+    /// `None`, and nothing bound, when no scope does
+    pub(crate) fn assign(&mut self, key: &str, val: Object) -> Result<Option<Object>, MonkeyError> {
+        match self.store.get_mut(key) {
+            Some(_) if self.frozen => Err(MonkeyError::EnvironmentFrozen(key.to_owned())),
+            Some(obj) => Ok(Some(std::mem::replace(obj, val))),
+            None => match &self.outer {
+                Some(env) => env.borrow_mut().assign(key, val),
+                None => Ok(None),
+            },
+        }
+    }
+
+    pub(crate) fn get_doc(&self, key: &str) -> Option<String> {
+        match self.docs.get(key) {
+            Some(doc) => Some(doc.clone()),
+            None => match &self.outer {
+                Some(env) => env.borrow().get_doc(key),
+                None => None,
+            },
+        }
+    }
+
+    /// rebinding a name without a doc comment drops the old documentation
+    pub(crate) fn set_doc(&mut self, key: String, doc: Option<String>) {
+        match doc {
+            Some(doc) => self.docs.insert(key, doc),
+            None => self.docs.remove(&key),
+        };
+    }
 }
 
 impl Default for Environment {
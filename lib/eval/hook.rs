@@ -0,0 +1,45 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{eval::environment::Environment, parser::ast};
+
+/// What a statement hook wants the evaluator to do next.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum HookAction {
+    Continue,
+    /// stop evaluation with `MonkeyError::AbortedByHost(reason)`
+    Abort(String),
+}
+
+type HookFn = dyn FnMut(&ast::Statement, &Environment) -> HookAction;
+
+/// Host callback run before each statement, shared with the evaluators of
+/// function bodies so calls are observed too.
+#[derive(Clone)]
+pub(crate) struct StatementHook(Rc<RefCell<HookFn>>);
+
+impl StatementHook {
+    pub(crate) fn new(
+        hook: impl FnMut(&ast::Statement, &Environment) -> HookAction + 'static,
+    ) -> Self {
+        StatementHook(Rc::new(RefCell::new(hook)))
+    }
+
+    pub(crate) fn call(&self, stmt: &ast::Statement, env: &Environment) -> HookAction {
+        (self.0.borrow_mut())(stmt, env)
+    }
+}
+
+impl fmt::Debug for StatementHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StatementHook")
+    }
+}
+
+/// hooks are equal only when they are the same installed callback
+impl PartialEq for StatementHook {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StatementHook {}
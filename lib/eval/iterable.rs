@@ -0,0 +1,75 @@
+use crate::{
+    error::MonkeyError,
+    eval::object::{Object, ObjectType},
+};
+
+/// Something `for` and the builtins that walk their argument (`map`,
+/// `filter`, `reduce`) can step through one item at a time. A new iterable
+/// kind implements this rather than getting an arm in each of them.
+pub(crate) trait Iterable {
+    /// The items in order, produced as they are asked for; `None` when this
+    /// cannot be iterated.
+    fn items(&self) -> Option<Box<dyn Iterator<Item = Object> + '_>>;
+
+    /// what `NotIterable` names when `items` is `None`
+    fn object_type(&self) -> ObjectType;
+}
+
+impl Iterable for Object {
+    /// the elements of an array, the chars of a string or the `[key, value]`
+    /// pairs of a hash in key order
+    fn items(&self) -> Option<Box<dyn Iterator<Item = Object> + '_>> {
+        match self {
+            Object::Array(elements) => Some(Box::new(elements.iter().cloned())),
+            Object::String(string) => Some(Box::new(string.chars().map(Object::Char))),
+            Object::Hash(pairs) => {
+                let mut pairs = pairs.iter().collect::<Vec<_>>();
+                pairs.sort_by_key(|(key, _)| *key);
+                Some(Box::new(pairs.into_iter().map(|(key, value)| {
+                    Object::Array(vec![key.clone().into(), value.clone()])
+                })))
+            }
+            _ => None,
+        }
+    }
+
+    fn object_type(&self) -> ObjectType {
+        Object::object_type(self)
+    }
+}
+
+/// `start..end`, the integers from `start` up to but not including `end`;
+/// reversed bounds give none
+pub(crate) struct Range {
+    start: i64,
+    end: i64,
+}
+
+impl Range {
+    pub(crate) fn new(start: Object, end: Object) -> Result<Self, MonkeyError> {
+        match (start, end) {
+            (Object::Integer(start), Object::Integer(end)) => Ok(Range { start, end }),
+            (start, end) => Err(MonkeyError::InvalidRange {
+                start: start.object_type(),
+                end: end.object_type(),
+            }),
+        }
+    }
+
+    /// how many integers the range holds, which may not fit an `i64`
+    pub(crate) fn len(&self) -> u128 {
+        (self.end as i128 - self.start as i128).max(0) as u128
+    }
+}
+
+impl Iterable for Range {
+    /// counted out one at a time, so walking a range never builds it
+    fn items(&self) -> Option<Box<dyn Iterator<Item = Object> + '_>> {
+        Some(Box::new((self.start..self.end).map(Object::Integer)))
+    }
+
+    /// an array, as the range is when built
+    fn object_type(&self) -> ObjectType {
+        ObjectType::Array
+    }
+}
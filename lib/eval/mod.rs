@@ -1,32 +1,274 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+};
 
-use crate::eval::object::{Object, ObjectType};
+use crate::eval::object::{insert_pair, HashState, Object, ObjectType};
 use crate::{
-    error::MonkeyError,
-    eval::environment::Environment,
+    error::{Diagnostic, MonkeyError},
+    eval::{
+        builtins::Builtin,
+        environment::Environment,
+        hook::{HookAction, StatementHook},
+        iterable::{Iterable, Range},
+        output::Output,
+    },
     parser::ast::{self, Statement},
+    stack,
 };
 
+#[cfg(feature = "bigint")]
+mod bigint;
+pub mod builtins;
 pub mod environment;
+pub mod hook;
+mod iterable;
 pub mod object;
+mod output;
+
+/// How `if` treats a condition that is not a `Bool`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum Truthiness {
+    /// use `Object::is_truthy`
+    #[default]
+    Permissive,
+    /// like `Permissive`, but record a `Diagnostic`
+    Warn,
+    /// `MonkeyError::ConditionTypeMismatch`
+    Strict,
+}
+
+/// What a recoverable failure evaluates to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Policy {
+    /// the failure's `MonkeyError`
+    Error,
+    /// `Object::Null`, and evaluation carries on
+    Null,
+}
+
+/// Failure policies of an `Evaluator`. The default errors on everything
+/// but an out-of-range index, which has always been `null`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EvalOptions {
+    /// `x / 0` and `x % 0`
+    pub division_by_zero: Policy,
+    /// an array or string index past either end
+    pub index_out_of_range: Policy,
+    /// a name with no binding
+    pub missing_identifier: Policy,
+}
+
+impl Default for EvalOptions {
+    fn default() -> Self {
+        EvalOptions {
+            division_by_zero: Policy::Error,
+            index_out_of_range: Policy::Null,
+            missing_identifier: Policy::Error,
+        }
+    }
+}
+
+impl EvalOptions {
+    /// `null` for every recoverable failure, e.g. for templating hosts
+    pub fn lenient() -> Self {
+        EvalOptions {
+            division_by_zero: Policy::Null,
+            index_out_of_range: Policy::Null,
+            missing_identifier: Policy::Null,
+        }
+    }
+
+    /// `error`, or `null` when its policy says to recover from it
+    fn recover(&self, error: MonkeyError) -> Result<Object, MonkeyError> {
+        let policy = match error {
+            MonkeyError::DivisionByZero => self.division_by_zero,
+            MonkeyError::IndexOutOfRange { .. } => self.index_out_of_range,
+            MonkeyError::IdentifierNotFound(_) => self.missing_identifier,
+            _ => Policy::Error,
+        };
+        match policy {
+            Policy::Error => Err(error),
+            Policy::Null => Ok(Object::Null),
+        }
+    }
+}
+
+/// Deepest nesting of function calls. Each call recurses through the
+/// evaluator, so a cap keeps deep recursion like `1 + f(n - 1)` from
+/// running away; it fails with `CallStackExceeded` instead. Tail calls run
+/// in a loop and do not count. The evaluator grows its stack as it goes, so
+/// calls at the limit are safe on any thread, whatever its stack size.
+pub const MAX_CALL_DEPTH: usize = 128;
+
+/// the most elements a range builds an array of, so a script cannot make
+/// one exhaust memory by accident
+const MAX_RANGE_LEN: usize = 1 << 20;
+
+/// Why evaluation left a statement or expression without a value. It
+/// travels in the error position, so `?` carries it out to the construct
+/// that handles it: a loop for `break` and `continue`, a call for `return`,
+/// and the program for anything left. It never becomes an `Object`.
+#[derive(Debug)]
+enum Flow {
+    Error(MonkeyError),
+    Return(Box<Object>),
+    /// value given to `break`
+    Break(Box<Object>),
+    Continue,
+    /// a call in tail position, for `apply_function` to make once the body
+    /// it was in has been left
+    TailCall {
+        function: Box<Object>,
+        arguments: Vec<Object>,
+    },
+}
+
+impl From<MonkeyError> for Flow {
+    fn from(error: MonkeyError) -> Self {
+        Flow::Error(error)
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Evaluator {
     // Why do we need Rc & Refcell ?
     env: Rc<RefCell<Environment>>,
+    truthiness: Truthiness,
+    options: EvalOptions,
+    diagnostics: Vec<Diagnostic>,
+    on_statement: Option<StatementHook>,
+    /// where `puts` writes; stdout when unset
+    output: Option<Output>,
+    /// where `dbg` writes, stderr when unset
+    debug_output: Option<Output>,
+    /// inside a function body, where `return f(x)` can be a tail call
+    in_function: bool,
+    /// calls of functions under way, see `MAX_CALL_DEPTH`
+    call_depth: usize,
+    /// what the hashes this builds are keyed with
+    hash_state: HashState,
+    /// promote integers to arbitrary precision instead of erroring on overflow
+    #[cfg(feature = "bigint")]
+    bigint: bool,
 }
 
 impl Evaluator {
     pub fn new() -> Self {
-        Evaluator {
-            env: Rc::new(RefCell::new(Environment::new())),
-        }
+        Evaluator::from_env(Environment::new())
     }
 
     pub fn from_env(env: Environment) -> Self {
         Evaluator {
             env: Rc::new(RefCell::new(env.to_owned())),
+            truthiness: Truthiness::default(),
+            options: EvalOptions::default(),
+            diagnostics: vec![],
+            on_statement: None,
+            output: None,
+            debug_output: None,
+            in_function: false,
+            call_depth: 0,
+            hash_state: HashState::random(),
+            #[cfg(feature = "bigint")]
+            bigint: false,
+        }
+    }
+
+    /// evaluator for a function body, keeping this evaluator's settings
+    fn enclosed(&self, env: Environment) -> Self {
+        let mut evaluator = Evaluator::from_env(env);
+        evaluator.truthiness = self.truthiness;
+        evaluator.options = self.options;
+        evaluator.on_statement = self.on_statement.clone();
+        evaluator.output = self.output.clone();
+        evaluator.debug_output = self.debug_output.clone();
+        evaluator.in_function = self.in_function;
+        evaluator.call_depth = self.call_depth;
+        evaluator.hash_state = self.hash_state;
+        #[cfg(feature = "bigint")]
+        evaluator.set_bigint(self.bigint);
+        evaluator
+    }
+
+    pub fn set_truthiness(&mut self, truthiness: Truthiness) {
+        self.truthiness = truthiness;
+    }
+
+    pub fn set_options(&mut self, options: EvalOptions) {
+        self.options = options;
+    }
+
+    /// Install a hook called before every statement, including statements
+    /// inside blocks and function bodies (but not the blocks themselves).
+    /// Returning `HookAction::Abort` stops evaluation with
+    /// `MonkeyError::AbortedByHost`. Replaces any previous hook.
+    pub fn on_statement(
+        &mut self,
+        hook: impl FnMut(&ast::Statement, &Environment) -> HookAction + 'static,
+    ) {
+        self.on_statement = Some(StatementHook::new(hook));
+    }
+
+    /// Bind `name` to a function of the host's, called with its arguments
+    /// evaluated; an error it returns fails the call like any other. Like a
+    /// `let`, it replaces an earlier binding of `name`.
+    pub fn register_builtin(
+        &mut self,
+        name: &str,
+        func: impl Fn(Vec<Object>) -> Result<Object, MonkeyError> + 'static,
+    ) {
+        let builtin = Builtin::host(name.to_string(), Rc::new(func));
+        self.set(name.to_string(), Object::Builtin(builtin));
+    }
+
+    /// Send what `puts` prints to `output` instead of stdout. Keep a clone
+    /// of the `Rc` to read it back, e.g. from an `Rc<RefCell<Vec<u8>>>`.
+    pub fn set_output<W: Write + 'static>(&mut self, output: Rc<RefCell<W>>) {
+        self.output = Some(Output::new(output));
+    }
+
+    pub(crate) fn write_output(&self, text: &str) -> Result<(), MonkeyError> {
+        match &self.output {
+            Some(output) => output.write(text),
+            None => io::stdout().write_all(text.as_bytes()),
+        }
+        .map_err(|e| MonkeyError::OutputFailed(e.to_string()))
+    }
+
+    /// Send what `dbg` traces to `output` instead of stderr, apart from what
+    /// the program prints.
+    pub fn set_debug_output<W: Write + 'static>(&mut self, output: Rc<RefCell<W>>) {
+        self.debug_output = Some(Output::new(output));
+    }
+
+    pub(crate) fn write_debug_output(&self, text: &str) -> Result<(), MonkeyError> {
+        match &self.debug_output {
+            Some(output) => output.write(text),
+            None => io::stderr().write_all(text.as_bytes()),
         }
+        .map_err(|e| MonkeyError::OutputFailed(e.to_string()))
+    }
+
+    /// diagnostics recorded since the last call, oldest first
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    #[cfg(feature = "bigint")]
+    pub fn set_bigint(&mut self, enabled: bool) {
+        self.bigint = enabled;
+    }
+
+    /// Keys the hashes built from now on with `seed` rather than a random
+    /// key, so the same script gives hashes that iterate in the same order
+    /// on the host side. What scripts observe never depends on it: `keys`,
+    /// `for` and display all sort. Only for tests and debugging, as a fixed
+    /// seed lets a script craft colliding keys.
+    pub fn set_hash_seed(&mut self, seed: u64) {
+        self.hash_state = HashState::seeded(seed);
     }
 
     pub fn get(&mut self, key: &str) -> Option<Object> {
@@ -37,62 +279,256 @@ impl Evaluator {
         self.env.borrow_mut().set(key, value)
     }
 
-    pub fn evaluate(&mut self, program: &ast::Program) -> Result<Object, MonkeyError> {
-        let mut result = Object::Null;
-        for stmt in &program.statements {
-            result = self.eval_statement(stmt)?;
-            if let Object::Return(return_value) = result {
-                return Ok(*return_value);
+    /// doc comment attached to the `let` that defined `key`
+    pub fn get_doc(&self, key: &str) -> Option<String> {
+        self.env.borrow().get_doc(key)
+    }
+
+    /// Parse and evaluate `input` against this evaluator's environment.
+    pub fn eval_source(&mut self, input: &str) -> Result<Object, MonkeyError> {
+        let program = crate::parse(input)?;
+        self.evaluate(&program)
+    }
+
+    /// Evaluate `input` with this evaluator's environment and every scope
+    /// it is enclosed in frozen, so it can read and call what is bound but
+    /// neither `let` nor assign to it. Each scope gets its own frozen state
+    /// back afterwards.
+    pub fn eval_pure(&mut self, input: &str) -> Result<Object, MonkeyError> {
+        let mut scopes = vec![Rc::clone(&self.env)];
+        loop {
+            let outer = scopes[scopes.len() - 1].borrow().outer();
+            match outer {
+                Some(outer) => scopes.push(outer),
+                None => break,
             }
         }
-        Ok(result)
+        let frozen = scopes
+            .iter()
+            .map(|scope| {
+                let frozen = scope.borrow().is_frozen();
+                scope.borrow_mut().freeze();
+                frozen
+            })
+            .collect::<Vec<_>>();
+        let result = self.eval_source(input);
+        for (scope, frozen) in scopes.iter().zip(frozen) {
+            scope.borrow_mut().set_frozen(frozen);
+        }
+        result
+    }
+
+    /// Evaluate `input` and convert the result to a Rust value.
+    ///
+    /// ```
+    /// use lib::prelude::*;
+    ///
+    /// let mut eval = Evaluator::new();
+    /// let answer: i64 = eval.eval_as("let x = 21; x * 2").unwrap();
+    /// assert_eq!(answer, 42);
+    /// let name: String = eval.eval_as(r#""monkey""#).unwrap();
+    /// assert_eq!(name, "monkey");
+    /// let items: Vec<Object> = eval.eval_as("[1, x]").unwrap();
+    /// assert_eq!(items, vec![Object::Integer(1), Object::Integer(21)]);
+    ///
+    /// let err = eval.eval_as::<i64>("true").unwrap_err();
+    /// assert_eq!(err.to_string(), "cannot convert true (BOOLEAN) to i64");
+    /// ```
+    pub fn eval_as<T>(&mut self, input: &str) -> Result<T, MonkeyError>
+    where
+        T: TryFrom<Object, Error = MonkeyError>,
+    {
+        T::try_from(self.eval_source(input)?)
+    }
+
+    /// The value of the last statement. A `return` outside any function
+    /// ends the program there with its value, however deep in blocks and
+    /// loops it is; `break` and `continue` outside a loop are errors.
+    /// Recursion is bounded by `MAX_NESTING_DEPTH` and `MAX_CALL_DEPTH`, not
+    /// by the calling thread's stack, which is grown when it runs low.
+    pub fn evaluate(&mut self, program: &ast::Program) -> Result<Object, MonkeyError> {
+        match self.eval_statements(&program.statements) {
+            Ok(obj) => Ok(obj),
+            Err(Flow::Return(obj)) => Ok(*obj),
+            Err(Flow::Error(e)) => Err(e),
+            Err(Flow::Break(_)) => Err(MonkeyError::BreakOutsideLoop),
+            Err(Flow::Continue) => Err(MonkeyError::ContinueOutsideLoop),
+            Err(Flow::TailCall {
+                function,
+                arguments,
+            }) => self.apply_function(*function, arguments),
+        }
     }
 
-    fn eval_statement(&mut self, stmt: &ast::Statement) -> Result<Object, MonkeyError> {
+    fn eval_statement(&mut self, stmt: &ast::Statement) -> Result<Object, Flow> {
+        self.call_hook(stmt)?;
         match stmt {
             ast::Statement::Expression(expr) => self.eval_expression(expr),
             ast::Statement::Block(stmts) => self.eval_block_statement(stmts),
-            ast::Statement::Return(expr) => {
-                let obj = self.eval_expression(expr)?;
-                Ok(Object::Return(Box::new(obj)))
+            ast::Statement::Return(expr) => self.eval_return_statement(expr),
+            ast::Statement::Break(expr) => {
+                let obj = match expr {
+                    Some(expr) => self.eval_expression(expr)?,
+                    None => Object::Null,
+                };
+                Err(Flow::Break(Box::new(obj)))
+            }
+            ast::Statement::Continue => Err(Flow::Continue),
+            ast::Statement::For {
+                binding,
+                iterable,
+                body,
+            } => match iterable {
+                // a range written out is walked without being built
+                ast::Expression::Range { start, end } => {
+                    let start = self.eval_expression(start)?;
+                    let end = self.eval_expression(end)?;
+                    let range = Range::new(start, end)?;
+                    self.eval_for_statement(binding, &range, body)
+                }
+                iterable => {
+                    let iterable = self.eval_expression(iterable)?;
+                    self.eval_for_statement(binding, &iterable, body)
+                }
+            },
+            ast::Statement::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                let value = self.eval_expression(value)?;
+                self.eval_assignment(target, *operator, value)?;
+                Ok(Object::Null)
             }
-            ast::Statement::Let { identifier, value } => {
+            ast::Statement::Let {
+                identifier,
+                value,
+                doc,
+            } => {
                 if let ast::Expression::Identifier(ident) = identifier {
                     let val = self.eval_expression(value)?;
-                    self.set(ident.to_owned(), val);
+                    self.env.borrow_mut().define(ident.to_owned(), val)?;
+                    self.env.borrow_mut().set_doc(ident.to_owned(), doc.clone());
                     Ok(Object::Null)
                 } else {
-                    panic!("panic at let statement evaluation")
+                    Err(MonkeyError::InvalidIdentifier.into())
+                }
+            }
+        }
+    }
+
+    fn call_hook(&self, stmt: &ast::Statement) -> Result<(), MonkeyError> {
+        if let (Some(hook), false) = (&self.on_statement, matches!(stmt, Statement::Block(_))) {
+            if let HookAction::Abort(reason) = hook.call(stmt, &self.env.borrow()) {
+                return Err(MonkeyError::AbortedByHost(reason));
+            }
+        }
+        Ok(())
+    }
+
+    /// A function body, evaluated like `eval_statement` except that a call
+    /// whose value would be the function's own, at the end of the body or of
+    /// an `if` branch there, leaves it as `Flow::TailCall`.
+    fn eval_tail_statement(&mut self, stmt: &ast::Statement) -> Result<Object, Flow> {
+        match stmt {
+            ast::Statement::Block(stmts) => {
+                let Some((last, init)) = stmts.split_last() else {
+                    return Ok(Object::Null);
+                };
+                let outer = Rc::clone(&self.env);
+                self.env = Rc::new(RefCell::new(Environment::new_enclosed(Rc::clone(&outer))));
+                let result = self
+                    .eval_statements(init)
+                    .and_then(|_| self.eval_tail_statement(last));
+                self.env = outer;
+                result
+            }
+            ast::Statement::Expression(ast::Expression::Call {
+                function,
+                arguments,
+            }) => {
+                self.call_hook(stmt)?;
+                self.eval_tail_call(function, arguments)
+            }
+            ast::Statement::Expression(ast::Expression::If {
+                condition,
+                consequence,
+                alternative,
+            }) => {
+                self.call_hook(stmt)?;
+                let condition = self.eval_expression(condition)?;
+                if self.eval_condition(&condition)? {
+                    self.eval_tail_statement(consequence)
+                } else {
+                    match alternative {
+                        Some(alt) => self.eval_tail_statement(alt),
+                        None => Ok(Object::Null),
+                    }
                 }
             }
-            _ => Err(MonkeyError::Unknown),
+            stmt => self.eval_statement(stmt),
+        }
+    }
+
+    /// `return f(x)` in a function body is a tail call
+    fn eval_return_statement(&mut self, expr: &ast::Expression) -> Result<Object, Flow> {
+        match expr {
+            ast::Expression::Call {
+                function,
+                arguments,
+            } if self.in_function => self.eval_tail_call(function, arguments),
+            expr => Err(Flow::Return(Box::new(self.eval_expression(expr)?))),
         }
     }
 
-    fn eval_expression(&mut self, expr: &ast::Expression) -> Result<Object, MonkeyError> {
+    fn eval_tail_call(
+        &mut self,
+        function: &ast::Expression,
+        arguments: &[ast::Expression],
+    ) -> Result<Object, Flow> {
+        let arguments = self.eval_expressions(arguments)?;
+        let function = self.eval_expression(function)?;
+        Err(Flow::TailCall {
+            function: Box::new(function),
+            arguments,
+        })
+    }
+
+    /// Every recursion of the evaluator passes through here, calls included,
+    /// so this is where it makes sure there is stack for another level.
+    fn eval_expression(&mut self, expr: &ast::Expression) -> Result<Object, Flow> {
+        stack::grow(|| self.eval_expression_here(expr))
+    }
+
+    fn eval_expression_here(&mut self, expr: &ast::Expression) -> Result<Object, Flow> {
         match expr {
             ast::Expression::Integer(int) => Ok(Object::Integer(*int)),
+            ast::Expression::BigInteger(digits) => {
+                self.eval_big_integer_literal(digits).map_err(Flow::from)
+            }
             ast::Expression::String(str) => Ok(Object::String(str.to_owned())),
+            ast::Expression::Char(ch) => Ok(Object::Char(*ch)),
             ast::Expression::Boolean(bool) => Ok(Object::Bool(*bool)),
+            ast::Expression::Array(elements) => Ok(Object::Array(self.eval_expressions(elements)?)),
+            ast::Expression::Hash(pairs) => self.eval_hash_literal(pairs),
             ast::Expression::Prefix { operator, right } => {
                 let right = self.eval_expression(right)?;
                 self.eval_prefix_expression(operator, right)
+                    .map_err(Flow::from)
             }
-            ast::Expression::Infix {
-                operator,
-                left,
-                right,
-            } => {
-                let right = self.eval_expression(right)?;
-                let left = self.eval_expression(left)?;
-                self.eval_infix_expression(operator, left, right)
-            }
+            ast::Expression::Infix { .. }
+            | ast::Expression::Call { .. }
+            | ast::Expression::Index { .. }
+            | ast::Expression::SafeIndex { .. }
+            | ast::Expression::Slice { .. }
+            | ast::Expression::Range { .. } => self.eval_chain(expr),
             ast::Expression::If {
                 condition,
                 consequence,
                 alternative,
             } => {
-                if self.eval_expression(condition)?.is_truthy() {
+                let condition = self.eval_expression(condition)?;
+                if self.eval_condition(&condition)? {
                     self.eval_statement(consequence)
                 } else {
                     match alternative {
@@ -101,30 +537,166 @@ impl Evaluator {
                     }
                 }
             }
-            ast::Expression::Identifier(ident) => match self.get(ident) {
-                Some(val) => Ok(val),
-                None => Err(MonkeyError::IdentifierNotFound),
-            },
+            ast::Expression::Ternary {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                let condition = self.eval_expression(condition)?;
+                if self.eval_condition(&condition)? {
+                    self.eval_expression(consequence)
+                } else {
+                    self.eval_expression(alternative)
+                }
+            }
+            ast::Expression::Match {
+                subject,
+                arms,
+                default,
+            } => {
+                let subject = self.eval_expression(subject)?;
+                self.eval_match_expression(subject, arms, default.as_deref())
+            }
+            ast::Expression::Try {
+                body,
+                binding,
+                handler,
+            } => self.eval_try_expression(body, binding, handler),
+            ast::Expression::Loop { body } => self.eval_loop_expression(body),
+            ast::Expression::Identifier(ident) => self.eval_identifier(ident).map_err(Flow::from),
             ast::Expression::Function { parameters, body } => Ok(Object::Function {
                 parameters: parameters.clone(),
-                body: *body.clone(),
-                env: Environment::new_enclosed(Rc::clone(&self.env)),
+                body: Rc::clone(body),
+                env: Rc::clone(&self.env),
             }),
-            ast::Expression::Call {
-                function,
-                arguments,
-            } => {
-                let args = self.eval_expressions(arguments)?;
-                let function = self.eval_expression(function)?;
-                // We have to evaluate inside of function.
+        }
+    }
+
+    /// Infix operators, calls, indexes, slices and ranges, whose trees the
+    /// parser grows leftwards in a loop. A chain of them like `1 + 1 + …` or
+    /// `a[0][0]…` is walked down its left side and back up again here, so
+    /// it takes no native stack per link. Operands still evaluate as they
+    /// would recursing: an infix right operand and call arguments on the
+    /// way down, before the left side, the rest on the way up.
+    fn eval_chain(&mut self, expr: &ast::Expression) -> Result<Object, Flow> {
+        let mut links = vec![];
+        let mut root = expr;
+        while let Some(left) = root.chain_left() {
+            let operand = match root {
+                ast::Expression::Infix {
+                    operator: ast::Infix::Coalesce | ast::Infix::Pipe,
+                    ..
+                } => vec![],
+                ast::Expression::Infix { right, .. } => vec![self.eval_expression(right)?],
+                ast::Expression::Call { arguments, .. } => self.eval_expressions(arguments)?,
+                _ => vec![],
+            };
+            links.push((root, operand));
+            root = left;
+        }
+        let mut left = self.eval_expression(root)?;
+        for (link, operand) in links.into_iter().rev() {
+            left = self.eval_link(link, left, operand)?;
+        }
+        Ok(left)
+    }
 
-                self.apply_function(function, args)
+    /// one link of `eval_chain` given its evaluated left side and, when
+    /// those come first, its right operand or arguments
+    fn eval_link(
+        &mut self,
+        link: &ast::Expression,
+        left: Object,
+        mut operand: Vec<Object>,
+    ) -> Result<Object, Flow> {
+        match link {
+            ast::Expression::Infix {
+                operator: ast::Infix::Coalesce,
+                right,
+                ..
+            } => match left {
+                Object::Null => self.eval_expression(right),
+                left => Ok(left),
+            },
+            ast::Expression::Infix {
+                operator: ast::Infix::Pipe,
+                right,
+                ..
+            } => self.eval_pipe_expression(left, right),
+            ast::Expression::Infix { operator, .. } => {
+                let right = operand.pop().unwrap_or(Object::Null);
+                self.eval_infix_expression(operator, left, right)
+                    .or_else(|e| self.options.recover(e))
+                    .map_err(Flow::from)
+            }
+            ast::Expression::Call { .. } => self.apply_function(left, operand).map_err(Flow::from),
+            ast::Expression::Index { index, .. } => {
+                let index = self.eval_expression(index)?;
+                self.eval_index_expression(left, index)
+                    .or_else(|e| self.options.recover(e))
+                    .map_err(Flow::from)
             }
-            _ => Err(MonkeyError::Unknown),
+            ast::Expression::SafeIndex { index, .. } => self.eval_safe_index(left, index),
+            ast::Expression::Slice { start, end, .. } => {
+                let start = match start {
+                    Some(start) => Some(self.eval_expression(start)?),
+                    None => None,
+                };
+                let end = match end {
+                    Some(end) => Some(self.eval_expression(end)?),
+                    None => None,
+                };
+                self.eval_slice_expression(left, start, end)
+                    .map_err(Flow::from)
+            }
+            ast::Expression::Range { end, .. } => {
+                let end = self.eval_expression(end)?;
+                self.eval_range_expression(left, end).map_err(Flow::from)
+            }
+            // only what `chain_left` walks is a link
+            _ => Ok(left),
+        }
+    }
+
+    /// a binding, or else the builtin of that name
+    fn eval_identifier(&mut self, ident: &str) -> Result<Object, MonkeyError> {
+        match self.get(ident) {
+            Some(val) => Ok(val),
+            None => match builtins::lookup(ident) {
+                Some(builtin) => Ok(Object::Builtin(builtin)),
+                None => self
+                    .options
+                    .recover(MonkeyError::IdentifierNotFound(ident.to_owned())),
+            },
         }
     }
 
-    fn eval_expressions(&mut self, exprs: &[ast::Expression]) -> Result<Vec<Object>, MonkeyError> {
+    /// `body`, or, when it fails, `handler` with the error's message bound to
+    /// `binding`. Calls in the body are never tail calls, so they fail here.
+    /// A host abort is not caught.
+    fn eval_try_expression(
+        &mut self,
+        body: &ast::Statement,
+        binding: &str,
+        handler: &ast::Statement,
+    ) -> Result<Object, Flow> {
+        let in_function = std::mem::replace(&mut self.in_function, false);
+        let result = self.eval_statement(body);
+        self.in_function = in_function;
+        let message = match result {
+            Err(Flow::Error(MonkeyError::Raised(message))) => message,
+            Err(Flow::Error(e @ MonkeyError::AbortedByHost(_))) => return Err(e.into()),
+            Err(Flow::Error(e)) => e.to_string(),
+            other => return other,
+        };
+        let mut evaluator = self.enclosed(Environment::new_enclosed(Rc::clone(&self.env)));
+        evaluator.set(binding.to_owned(), Object::String(message));
+        let result = evaluator.eval_statement(handler);
+        self.diagnostics.append(&mut evaluator.diagnostics);
+        result
+    }
+
+    fn eval_expressions(&mut self, exprs: &[ast::Expression]) -> Result<Vec<Object>, Flow> {
         let mut result = vec![];
         for expr in exprs.iter() {
             result.push(self.eval_expression(expr)?)
@@ -132,133 +704,811 @@ impl Evaluator {
         Ok(result)
     }
 
+    /// later duplicates of a key replace earlier ones
+    fn eval_hash_literal(
+        &mut self,
+        pairs: &[(ast::Expression, ast::Expression)],
+    ) -> Result<Object, Flow> {
+        let mut hash = HashMap::with_hasher(self.hash_state);
+        for (key, value) in pairs.iter() {
+            let key = self.eval_expression(key)?.hash_key()?;
+            let value = self.eval_expression(value)?;
+            insert_pair(&mut hash, key, value)?;
+        }
+        Ok(Object::Hash(hash))
+    }
+
+    /// Calls `function`, then each tail call it ends in, in a loop rather
+    /// than recursively, so tail recursion runs in constant Rust stack.
+    /// Builtins taking a callback call it through here too, so every native
+    /// recursion into the evaluator counts toward `MAX_CALL_DEPTH` here.
     fn apply_function(
         &mut self,
         function: Object,
         args: Vec<Object>,
     ) -> Result<Object, MonkeyError> {
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(MonkeyError::CallStackExceeded(MAX_CALL_DEPTH));
+        }
+        // every scope the calls evaluate in inherits the deeper count
+        self.call_depth += 1;
+        let (mut function, mut args) = (function, args);
+        let result = loop {
+            match self.call_function(function, args) {
+                Ok(obj) => break Ok(obj),
+                Err(Flow::Return(obj)) => break Ok(*obj),
+                Err(Flow::Error(e)) => break Err(e),
+                Err(Flow::Break(_)) => break Err(MonkeyError::BreakOutsideLoop),
+                Err(Flow::Continue) => break Err(MonkeyError::ContinueOutsideLoop),
+                Err(Flow::TailCall {
+                    function: next,
+                    arguments,
+                }) => {
+                    function = *next;
+                    args = arguments;
+                }
+            }
+        };
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_function(&mut self, function: Object, args: Vec<Object>) -> Result<Object, Flow> {
         if let Object::Function {
             parameters,
             body,
             env,
         } = function
         {
-            if parameters.len() != args.len() {
+            // a rest parameter can only be last
+            let fixed = parameters.iter().take_while(|param| !param.rest).count();
+            let variadic = fixed < parameters.len();
+            if args.len() < fixed || (!variadic && args.len() > fixed) {
                 return Err(MonkeyError::IncorrectNumberOfArguments {
-                    expected: args.len(),
-                    actual: parameters.len(),
-                });
-            }
-            let mut evaluator = Evaluator::from_env(env);
-            for (ident, arg) in parameters.iter().zip(args.iter()) {
-                if let ast::Expression::Identifier(ident) = ident {
-                    evaluator.set(ident.to_owned(), arg.clone());
+                    expected: fixed,
+                    actual: args.len(),
                 }
+                .into());
             }
-            match evaluator.eval_statement(&body)? {
-                Object::Return(obj) => Ok(*obj),
-                obj => Ok(obj),
+            let mut evaluator = self.enclosed(Environment::new_enclosed(env));
+            evaluator.in_function = true;
+            let mut args = args;
+            let rest = args.split_off(fixed);
+            for (param, arg) in parameters.iter().zip(args) {
+                evaluator.set(param.name.to_owned(), arg);
             }
+            if let Some(param) = parameters.get(fixed) {
+                evaluator.set(param.name.to_owned(), Object::Array(rest));
+            }
+            let result = evaluator.eval_tail_statement(&body);
+            self.diagnostics.append(&mut evaluator.diagnostics);
+            result
+        } else if let Object::Builtin(builtin) = function {
+            Ok(builtin.call(self, args)?)
         } else {
-            Err(MonkeyError::Unknown)
+            Err(MonkeyError::NotCallable(function.object_type()).into())
         }
     }
 
-    fn eval_block_statement(&mut self, stmts: &[Statement]) -> Result<Object, MonkeyError> {
-        let mut result = Object::Null;
-        for stmt in stmts.iter() {
-            result = self.eval_statement(stmt)?;
-
-            if let Object::Return(_) = result {
-                return Ok(result);
+    fn eval_condition(&mut self, condition: &Object) -> Result<bool, MonkeyError> {
+        if let Object::Bool(value) = condition {
+            return Ok(*value);
+        }
+        match self.truthiness {
+            Truthiness::Permissive => {}
+            Truthiness::Warn => self.diagnostics.push(Diagnostic {
+                message: format!("condition is {}, not BOOLEAN", condition.object_type()),
+            }),
+            Truthiness::Strict => {
+                return Err(MonkeyError::ConditionTypeMismatch(condition.object_type()))
             }
         }
-        // for block statement
-        Ok(result)
+        Ok(condition.is_truthy())
     }
 
-    fn eval_infix_expression(
+    /// `start..end` as an array of the integers from `start` up to but not
+    /// including `end`; a reversed range is empty. One of over
+    /// `MAX_RANGE_LEN` elements fails with `RangeTooLong`.
+    fn eval_range_expression(&self, start: Object, end: Object) -> Result<Object, MonkeyError> {
+        let range = Range::new(start, end)?;
+        let length = range.len();
+        if length > MAX_RANGE_LEN as u128 {
+            return Err(MonkeyError::RangeTooLong {
+                length,
+                limit: MAX_RANGE_LEN,
+            });
+        }
+        Ok(Object::Array(range.items().into_iter().flatten().collect()))
+    }
+
+    /// `left?[index]` is null without evaluating `index` once `left` is null,
+    /// so each link of `a?[x]?[y]` only runs while the chain is unbroken
+    fn eval_safe_index(&mut self, left: Object, index: &ast::Expression) -> Result<Object, Flow> {
+        if let Object::Null = left {
+            return Ok(Object::Null);
+        }
+        let index = self.eval_expression(index)?;
+        self.eval_index_expression(left, index)
+            .or_else(|e| self.options.recover(e))
+            .map_err(Flow::from)
+    }
+
+    fn eval_index_expression(
         &mut self,
-        operator: &ast::Infix,
         left: Object,
-        right: Object,
+        index: Object,
     ) -> Result<Object, MonkeyError> {
-        match (left, right) {
-            (Object::Integer(left), Object::Integer(right)) => match operator {
-                ast::Infix::Eq => Ok(Object::Bool(left == right)),
-                ast::Infix::NotEq => Ok(Object::Bool(left != right)),
-                ast::Infix::LT => Ok(Object::Bool(left < right)),
-                ast::Infix::GT => Ok(Object::Bool(left > right)),
-                ast::Infix::Plus => Ok(Object::Integer(left + right)),
-                ast::Infix::Minus => Ok(Object::Integer(left - right)),
-                ast::Infix::Slash => Ok(Object::Integer(left / right)),
-                ast::Infix::Asterisk => Ok(Object::Integer(left * right)),
-            },
-            (Object::Bool(left), Object::Bool(right)) => match operator {
-                ast::Infix::Eq => Ok(Object::Bool(left == right)),
-                ast::Infix::NotEq => Ok(Object::Bool(left != right)),
-                operator => Err(MonkeyError::UnknownOperator {
-                    left: ObjectType::Bool,
-                    operator: *operator,
-                    right: ObjectType::Bool,
-                }),
-            },
-            (Object::Integer(_), Object::Bool(_)) => Err(MonkeyError::TypeMismatch {
-                operator: *operator,
-                left: ObjectType::Integer,
-                right: ObjectType::Bool,
-            }),
-            (Object::Bool(_), Object::Integer(_)) => Err(MonkeyError::TypeMismatch {
-                operator: *operator,
-                left: ObjectType::Bool,
-                right: ObjectType::Integer,
+        match (left, index) {
+            (Object::Array(elements), Object::Integer(index)) => {
+                resolve_index(index, elements.len())
+                    .map(|position| elements[position].clone())
+                    .ok_or(MonkeyError::IndexOutOfRange {
+                        index,
+                        length: elements.len(),
+                    })
+            }
+            // by char, not by byte
+            (Object::String(string), Object::Integer(index)) => {
+                let length = string.chars().count();
+                resolve_index(index, length)
+                    .and_then(|position| string.chars().nth(position))
+                    .map(Object::Char)
+                    .ok_or(MonkeyError::IndexOutOfRange { index, length })
+            }
+            (Object::Hash(pairs), index) => Ok(pairs
+                .get(&index.hash_key()?)
+                .cloned()
+                .unwrap_or(Object::Null)),
+            (left, index) => Err(MonkeyError::IndexNotSupported {
+                left: left.object_type(),
+                index: index.object_type(),
             }),
-            _ => Ok(Object::Null),
         }
     }
 
-    fn eval_prefix_expression(
+    /// The body of the first arm whose pattern equals `subject`, patterns
+    /// evaluated in order up to that one; `default`, or null, otherwise.
+    fn eval_match_expression(
         &mut self,
-        operator: &ast::Prefix,
-        right: Object,
-    ) -> Result<Object, MonkeyError> {
-        match operator {
-            ast::Prefix::Bang => match right {
-                Object::Bool(value) => Ok(Object::Bool(!value)),
-                Object::Null => Ok(Object::Bool(true)),
-                _ => Ok(Object::Bool(false)),
-            },
-            ast::Prefix::Minus => match right {
-                Object::Integer(int) => Ok(Object::Integer(-int)),
-                _ => Ok(Object::Null),
-            },
+        subject: Object,
+        arms: &[(ast::Pattern, ast::Statement)],
+        default: Option<&ast::Statement>,
+    ) -> Result<Object, Flow> {
+        for (pattern, body) in arms {
+            let mut bindings = vec![];
+            if !self.match_pattern(pattern, &subject, &mut bindings)? {
+                continue;
+            }
+            if bindings.is_empty() {
+                return self.eval_statement(body);
+            }
+            let mut evaluator = self.enclosed(Environment::new_enclosed(Rc::clone(&self.env)));
+            for (name, value) in bindings {
+                evaluator.set(name, value);
+            }
+            let result = evaluator.eval_statement(body);
+            self.diagnostics.append(&mut evaluator.diagnostics);
+            return result;
+        }
+        match default {
+            Some(default) => self.eval_statement(default),
+            None => Ok(Object::Null),
         }
     }
-}
 
-impl Default for Evaluator {
-    fn default() -> Self {
-        Self::new()
+    /// Whether `subject` fits `pattern`, pushing what the pattern binds onto
+    /// `bindings`. Values in the pattern are evaluated as they are reached,
+    /// so those after a mismatch are not.
+    fn match_pattern(
+        &mut self,
+        pattern: &ast::Pattern,
+        subject: &Object,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> Result<bool, Flow> {
+        match (pattern, subject) {
+            (ast::Pattern::Value(expr), subject) => {
+                let value = self.eval_expression(expr)?;
+                Ok(equals(subject, &value)?)
+            }
+            (ast::Pattern::Binding(name), _) if name == "_" => Ok(true),
+            (ast::Pattern::Binding(name), subject) => {
+                bindings.push((name.to_owned(), subject.clone()));
+                Ok(true)
+            }
+            (
+                ast::Pattern::Array {
+                    elements: patterns,
+                    rest,
+                },
+                Object::Array(elements),
+            ) => {
+                let fits = match rest {
+                    None => elements.len() == patterns.len(),
+                    Some(_) => elements.len() >= patterns.len(),
+                };
+                if !fits {
+                    return Ok(false);
+                }
+                for (pattern, element) in patterns.iter().zip(elements) {
+                    if !self.match_pattern(pattern, element, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                if let Some(rest) = rest.as_ref().filter(|rest| *rest != "_") {
+                    let others = elements[patterns.len()..].to_vec();
+                    bindings.push((rest.to_owned(), Object::Array(others)));
+                }
+                Ok(true)
+            }
+            (ast::Pattern::Hash(patterns), Object::Hash(pairs)) => {
+                for (key, pattern) in patterns {
+                    let key = self.eval_expression(key)?.hash_key()?;
+                    match pairs.get(&key) {
+                        Some(value) if self.match_pattern(pattern, value, bindings)? => {}
+                        _ => return Ok(false),
+                    }
+                }
+                Ok(true)
+            }
+            (ast::Pattern::Array { .. } | ast::Pattern::Hash(_), _) => Ok(false),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        error::MonkeyError,
-        eval::{environment::Environment, Evaluator, Object, ObjectType},
-        lexer::Lexer,
-        parser::{
-            ast::{self, Expression, Program},
-            Parser,
+    /// `body` over and over until a `break`, whose value it evaluates to
+    fn eval_loop_expression(&mut self, body: &ast::Statement) -> Result<Object, Flow> {
+        loop {
+            match self.eval_statement(body) {
+                Ok(_) | Err(Flow::Continue) => {}
+                Err(Flow::Break(obj)) => return Ok(*obj),
+                Err(flow) => return Err(flow),
+            }
+        }
+    }
+
+    /// Runs `body` once per item of `iterable`, see `Iterable`, each time in
+    /// a fresh scope binding the item to `binding`. `continue` moves on to
+    /// the next item, `break` ends the loop and `return` leaves the
+    /// enclosing function.
+    fn eval_for_statement(
+        &mut self,
+        binding: &str,
+        iterable: &dyn Iterable,
+        body: &ast::Statement,
+    ) -> Result<Object, Flow> {
+        let items = iterable
+            .items()
+            .ok_or(MonkeyError::NotIterable(iterable.object_type()))?;
+        for item in items {
+            let mut evaluator = self.enclosed(Environment::new_enclosed(Rc::clone(&self.env)));
+            evaluator.set(binding.to_owned(), item);
+            let result = evaluator.eval_statement(body);
+            self.diagnostics.append(&mut evaluator.diagnostics);
+            match result {
+                Ok(_) | Err(Flow::Continue) => {}
+                Err(Flow::Break(_)) => break,
+                Err(flow) => return Err(flow),
+            }
+        }
+        Ok(Object::Null)
+    }
+
+    /// `left |> f(a)` as `f(left, a)`, `left |> f` as `f(left)`
+    fn eval_pipe_expression(
+        &mut self,
+        left: Object,
+        right: &ast::Expression,
+    ) -> Result<Object, Flow> {
+        let mut args = vec![left];
+        let function = match right {
+            ast::Expression::Call {
+                function,
+                arguments,
+            } => {
+                args.extend(self.eval_expressions(arguments)?);
+                self.eval_expression(function)?
+            }
+            right => self.eval_expression(right)?,
+        };
+        Ok(self.apply_function(function, args)?)
+    }
+
+    /// `target op= value`, updating the binding in the scope that defines it.
+    /// Each index is evaluated once, left to right, after the value.
+    fn eval_assignment(
+        &mut self,
+        target: &ast::Expression,
+        operator: Option<ast::Infix>,
+        value: Object,
+    ) -> Result<(), Flow> {
+        let mut path = vec![];
+        let mut root = target;
+        while let ast::Expression::Index { left, index } = root {
+            path.push(index.as_ref());
+            root = left;
+        }
+        let name = match root {
+            ast::Expression::Identifier(name) => name,
+            target => return Err(MonkeyError::InvalidAssignmentTarget(target.to_string()).into()),
+        };
+        let indices = path
+            .iter()
+            .rev()
+            .map(|index| self.eval_expression(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let current = self
+            .get(name)
+            .ok_or_else(|| MonkeyError::IdentifierNotFound(name.to_owned()))?;
+        let updated = self.eval_assignment_path(current, &indices, operator, value)?;
+        self.env.borrow_mut().assign(name, updated)?;
+        Ok(())
+    }
+
+    /// `container` with the element at `indices` replaced by `value`, or by
+    /// `element op value` for a compound assignment
+    fn eval_assignment_path(
+        &mut self,
+        container: Object,
+        indices: &[Object],
+        operator: Option<ast::Infix>,
+        value: Object,
+    ) -> Result<Object, MonkeyError> {
+        let (index, rest) = match indices.split_first() {
+            Some(first) => first,
+            None => {
+                return match operator {
+                    Some(operator) => self.eval_infix_expression(&operator, container, value),
+                    None => Ok(value),
+                }
+            }
+        };
+        let element = match (rest.is_empty(), operator) {
+            // a plain assignment may add a hash key, so the old element is not needed
+            (true, None) => Object::Null,
+            _ => self.eval_index_expression(container.clone(), index.clone())?,
+        };
+        let element = self.eval_assignment_path(element, rest, operator, value)?;
+        match (container, index) {
+            (Object::Array(mut elements), Object::Integer(index)) => {
+                let length = elements.len();
+                let position =
+                    resolve_index(*index, length).ok_or(MonkeyError::IndexOutOfRange {
+                        index: *index,
+                        length,
+                    })?;
+                elements[position] = element;
+                Ok(Object::Array(elements))
+            }
+            (Object::Hash(mut pairs), index) => {
+                insert_pair(&mut pairs, index.hash_key()?, element)?;
+                Ok(Object::Hash(pairs))
+            }
+            (container, index) => Err(MonkeyError::IndexNotSupported {
+                left: container.object_type(),
+                index: index.object_type(),
+            }),
+        }
+    }
+
+    /// `left[start:end]` on arrays and strings, by element or char. Bounds
+    /// count from the end when negative and are clamped to the length, so
+    /// slicing never fails on range; reversed bounds give an empty result.
+    fn eval_slice_expression(
+        &mut self,
+        left: Object,
+        start: Option<Object>,
+        end: Option<Object>,
+    ) -> Result<Object, MonkeyError> {
+        let bound = |bound: Option<Object>| match bound {
+            None => Ok(None),
+            Some(Object::Integer(int)) => Ok(Some(int)),
+            Some(obj) => Err(MonkeyError::IndexNotSupported {
+                left: left.object_type(),
+                index: obj.object_type(),
+            }),
+        };
+        let (start, end) = (bound(start)?, bound(end)?);
+        match left {
+            Object::Array(elements) => {
+                let (start, end) = slice_bounds(start, end, elements.len());
+                Ok(Object::Array(elements[start..end].to_vec()))
+            }
+            Object::String(string) => {
+                let (start, end) = slice_bounds(start, end, string.chars().count());
+                Ok(Object::String(
+                    string.chars().skip(start).take(end - start).collect(),
+                ))
+            }
+            left => Err(MonkeyError::IndexNotSupported {
+                left: left.object_type(),
+                index: ObjectType::Integer,
+            }),
+        }
+    }
+
+    /// Every block runs in its own scope, so a `let` in the body of an `if`,
+    /// `loop`, `for`, `match` arm or function ends with the block instead of
+    /// leaking into the code around it. Assigning to a name from outside the
+    /// block still updates that binding where it was defined.
+    fn eval_block_statement(&mut self, stmts: &[Statement]) -> Result<Object, Flow> {
+        let outer = Rc::clone(&self.env);
+        self.env = Rc::new(RefCell::new(Environment::new_enclosed(Rc::clone(&outer))));
+        let result = self.eval_statements(stmts);
+        self.env = outer;
+        result
+    }
+
+    fn eval_statements(&mut self, stmts: &[Statement]) -> Result<Object, Flow> {
+        let mut result = Object::Null;
+        for stmt in stmts.iter() {
+            result = self.eval_statement(stmt)?;
+        }
+        Ok(result)
+    }
+
+    fn eval_infix_expression(
+        &mut self,
+        operator: &ast::Infix,
+        left: Object,
+        right: Object,
+    ) -> Result<Object, MonkeyError> {
+        match (left, right) {
+            (left, right) if *operator == ast::Infix::In => self.eval_in_expression(left, right),
+            (Object::Integer(left), Object::Integer(right)) => {
+                self.eval_integer_infix_expression(operator, left, right)
+            }
+            #[cfg(feature = "bigint")]
+            (left @ Object::BigInteger(_), right) | (left, right @ Object::BigInteger(_)) => {
+                match (bigint::to_bigint(&left), bigint::to_bigint(&right)) {
+                    (Some(left), Some(right)) => {
+                        bigint::eval_infix_expression(operator, left, right)
+                    }
+                    _ => Err(MonkeyError::TypeMismatch {
+                        operator: *operator,
+                        left: left.object_type(),
+                        right: right.object_type(),
+                    }),
+                }
+            }
+            (Object::Bool(left), Object::Bool(right)) => match operator {
+                ast::Infix::Eq => Ok(Object::Bool(left == right)),
+                ast::Infix::NotEq => Ok(Object::Bool(left != right)),
+                operator => Err(MonkeyError::UnknownOperator {
+                    left: ObjectType::Bool,
+                    operator: *operator,
+                    right: ObjectType::Bool,
+                }),
+            },
+            (Object::Char(left), Object::Char(right)) => match operator {
+                ast::Infix::Eq => Ok(Object::Bool(left == right)),
+                ast::Infix::NotEq => Ok(Object::Bool(left != right)),
+                ast::Infix::LT => Ok(Object::Bool(left < right)),
+                ast::Infix::GT => Ok(Object::Bool(left > right)),
+                ast::Infix::LTEq => Ok(Object::Bool(left <= right)),
+                ast::Infix::GTEq => Ok(Object::Bool(left >= right)),
+                operator => Err(MonkeyError::UnknownOperator {
+                    left: ObjectType::Char,
+                    operator: *operator,
+                    right: ObjectType::Char,
+                }),
+            },
+            (Object::Array(mut left), Object::Array(right)) if *operator == ast::Infix::Plus => {
+                left.extend(right);
+                Ok(Object::Array(left))
+            }
+            (left @ Object::Array(_), right @ Object::Array(_))
+            | (left @ Object::Hash(_), right @ Object::Hash(_))
+            | (left @ Object::Function { .. }, right @ Object::Function { .. })
+            | (left @ Object::Builtin(_), right @ Object::Builtin(_)) => match operator {
+                ast::Infix::Eq | ast::Infix::NotEq => match left.equals(&right) {
+                    Some(equal) => Ok(Object::Bool(equal == (*operator == ast::Infix::Eq))),
+                    None => Err(MonkeyError::UnknownOperator {
+                        left: left.object_type(),
+                        operator: *operator,
+                        right: right.object_type(),
+                    }),
+                },
+                operator => Err(MonkeyError::UnknownOperator {
+                    left: left.object_type(),
+                    operator: *operator,
+                    right: right.object_type(),
+                }),
+            },
+            (left @ (Object::Array(_) | Object::Hash(_)), right)
+            | (left, right @ (Object::Array(_) | Object::Hash(_)))
+                if matches!(operator, ast::Infix::Eq | ast::Infix::NotEq) =>
+            {
+                Err(MonkeyError::TypeMismatch {
+                    operator: *operator,
+                    left: left.object_type(),
+                    right: right.object_type(),
+                })
+            }
+            (Object::String(left), Object::String(right))
+                if matches!(operator, ast::Infix::Eq | ast::Infix::NotEq) =>
+            {
+                Ok(Object::Bool(
+                    (left == right) == (*operator == ast::Infix::Eq),
+                ))
+            }
+            (
+                left @ (Object::String(_) | Object::Char(_)),
+                right @ (Object::Integer(_) | Object::Bool(_)),
+            )
+            | (
+                left @ (Object::Integer(_) | Object::Bool(_)),
+                right @ (Object::String(_) | Object::Char(_)),
+            )
+            | (left @ Object::String(_), right @ Object::Char(_))
+            | (left @ Object::Char(_), right @ Object::String(_)) => {
+                Err(MonkeyError::TypeMismatch {
+                    operator: *operator,
+                    left: left.object_type(),
+                    right: right.object_type(),
+                })
+            }
+            (Object::Integer(_), Object::Bool(_)) => Err(MonkeyError::TypeMismatch {
+                operator: *operator,
+                left: ObjectType::Integer,
+                right: ObjectType::Bool,
+            }),
+            (Object::Bool(_), Object::Integer(_)) => Err(MonkeyError::TypeMismatch {
+                operator: *operator,
+                left: ObjectType::Bool,
+                right: ObjectType::Integer,
+            }),
+            (left, right)
+                if matches!(
+                    operator,
+                    ast::Infix::LT | ast::Infix::GT | ast::Infix::LTEq | ast::Infix::GTEq
+                ) =>
+            {
+                self.eval_comparison(operator, &left, &right)
+            }
+            (left, right) if left.object_type() == right.object_type() => {
+                Err(MonkeyError::UnknownOperator {
+                    left: left.object_type(),
+                    operator: *operator,
+                    right: right.object_type(),
+                })
+            }
+            (left, right) => Err(MonkeyError::TypeMismatch {
+                operator: *operator,
+                left: left.object_type(),
+                right: right.object_type(),
+            }),
+        }
+    }
+
+    /// `needle in haystack`: an element equal to `needle` by `Object::equals`,
+    /// a key of a hash, or a substring (or char) of a string
+    fn eval_in_expression(
+        &mut self,
+        needle: Object,
+        haystack: Object,
+    ) -> Result<Object, MonkeyError> {
+        match (needle, haystack) {
+            (needle, Object::Array(elements)) => {
+                for element in elements.iter() {
+                    if equals(element, &needle)? {
+                        return Ok(Object::Bool(true));
+                    }
+                }
+                Ok(Object::Bool(false))
+            }
+            (needle, Object::Hash(pairs)) => {
+                Ok(Object::Bool(pairs.contains_key(&needle.hash_key()?)))
+            }
+            (Object::String(needle), Object::String(haystack)) => {
+                Ok(Object::Bool(haystack.contains(needle.as_str())))
+            }
+            (Object::Char(needle), Object::String(haystack)) => {
+                Ok(Object::Bool(haystack.contains(needle)))
+            }
+            (needle, haystack) => Err(MonkeyError::TypeMismatch {
+                operator: ast::Infix::In,
+                left: needle.object_type(),
+                right: haystack.object_type(),
+            }),
+        }
+    }
+
+    /// `<`, `>`, `<=` and `>=` on anything but two plain integers, via
+    /// `Object::compare`
+    fn eval_comparison(
+        &mut self,
+        operator: &ast::Infix,
+        left: &Object,
+        right: &Object,
+    ) -> Result<Object, MonkeyError> {
+        let ordering = compare(*operator, left, right)?;
+        Ok(Object::Bool(match operator {
+            ast::Infix::LT => ordering.is_lt(),
+            ast::Infix::LTEq => ordering.is_le(),
+            ast::Infix::GTEq => ordering.is_ge(),
+            _ => ordering.is_gt(),
+        }))
+    }
+
+    fn eval_integer_infix_expression(
+        &mut self,
+        operator: &ast::Infix,
+        left: i64,
+        right: i64,
+    ) -> Result<Object, MonkeyError> {
+        let result = match operator {
+            ast::Infix::Eq => return Ok(Object::Bool(left == right)),
+            ast::Infix::NotEq => return Ok(Object::Bool(left != right)),
+            ast::Infix::LT => return Ok(Object::Bool(left < right)),
+            ast::Infix::GT => return Ok(Object::Bool(left > right)),
+            ast::Infix::LTEq => return Ok(Object::Bool(left <= right)),
+            ast::Infix::GTEq => return Ok(Object::Bool(left >= right)),
+            ast::Infix::Plus => left.checked_add(right),
+            ast::Infix::Minus => left.checked_sub(right),
+            ast::Infix::Slash | ast::Infix::Percent if right == 0 => {
+                return Err(MonkeyError::DivisionByZero)
+            }
+            ast::Infix::Slash => left.checked_div(right),
+            ast::Infix::Asterisk => left.checked_mul(right),
+            // truncated like `/`, so `(a / b) * b + a % b == a` and `-7 % 3` is -1
+            ast::Infix::Percent => left.checked_rem(right),
+            ast::Infix::Pow if right < 0 => {
+                return Err(MonkeyError::NegativeExponent(right.to_string()))
+            }
+            ast::Infix::Pow => checked_pow(left, right),
+            // `??` and `|>` are handled by `eval_link`, `in` by `eval_in_expression`
+            ast::Infix::Coalesce | ast::Infix::Pipe | ast::Infix::In => {
+                return Err(MonkeyError::UnknownOperator {
+                    operator: *operator,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Integer,
+                })
+            }
+        };
+        match result {
+            Some(int) => Ok(Object::Integer(int)),
+            #[cfg(feature = "bigint")]
+            None if self.bigint => {
+                bigint::eval_infix_expression(operator, left.into(), right.into())
+            }
+            None => Err(MonkeyError::IntegerOverflow),
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    fn eval_big_integer_literal(&mut self, digits: &str) -> Result<Object, MonkeyError> {
+        if self.bigint {
+            bigint::parse_literal(digits)
+        } else {
+            Err(MonkeyError::InvalidInteger)
+        }
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    fn eval_big_integer_literal(&mut self, _digits: &str) -> Result<Object, MonkeyError> {
+        Err(MonkeyError::InvalidInteger)
+    }
+
+    fn eval_prefix_expression(
+        &mut self,
+        operator: &ast::Prefix,
+        right: Object,
+    ) -> Result<Object, MonkeyError> {
+        match operator {
+            ast::Prefix::Bang => match right {
+                Object::Bool(value) => Ok(Object::Bool(!value)),
+                Object::Null => Ok(Object::Bool(true)),
+                _ => Ok(Object::Bool(false)),
+            },
+            ast::Prefix::Minus => match right {
+                Object::Integer(int) => match int.checked_neg() {
+                    Some(int) => Ok(Object::Integer(int)),
+                    #[cfg(feature = "bigint")]
+                    None if self.bigint => Ok(bigint::normalize(-num_bigint::BigInt::from(int))),
+                    None => Err(MonkeyError::IntegerOverflow),
+                },
+                #[cfg(feature = "bigint")]
+                Object::BigInteger(int) => Ok(bigint::normalize(-int)),
+                _ => Ok(Object::Null),
+            },
+        }
+    }
+}
+
+/// `left == right` as `Object::equals` decides it, an error where a function
+/// makes it undecidable
+fn equals(left: &Object, right: &Object) -> Result<bool, MonkeyError> {
+    left.equals(right)
+        .ok_or_else(|| MonkeyError::UnknownOperator {
+            left: left.object_type(),
+            operator: ast::Infix::Eq,
+            right: right.object_type(),
+        })
+}
+
+/// `Object::compare`, or an error for `left operator right` where the pair
+/// is unordered
+fn compare(
+    operator: ast::Infix,
+    left: &Object,
+    right: &Object,
+) -> Result<std::cmp::Ordering, MonkeyError> {
+    match left.compare(right) {
+        Some(ordering) => Ok(ordering),
+        None if left.object_type() == right.object_type() => Err(MonkeyError::UnknownOperator {
+            left: left.object_type(),
+            operator,
+            right: right.object_type(),
+        }),
+        None => Err(MonkeyError::TypeMismatch {
+            operator,
+            left: left.object_type(),
+            right: right.object_type(),
+        }),
+    }
+}
+
+/// Position of `index` in a sequence of `len` elements, counting from the end
+/// when negative, so `-1` is the last element. `None` when out of range.
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    usize::try_from(index).ok().filter(|index| *index < len)
+}
+
+/// `base ** exp` for `exp >= 0`, or `None` on overflow
+fn checked_pow(base: i64, exp: i64) -> Option<i64> {
+    match u32::try_from(exp) {
+        Ok(exp) => base.checked_pow(exp),
+        // the only bases that do not overflow at such exponents
+        Err(_) => match base {
+            0 | 1 => Some(base),
+            -1 => Some(if exp % 2 == 0 { 1 } else { -1 }),
+            _ => None,
+        },
+    }
+}
+
+/// `start..end` of a slice of `len` elements, with missing bounds at either
+/// end, negative ones counted from the end and everything clamped to `len`.
+fn slice_bounds(start: Option<i64>, end: Option<i64>, len: usize) -> (usize, usize) {
+    let clamp = |bound: i64| {
+        let bound = if bound < 0 { bound + len as i64 } else { bound };
+        bound.clamp(0, len as i64) as usize
+    };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    (start, end.max(start))
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, cmp::Ordering, collections::HashMap, rc::Rc};
+
+    use crate::{
+        error::MonkeyError,
+        eval::{
+            environment::Environment,
+            hook::HookAction,
+            object::{HashKey, InspectOptions, MAX_HASH_LEN},
+            EvalOptions, Evaluator, Object, ObjectType, Policy, Truthiness, MAX_CALL_DEPTH,
+            MAX_RANGE_LEN,
+        },
+        lexer::Lexer,
+        parser::{
+            ast::{self, Program},
+            Parser,
         },
     };
 
     fn generate_program(input: &str) -> Program {
         let l = Lexer::new(input.to_string());
         let mut p = Parser::new(l);
-        let program = p.parse_program().unwrap();
-        program
+        p.parse_program().unwrap()
     }
 
     fn evaluate_program(input: &str) -> Object {
@@ -330,10 +1580,40 @@ mod tests {
             ("1 > 2", Object::Bool(false)),
             ("1 < 1", Object::Bool(false)),
             ("1 > 1", Object::Bool(false)),
+            ("5 <= 5", Object::Bool(true)),
+            ("4 <= 5", Object::Bool(true)),
+            ("6 <= 5", Object::Bool(false)),
+            ("5 >= 5", Object::Bool(true)),
+            ("4 >= 5", Object::Bool(false)),
+            ("-1 >= -2", Object::Bool(true)),
+            ("7 % 3", Object::Integer(1)),
+            ("-7 % 3", Object::Integer(-1)),
+            ("7 % -3", Object::Integer(1)),
+            ("-7 % -3", Object::Integer(-1)),
+            ("6 % 3", Object::Integer(0)),
+            ("1 + 7 % 4 * 2", Object::Integer(7)),
+            (
+                "let even = fn(n) { n % 2 == 0 }; even(10)",
+                Object::Bool(true),
+            ),
+            ("2 ** 10", Object::Integer(1024)),
+            ("2 ** 3 ** 2", Object::Integer(512)),
+            ("(2 ** 3) ** 2", Object::Integer(64)),
+            ("-2 ** 2", Object::Integer(-4)),
+            ("(-2) ** 3", Object::Integer(-8)),
+            ("7 ** 0", Object::Integer(1)),
+            ("0 ** 0", Object::Integer(1)),
+            ("1 ** 9999999999", Object::Integer(1)),
+            ("-1 ** 9999999999", Object::Integer(-1)),
+            ("(-1) ** 9999999999", Object::Integer(-1)),
+            ("2 ** 62", Object::Integer(1 << 62)),
             ("1 == 1", Object::Bool(true)),
             ("1 == 2", Object::Bool(false)),
             ("1 != 2", Object::Bool(true)),
-            (r#""Hello World!""#, Object::String("Hello World!".to_string()))
+            (
+                r#""Hello World!""#,
+                Object::String("Hello World!".to_string()),
+            ),
         ];
         for (input, expected) in tests {
             let actual = evaluate_program(input);
@@ -341,6 +1621,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_unicode_strings() {
+        for text in ["日本語", "👍", "héllo wörld"] {
+            let input = format!("let s = \"{}\"; [s, s]", text);
+            let actual = evaluate_program(&input);
+            assert_eq!(
+                actual,
+                Object::Array(vec![Object::String(text.to_string()); 2])
+            );
+            assert_eq!(format!("{}", actual), format!("[{}, {}]", text, text));
+        }
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let tests = [
+            (
+                "let 名前 = \"monkey\"; 名前",
+                Object::String("monkey".to_string()),
+            ),
+            (
+                "let café = 1; let cafe = 2; café + cafe * 10",
+                Object::Integer(21),
+            ),
+            ("let f = fn(α, β2) { α - β2 }; f(5, 3)", Object::Integer(2)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected);
+        }
+    }
+
     #[test]
     fn test_if_else_expression() {
         let tests = [
@@ -351,6 +1662,18 @@ mod tests {
             ("if (1 > 2) { 10 }", Object::Null),
             ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
             ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+            ("if (false) { 1 } else if (true) { 2 } else { 3 }", Object::Integer(2)),
+            ("if (false) { 1 } else if (false) { 2 } else { 3 }", Object::Integer(3)),
+            ("if (true) { 1 } else if (missing) { 2 } else { 3 }", Object::Integer(1)),
+            ("if (false) { 1 } else if (false) { 2 }", Object::Null),
+            (
+                "let grade = fn(n) { if (n >= 90) { 'A' } else if (n >= 80) { 'B' } else { 'C' } }; [grade(95), grade(85), grade(10)]",
+                Object::Array(vec![Object::Char('A'), Object::Char('B'), Object::Char('C')]),
+            ),
+            (
+                "let f = fn(n) { if (n == 0) { return 0; } else if (n < 0) { return -1; }; 1 }; [f(0), f(-5), f(5)]",
+                Object::Array(vec![Object::Integer(0), Object::Integer(-1), Object::Integer(1)]),
+            ),
         ];
 
         for (input, expected) in tests {
@@ -360,67 +1683,178 @@ mod tests {
     }
 
     #[test]
-    fn test_let_statements() {
+    fn test_array_literals() {
         let tests = [
-            ("let a = 5; a;", Object::Integer(5)),
-            ("let a = 5 * 5; a;", Object::Integer(25)),
-            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            ("[]", Object::Array(vec![])),
             (
-                "let a = 5; let b = a; let c = a + b + 5; c;",
-                Object::Integer(15),
+                "[1, 2 * 2, 3 + 3]",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(4),
+                    Object::Integer(6),
+                ]),
+            ),
+            (
+                "[[1], [], true]",
+                Object::Array(vec![
+                    Object::Array(vec![Object::Integer(1)]),
+                    Object::Array(vec![]),
+                    Object::Bool(true),
+                ]),
             ),
         ];
         for (input, expected) in tests {
             let actual = evaluate_program(input);
             assert_eq!(actual, expected)
         }
-    }
 
-    #[test]
-    fn test_return_statements() {
         let tests = [
-            ("return 10;", Object::Integer(10)),
-            ("return 10; 9;", Object::Integer(10)),
-            ("return 2 * 5; 9;", Object::Integer(10)),
-            ("9; return 2 * 5; 9;", Object::Integer(10)),
-            (
-                "if (10 > 1) {
-                if (10 > 1) {
-                    return 10;
-                }
-                return 1;
-            }
-            ",
-                Object::Integer(10),
-            ),
+            (r#"[1, 2 * 2, "three"]"#, "[1, 4, three]"),
+            ("[[1, [2]], []]", "[[1, [2]], []]"),
+            ("let a = [1, fn(x) { x }]; a", "[1, fn(x){x;}]"),
         ];
-
         for (input, expected) in tests {
             let actual = evaluate_program(input);
-            assert_eq!(actual, expected)
+            assert_eq!(format!("{}", actual), expected)
         }
+
+        assert_eq!(
+            evaluate_error_program("[1, 2 + true, missing]"),
+            MonkeyError::TypeMismatch {
+                operator: ast::Infix::Plus,
+                left: ObjectType::Integer,
+                right: ObjectType::Bool,
+            }
+        );
     }
 
     #[test]
-    fn test_error_handling() {
+    fn test_array_concatenation() {
         let tests = [
-            (
-                "5 + true;",
-                MonkeyError::TypeMismatch {
-                    operator: ast::Infix::Plus,
-                    left: ObjectType::Integer,
-                    right: ObjectType::Bool,
+            ("[1, 2] + [3]", "[1, 2, 3]"),
+            ("[] + []", "[]"),
+            ("[] + [1]", "[1]"),
+            ("[1] + []", "[1]"),
+            (r#"[1, "two"] + ['3', [4], true]"#, "[1, two, 3, [4], true]"),
+            ("let a = [1]; let b = a + a + a; [a, b]", "[[1], [1, 1, 1]]"),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(format!("{}", actual), expected, "{}", input)
+        }
+
+        let tests = [
+            ("[1] - [1]", ast::Infix::Minus),
+            ("[1] * [2]", ast::Infix::Asterisk),
+            ("[1] < [2]", ast::Infix::LT),
+        ];
+        for (input, operator) in tests {
+            assert_eq!(
+                evaluate_error_program(input),
+                MonkeyError::UnknownOperator {
+                    left: ObjectType::Array,
+                    operator,
+                    right: ObjectType::Array,
+                },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_array_equality() {
+        let tests = [
+            ("[1, 2] == [1, 2]", true),
+            ("[1, 2] == [2, 1]", false),
+            ("[1, 2] != [1, 2, 3]", true),
+            ("[1, 2, 3] == [1, 2]", false),
+            ("[] == []", true),
+            ("[] != [[]]", true),
+            ("[1, [2]] == [1, [2]]", true),
+            ("[1, [2, [3]]] == [1, [2, [4]]]", false),
+            (r#"["a", 'b', true] == ["a", 'b', true]"#, true),
+            (r#"["a"] == ["b"]"#, false),
+            ("[if (false) { 1 }] == [if (false) { 2 }]", true),
+            ("[if (false) { 1 }] == [0]", false),
+            // different element types are unequal, not an error
+            (r#"[1, "1"] == ["1", 1]"#, false),
+            ("let a = [1, 2]; a + [3] == [1, 2, 3]", true),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), Object::Bool(expected), "{}", input);
+        }
+
+        let tests = [
+            (
+                "[1] == 1",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Eq,
+                    left: ObjectType::Array,
+                    right: ObjectType::Integer,
                 },
             ),
             (
-                "5 + true; 5",
+                r#""[]" != []"#,
                 MonkeyError::TypeMismatch {
-                    operator: ast::Infix::Plus,
+                    operator: ast::Infix::NotEq,
+                    left: ObjectType::String,
+                    right: ObjectType::Array,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let tests = [
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1]", Object::Integer(2)),
+            ("[1, 2, 3][2]", Object::Integer(3)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Object::Integer(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];",
+                Object::Integer(6),
+            ),
+            (
+                "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+                Object::Integer(2),
+            ),
+            ("[[1, 2], [3, 4]][1][0]", Object::Integer(3)),
+            ("fn() { [5, 6, 7] }()[2]", Object::Integer(7)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Integer(3)),
+            ("[1, 2, 3][-3]", Object::Integer(1)),
+            ("let i = 1; [1, 2, 3][-i - 1]", Object::Integer(2)),
+            ("[1, 2, 3][-4]", Object::Null),
+            ("[][0]", Object::Null),
+            ("[][-1]", Object::Null),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(actual, expected)
+        }
+
+        let tests = [
+            (
+                r#"[1, 2, 3]["0"]"#,
+                MonkeyError::IndexNotSupported {
+                    left: ObjectType::Array,
+                    index: ObjectType::String,
+                },
+            ),
+            (
+                "1[0]",
+                MonkeyError::IndexNotSupported {
                     left: ObjectType::Integer,
-                    right: ObjectType::Bool,
+                    index: ObjectType::Integer,
                 },
             ),
-            ("foobar", MonkeyError::IdentifierNotFound),
         ];
         for (input, expected) in tests {
             let actual = evaluate_error_program(input);
@@ -429,20 +1863,4722 @@ mod tests {
     }
 
     #[test]
-    fn test_function_object() {
-        let tests = [("fn(x) { x + 2;};")];
-        for input in tests {
+    fn test_ranges() {
+        let tests = [
+            ("0..3", "[0, 1, 2]"),
+            ("-2..1", "[-2, -1, 0]"),
+            ("let n = 2; 1..n * 2", "[1, 2, 3]"),
+            ("(0..10)[9]", "9"),
+            ("3..3", "[]"),
+            ("5..1", "[]"),
+            // the longest range allowed
+            (
+                &format!("len(0..{})", MAX_RANGE_LEN),
+                &MAX_RANGE_LEN.to_string(),
+            ),
+        ];
+        for (input, expected) in tests {
             let actual = evaluate_program(input);
-            if let Object::Function {
-                parameters,
-                body,
-                env,
-            } = actual
-            {
-                assert_eq!(parameters.len(), 1);
-                assert_eq!(format!("{}", parameters[0]), "x".to_string());
-                // assert_eq!(format!("{}", body), "(x + 2)".to_string())
+            assert_eq!(format!("{}", actual), expected, "{}", input)
+        }
+
+        let tests = [
+            (
+                r#"1.."5""#,
+                MonkeyError::InvalidRange {
+                    start: ObjectType::Integer,
+                    end: ObjectType::String,
+                },
+            ),
+            (
+                "true..1",
+                MonkeyError::InvalidRange {
+                    start: ObjectType::Bool,
+                    end: ObjectType::Integer,
+                },
+            ),
+            (
+                "1..2..3",
+                MonkeyError::InvalidRange {
+                    start: ObjectType::Array,
+                    end: ObjectType::Integer,
+                },
+            ),
+            (
+                "len(0..100000000)",
+                MonkeyError::RangeTooLong {
+                    length: 100_000_000,
+                    limit: MAX_RANGE_LEN,
+                },
+            ),
+            (
+                "-9223372036854775807..9223372036854775807",
+                MonkeyError::RangeTooLong {
+                    length: u64::MAX as u128 - 1,
+                    limit: MAX_RANGE_LEN,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected)
+        }
+    }
+
+    #[test]
+    fn test_string_index_expressions() {
+        let tests = [
+            (r#""hello"[0]"#, Object::Char('h')),
+            (r#""héllo"[1]"#, Object::Char('é')),
+            (r#""héllo"[2]"#, Object::Char('l')),
+            (r#""日本語"[-1]"#, Object::Char('語')),
+            (r#"let s = "abc"; s[1 + 1]"#, Object::Char('c')),
+            (r#""héllo"[5]"#, Object::Null),
+            (r#""héllo"[-6]"#, Object::Null),
+            ("\"\"[0]", Object::Null),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(actual, expected, "{}", input)
+        }
+
+        assert_eq!(
+            evaluate_error_program(r#""abc"["a"]"#),
+            MonkeyError::IndexNotSupported {
+                left: ObjectType::String,
+                index: ObjectType::String,
+            }
+        );
+
+        // an indexed char is not the one-char string, so comparing the two
+        // is an error rather than a quiet null
+        assert_eq!(evaluate_program(r#""abc"[0] == 'a'"#), Object::Bool(true));
+        for (input, operator, left, right) in [
+            (
+                r#""abc"[0] == "a""#,
+                ast::Infix::Eq,
+                ObjectType::Char,
+                ObjectType::String,
+            ),
+            (
+                r#""a" != 'a'"#,
+                ast::Infix::NotEq,
+                ObjectType::String,
+                ObjectType::Char,
+            ),
+            (
+                "'1' == 1",
+                ast::Infix::Eq,
+                ObjectType::Char,
+                ObjectType::Integer,
+            ),
+            (
+                "true != 'a'",
+                ast::Infix::NotEq,
+                ObjectType::Bool,
+                ObjectType::Char,
+            ),
+        ] {
+            assert_eq!(
+                evaluate_error_program(input),
+                MonkeyError::TypeMismatch {
+                    operator,
+                    left,
+                    right,
+                },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_inspect() {
+        let tests = [
+            ("42", "42"),
+            ("true", "true"),
+            ("if (false) { 1 }", "null"),
+            ("\"tab\there\nnewline\"", r#""tab\there\nnewline""#),
+            (r#""héllo""#, r#""héllo""#),
+            ("'a'", "'a'"),
+            ("'\"'", "'\"'"),
+            ("[]", "[]"),
+            ("{}", "{}"),
+            (r#"[1, "two", '3', [true]]"#, r#"[1, "two", '3', [true]]"#),
+            (
+                r#"{"b": [1], "a": {1: "x"}, 'c': false}"#,
+                r#"{"a": {1: "x"}, "b": [1], 'c': false}"#,
+            ),
+            ("fn(x, y) { x + y }", "fn(x, y) { (x+y); }"),
+            (
+                "fn(x) { let y = x * 2; y * y + y - 1 }",
+                "fn(x) { let y = (x*2);(((y*y)+y)... }",
+            ),
+            ("fn() { return 1; }()", "1"),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(actual.inspect(), expected, "{}", input)
+        }
+
+        // Display is unchanged
+        assert_eq!(format!("{}", evaluate_program(r#"["a", 'b']"#)), "[a, b]");
+
+        let nested = evaluate_program(r#"[1, [2, [3, [4]]], {"k": [5]}, 6, 7]"#);
+        let tests = [
+            (
+                InspectOptions::default(),
+                r#"[1, [2, [3, [4]]], {"k": [5]}, 6, 7]"#,
+            ),
+            (
+                InspectOptions {
+                    max_depth: 2,
+                    ..InspectOptions::default()
+                },
+                r#"[1, [2, [...]], {"k": [...]}, 6, 7]"#,
+            ),
+            (
+                InspectOptions {
+                    max_depth: 0,
+                    ..InspectOptions::default()
+                },
+                "[...]",
+            ),
+            (
+                InspectOptions {
+                    max_width: 2,
+                    ..InspectOptions::default()
+                },
+                "[1, [2, [3, [4]]], ...]",
+            ),
+            (
+                InspectOptions {
+                    max_depth: 1,
+                    max_width: 3,
+                },
+                r#"[1, [...], {...}, ...]"#,
+            ),
+        ];
+        for (opts, expected) in tests {
+            assert_eq!(nested.inspect_limited(opts), expected, "{:?}", opts)
+        }
+    }
+
+    #[test]
+    fn test_slice_expressions() {
+        let tests = [
+            (r#""hello world"[0:5]"#, "hello"),
+            (r#""hello world"[6:]"#, "world"),
+            (r#""hello"[:-1]"#, "hell"),
+            (r#""hello"[-3:]"#, "llo"),
+            (r#""héllo wörld"[1:4]"#, "éll"),
+            (r#""日本語"[1:]"#, "本語"),
+            (r#""hello"[:]"#, "hello"),
+            (r#""hello"[2:100]"#, "llo"),
+            (r#""hello"[-100:2]"#, "he"),
+            (r#""hello"[3:1]"#, ""),
+            (r#""hello"[5:]"#, ""),
+            ("[1, 2, 3, 4][1:3]", "[2, 3]"),
+            ("[1, 2, 3, 4][-2:]", "[3, 4]"),
+            ("[1, 2, 3][:10]", "[1, 2, 3]"),
+            ("[1, 2, 3][2:0]", "[]"),
+            ("[][0:1]", "[]"),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(format!("{}", actual), expected, "{}", input)
+        }
+        assert_eq!(
+            evaluate_program(r#""héllo"[0:2]"#),
+            Object::String("hé".to_string())
+        );
+
+        let tests = [
+            (
+                r#""hello"["a":]"#,
+                MonkeyError::IndexNotSupported {
+                    left: ObjectType::String,
+                    index: ObjectType::String,
+                },
+            ),
+            (
+                "[1, 2][0:true]",
+                MonkeyError::IndexNotSupported {
+                    left: ObjectType::Array,
+                    index: ObjectType::Bool,
+                },
+            ),
+            (
+                "1[0:1]",
+                MonkeyError::IndexNotSupported {
+                    left: ObjectType::Integer,
+                    index: ObjectType::Integer,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input)
+        }
+    }
+
+    #[test]
+    fn test_eval_options() {
+        let tests = [
+            ("10 / 0", Err(MonkeyError::DivisionByZero), Object::Null),
+            (
+                "let f = fn(x) { x % 0 }; [f(1), 2]",
+                Err(MonkeyError::DivisionByZero),
+                Object::Array(vec![Object::Null, Object::Integer(2)]),
+            ),
+            (
+                "missing",
+                Err(MonkeyError::IdentifierNotFound("missing".to_string())),
+                Object::Null,
+            ),
+            (
+                "if (missing) { 1 } else { 2 }",
+                Err(MonkeyError::IdentifierNotFound("missing".to_string())),
+                Object::Integer(2),
+            ),
+            // out-of-range indices were `null` before policies existed
+            ("[1, 2, 3][5]", Ok(Object::Null), Object::Null),
+            (r#""abc"[-4]"#, Ok(Object::Null), Object::Null),
+            // only the listed failures are recoverable
+            (
+                "1 + true",
+                Err(MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Bool,
+                }),
+                Object::Null,
+            ),
+        ];
+        for (input, default, lenient) in tests {
+            let mut eval = Evaluator::new();
+            assert_eq!(eval.eval_source(input), default, "{}", input);
+
+            let mut eval = Evaluator::new();
+            eval.set_options(EvalOptions::lenient());
+            let expected = match default {
+                Err(MonkeyError::TypeMismatch { .. }) => default,
+                _ => Ok(lenient),
+            };
+            assert_eq!(eval.eval_source(input), expected, "{}", input);
+        }
+
+        let mut eval = Evaluator::new();
+        eval.set_options(EvalOptions {
+            index_out_of_range: Policy::Error,
+            ..EvalOptions::default()
+        });
+        assert_eq!(
+            eval.eval_source("[1, 2, 3][3]"),
+            Err(MonkeyError::IndexOutOfRange {
+                index: 3,
+                length: 3,
+            })
+        );
+        assert_eq!(
+            eval.eval_source(r#""héllo"[-6]"#),
+            Err(MonkeyError::IndexOutOfRange {
+                index: -6,
+                length: 5,
+            })
+        );
+        assert_eq!(eval.eval_source("[1, 2, 3][-1]"), Ok(Object::Integer(3)));
+        // missing hash keys are not out of range
+        assert_eq!(eval.eval_source(r#"{"a": 1}["b"]"#), Ok(Object::Null));
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let tests = [
+            (r#"let h = {"a": 1}; h["missing"] ?? 0"#, Object::Integer(0)),
+            (r#"let h = {"a": 1}; h["a"] ?? 0"#, Object::Integer(1)),
+            ("[1, 2][5] ?? -1", Object::Integer(-1)),
+            ("false ?? true", Object::Bool(false)),
+            ("0 ?? 1", Object::Integer(0)),
+            (r#""" ?? "default""#, Object::String("".to_string())),
+            ("[][0] ?? [][1] ?? 3", Object::Integer(3)),
+            ("[][0] ?? 2 ?? 3", Object::Integer(2)),
+            ("[][0] ?? 1 + 2", Object::Integer(3)),
+            // the right side is never evaluated when the left is not null
+            ("1 ?? missing", Object::Integer(1)),
+            ("1 ?? 1 / 0", Object::Integer(1)),
+            ("[][0] ?? 2 ?? missing", Object::Integer(2)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        assert_eq!(
+            evaluate_error_program("[][0] ?? missing"),
+            MonkeyError::IdentifierNotFound("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_safe_navigation() {
+        let config = r#"let config = {"server": {"tls": {"cert": "a.pem"}, "port": 80}};"#;
+        let tests = [
+            (
+                r#"config?["server"]?["tls"]?["cert"]"#,
+                Object::String("a.pem".to_string()),
+            ),
+            (
+                "config?.server?.tls?.cert",
+                Object::String("a.pem".to_string()),
+            ),
+            ("config?.server?.port", Object::Integer(80)),
+            // a missing link anywhere in the chain ends it with null
+            ("config?.client?.tls?.cert", Object::Null),
+            ("config?.server?.ssl?.cert", Object::Null),
+            ("config?.server?.tls?.key", Object::Null),
+            ("config?.client?.tls ?? 443", Object::Integer(443)),
+            ("[]?[0]?[1]", Object::Null),
+            ("[[1, 2]]?[0]?[1]", Object::Integer(2)),
+            // the index is never evaluated once the chain is broken
+            ("config?.client?[missing]", Object::Null),
+            ("config?.client?[1 / 0]", Object::Null),
+        ];
+        for (input, expected) in tests {
+            let input = format!("{} {}", config, input);
+            assert_eq!(evaluate_program(&input), expected, "{}", input);
+        }
+
+        // only null short-circuits; indexing anything else still fails
+        assert_eq!(
+            evaluate_error_program("1?[0]"),
+            MonkeyError::IndexNotSupported {
+                left: ObjectType::Integer,
+                index: ObjectType::Integer,
             }
+        );
+    }
+
+    #[test]
+    fn test_in() {
+        let tests = [
+            ("2 in [1, 2, 3]", Object::Bool(true)),
+            ("4 in [1, 2, 3]", Object::Bool(false)),
+            ("[1] in [[1], [2]]", Object::Bool(true)),
+            ("1 in []", Object::Bool(false)),
+            (r#""k" in {"k": 1}"#, Object::Bool(true)),
+            (r#""v" in {"k": "v"}"#, Object::Bool(false)),
+            ("true in {true: 1}", Object::Bool(true)),
+            (r#""ell" in "hello""#, Object::Bool(true)),
+            (r#""" in "hello""#, Object::Bool(true)),
+            (r#""olé" in "hello""#, Object::Bool(false)),
+            (r#"'e' in "hello""#, Object::Bool(true)),
+            ("!(3 in [1, 2])", Object::Bool(true)),
+            ("!(1 in [1, 2])", Object::Bool(false)),
+            ("1 + 1 in [2] == true", Object::Bool(true)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let errors = [
+            (
+                "1 in 2",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::In,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"1 in "123""#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::In,
+                    left: ObjectType::Integer,
+                    right: ObjectType::String,
+                },
+            ),
+            (
+                "[] in {}",
+                MonkeyError::UnusableAsHashKey(ObjectType::Array),
+            ),
+        ];
+        for (input, expected) in errors {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_pipe() {
+        let tests = [
+            (
+                "let double = fn(x) { x * 2 }; 3 |> double",
+                Object::Integer(6),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; 10 |> sub(3)",
+                Object::Integer(7),
+            ),
+            (
+                "let sub = fn(a, b) { a - b }; 10 |> sub(3) |> sub(2)",
+                Object::Integer(5),
+            ),
+            (
+                "let add = fn(a, b) { a + b }; 1 + 2 |> add(3)",
+                Object::Integer(6),
+            ),
+            (
+                "let adder = fn(n) { fn(x) { x + n } }; let add10 = adder(10); 1 |> add10",
+                Object::Integer(11),
+            ),
+            ("2 |> fn(x) { x * x }", Object::Integer(4)),
+            (
+                "let first = fn(xs) { xs[0] }; [7, 8] |> first",
+                Object::Integer(7),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let errors = [
+            ("1 |> 2", MonkeyError::NotCallable(ObjectType::Integer)),
+            (
+                "let x = 1; 2 |> x(3)",
+                MonkeyError::NotCallable(ObjectType::Integer),
+            ),
+            (r#"1 |> "f""#, MonkeyError::NotCallable(ObjectType::String)),
+            (
+                "let id = fn(x) { x }; 1 |> id(2)",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+            (
+                "1 |> missing",
+                MonkeyError::IdentifierNotFound("missing".to_string()),
+            ),
+        ];
+        for (input, expected) in errors {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+        // the same error as calling a non-function directly
+        assert_eq!(
+            evaluate_error_program("let x = 1; x(2, 3)"),
+            MonkeyError::NotCallable(ObjectType::Integer)
+        );
+    }
+
+    #[test]
+    fn test_ternary() {
+        let tests = [
+            ("true ? 1 : 2", Object::Integer(1)),
+            ("false ? 1 : 2", Object::Integer(2)),
+            ("1 < 2 ? 10 : 20", Object::Integer(10)),
+            ("let x = 5; x > 3 ? x * 2 : x", Object::Integer(10)),
+            ("let n = 0; n < 0 ? -1 : n == 0 ? 0 : 1", Object::Integer(0)),
+            ("let n = 7; n < 0 ? -1 : n == 0 ? 0 : 1", Object::Integer(1)),
+            (
+                r#"{"k": 1 > 2 ? "yes" : "no"}["k"]"#,
+                Object::String("no".to_string()),
+            ),
+            ("(true ? 1 : 2) + 1", Object::Integer(2)),
+            // only the selected branch is evaluated
+            ("true ? 1 : missing", Object::Integer(1)),
+            ("false ? 1 / 0 : 2", Object::Integer(2)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        assert_eq!(
+            evaluate_error_program("false ? 1 : missing"),
+            MonkeyError::IdentifierNotFound("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assignment() {
+        let tests = [
+            ("let x = 1; x = 2; x", Object::Integer(2)),
+            ("let x = 1; x = x + 1; x = x * 10; x", Object::Integer(20)),
+            (
+                r#"let x = 1; x = "one"; x"#,
+                Object::String("one".to_string()),
+            ),
+            ("let xs = [1, 2]; xs[0] = 5; xs[0]", Object::Integer(5)),
+            (r#"let h = {}; h["k"] = 1; h["k"]"#, Object::Integer(1)),
+            (
+                "let grid = [[0, 0], [0, 0]]; grid[0][1] = 7; grid",
+                Object::Array(vec![
+                    Object::Array(vec![Object::Integer(0), Object::Integer(7)]),
+                    Object::Array(vec![Object::Integer(0), Object::Integer(0)]),
+                ]),
+            ),
+            // arrays are values: the copy is not changed
+            ("let a = [1]; let b = a; b[0] = 2; a[0]", Object::Integer(1)),
+            // updates the scope where `x` was defined, not a new inner binding
+            (
+                "let x = 1; let set = fn() { x = 2; }; set(); x",
+                Object::Integer(2),
+            ),
+            ("let x = 1; if (true) { x = 2; }; x", Object::Integer(2)),
+            // parameters and `let` inside a function still shadow
+            (
+                "let x = 1; let f = fn(x) { x = 5; x }; [f(0), x][1]",
+                Object::Integer(1),
+            ),
+            (
+                "let x = 1; let f = fn() { let x = 0; x = 5; }; f(); x",
+                Object::Integer(1),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        // two closures sharing the environment of one call
+        let input = "
+            let counter = fn() {
+                let count = 0;
+                let increment = fn() { count = count + 1; };
+                let current = fn() { count };
+                [increment, current]
+            };
+            let c = counter();
+            let other = counter();
+            c[0]();
+            c[0]();
+            other[0]();
+            [c[1](), other[1]()]
+        ";
+        assert_eq!(
+            evaluate_program(input),
+            Object::Array(vec![Object::Integer(2), Object::Integer(1)])
+        );
+
+        assert_eq!(
+            evaluate_error_program("x = 1"),
+            MonkeyError::IdentifierNotFound("x".to_string())
+        );
+        assert_eq!(
+            evaluate_error_program("let f = fn() { y = 1; }; f()"),
+            MonkeyError::IdentifierNotFound("y".to_string())
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let tests = [
+            ("let x = 1; x += 2; x", Object::Integer(3)),
+            ("let x = 1; x -= 2; x", Object::Integer(-1)),
+            ("let x = 3; x *= 4; x", Object::Integer(12)),
+            ("let x = 7; x /= 2; x", Object::Integer(3)),
+            (
+                "let xs = [1, 2, 3]; xs[1] += 10; xs",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(12),
+                    Object::Integer(3),
+                ]),
+            ),
+            ("let xs = [1, 2, 3]; xs[-1] *= 5; xs[2]", Object::Integer(15)),
+            (
+                "let grid = [[1, 2], [3, 4]]; grid[1][0] -= 3; grid[1]",
+                Object::Array(vec![Object::Integer(0), Object::Integer(4)]),
+            ),
+            (r#"let h = {"n": 1}; h["n"] += 1; h["n"]"#, Object::Integer(2)),
+            (
+                "let i = 0; let total = 0; loop { i += 1; total += i; if (i == 4) { break total; } }",
+                Object::Integer(10),
+            ),
+            // updates the binding where it was defined, not a shadow
+            ("let x = 1; let bump = fn() { x += 1; }; bump(); bump(); x", Object::Integer(3)),
+            ("let x = 1; x += 1", Object::Null),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let errors = [
+            ("y += 1", MonkeyError::IdentifierNotFound("y".to_string())),
+            (
+                "xs[0] += 1",
+                MonkeyError::IdentifierNotFound("xs".to_string()),
+            ),
+            ("let x = 1; x /= 0", MonkeyError::DivisionByZero),
+            (
+                "let x = 1; x += true",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Bool,
+                },
+            ),
+            (
+                "let b = true; b -= 1",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Minus,
+                    left: ObjectType::Bool,
+                    right: ObjectType::Integer,
+                },
+            ),
+            (
+                "let xs = [1]; xs[3] += 1",
+                MonkeyError::IndexOutOfRange {
+                    index: 3,
+                    length: 1,
+                },
+            ),
+            (
+                "let n = 1; n[0] += 1",
+                MonkeyError::IndexNotSupported {
+                    left: ObjectType::Integer,
+                    index: ObjectType::Integer,
+                },
+            ),
+        ];
+        for (input, expected) in errors {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_statement_hooks() {
+        let program = generate_program("let a = 1; let b = 2; let c = 3; let d = 4;");
+        let mut eval = Evaluator::new();
+        let mut count = 0;
+        eval.on_statement(move |_, _| {
+            count += 1;
+            if count == 3 {
+                HookAction::Abort("third statement".to_string())
+            } else {
+                HookAction::Continue
+            }
+        });
+        assert_eq!(
+            eval.evaluate(&program),
+            Err(MonkeyError::AbortedByHost("third statement".to_string()))
+        );
+        assert_eq!(eval.get("b"), Some(Object::Integer(2)));
+        assert_eq!(eval.get("c"), None);
+
+        let program =
+            generate_program("let double = fn(x) { x * 2 }; if (true) { double(2); } return 1;");
+        let mut eval = Evaluator::new();
+        let seen = Rc::new(RefCell::new(vec![]));
+        let recorder = Rc::clone(&seen);
+        eval.on_statement(move |stmt, _| {
+            recorder.borrow_mut().push(format!("{}", stmt));
+            HookAction::Continue
+        });
+        assert_eq!(eval.evaluate(&program), Ok(Object::Integer(1)));
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                "let double = fn(x){(x*2);};",
+                "if(true){double(2);};",
+                "double(2);",
+                "(x*2);",
+                "return 1;",
+            ]
+        );
+
+        // the hook sees the environment as it is before the statement runs
+        let mut eval = Evaluator::new();
+        eval.on_statement(|_, env| match env.clone().get("secret") {
+            Some(_) => HookAction::Abort("secret was bound".to_string()),
+            None => HookAction::Continue,
+        });
+        assert_eq!(
+            eval.eval_source("let secret = 1; 2"),
+            Err(MonkeyError::AbortedByHost("secret was bound".to_string()))
+        );
+
+        // a program cannot catch a host abort
+        let mut eval = Evaluator::new();
+        eval.on_statement(|stmt, _| match stmt {
+            ast::Statement::Return(_) => HookAction::Abort("no returns".to_string()),
+            _ => HookAction::Continue,
+        });
+        assert_eq!(
+            eval.eval_source("try { return 1; } catch (e) { 2 }"),
+            Err(MonkeyError::AbortedByHost("no returns".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"let two = "two";
+        {
+            "one": 10 - 9,
+            two: 1 + 1,
+            ["three"][0]: 6 / 2,
+            4: 4,
+            true: 5,
+            false: 6
+        }"#;
+        let expected = Object::Hash(
+            [
+                (HashKey::String("one".to_string()), Object::Integer(1)),
+                (HashKey::String("two".to_string()), Object::Integer(2)),
+                (HashKey::String("three".to_string()), Object::Integer(3)),
+                (HashKey::Integer(4), Object::Integer(4)),
+                (HashKey::Bool(true), Object::Integer(5)),
+                (HashKey::Bool(false), Object::Integer(6)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(evaluate_program(input), expected);
+        assert_eq!(evaluate_program("{}"), Object::Hash(HashMap::default()));
+        assert_eq!(
+            evaluate_program(r#"{"a": 1, "a": 2}"#),
+            Object::Hash(
+                [(HashKey::String("a".to_string()), Object::Integer(2))]
+                    .into_iter()
+                    .collect()
+            )
+        );
+        assert_eq!(
+            format!("{}", evaluate_program(r#"{"b": [1], 2: "x", true: {}}"#)),
+            "{2: x, true: {}, b: [1]}"
+        );
+
+        let tests = [
+            (
+                r#"{fn(x) { x }: 1}"#,
+                MonkeyError::UnusableAsHashKey(ObjectType::Function),
+            ),
+            (
+                r#"{[1]: 1}"#,
+                MonkeyError::UnusableAsHashKey(ObjectType::Array),
+            ),
+            (
+                r#"{{}: 1}"#,
+                MonkeyError::UnusableAsHashKey(ObjectType::Hash),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_seed() {
+        let results = generate_program(
+            r#"let h = {}; for (i in 0..100) { h[str(i)] = i; }; [h, keys(h), h["42"], delete(h, "7")]"#,
+        );
+        let filled = generate_program(r#"let h = {}; for (i in 0..100) { h[str(i)] = i; }; h"#);
+        let evaluate = |program: &Program, seed: Option<u64>| {
+            let mut evaluator = Evaluator::new();
+            if let Some(seed) = seed {
+                evaluator.set_hash_seed(seed);
+            }
+            evaluator.evaluate(program).unwrap()
+        };
+
+        // whatever the key, scripts see the same thing
+        let unseeded = evaluate(&results, None);
+        for seed in [1, 2] {
+            let seeded = evaluate(&results, Some(seed));
+            assert_eq!(seeded, unseeded);
+            assert_eq!(seeded.to_string(), unseeded.to_string());
+        }
+
+        // a fixed seed fixes the order the host iterates the pairs in
+        let order = |seed| match evaluate(&filled, Some(seed)) {
+            Object::Hash(pairs) => pairs.into_keys().collect::<Vec<_>>(),
+            other => panic!("expected a hash, got {}", other),
+        };
+        assert_eq!(order(7), order(7));
+        assert_ne!(order(7), order(8));
+    }
+
+    #[test]
+    fn test_hash_limit() {
+        let full = (0..MAX_HASH_LEN as i64)
+            .map(|i| (HashKey::Integer(i), Object::Null))
+            .collect();
+        let mut evaluator = Evaluator::new();
+        evaluator.set("h".to_string(), Object::Hash(full));
+        let mut evaluate = |input| evaluator.evaluate(&generate_program(input));
+
+        // replacing a pair does not grow the hash
+        assert_eq!(
+            evaluate("len(with(h, 0, 1))"),
+            Ok(Object::Integer(MAX_HASH_LEN as i64))
+        );
+        for input in ["with(h, -1, 1)", "h[-1] = 1;"] {
+            assert_eq!(
+                evaluate(input),
+                Err(MonkeyError::HashTooLarge(MAX_HASH_LEN)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = [
+            (r#"{"a": 1}["a"]"#, Object::Integer(1)),
+            (r#"let key = "a"; {"a": 1}[key]"#, Object::Integer(1)),
+            (r#"{"a": 1}["b"]"#, Object::Null),
+            (r#"{}["a"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            (r#"{"a": {"b": [1, 2]}}["a"]["b"][1]"#, Object::Integer(2)),
+            // the key is the value, not the literal that produced it
+            (
+                r#"{1: "int", true: "bool", "1": "string"}["1"]"#,
+                Object::String("string".to_string()),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected);
+        }
+
+        let tests = [
+            (
+                r#"{"a": 1}[fn(x) { x }]"#,
+                MonkeyError::UnusableAsHashKey(ObjectType::Function),
+            ),
+            (
+                r#"{"a": 1}[if (false) { 1 }]"#,
+                MonkeyError::UnusableAsHashKey(ObjectType::Null),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_equality() {
+        let tests = [
+            (r#"{"a": 1} == {"a": 1}"#, true),
+            (r#"{"a": 1, "b": 2} == {"b": 2, "a": 1}"#, true),
+            (r#"{"a": 1} != {"a": 2}"#, true),
+            (r#"{"a": 1} == {"a": 1, "b": 2}"#, false),
+            (r#"{"a": 1, "b": 2} == {"a": 1}"#, false),
+            (r#"{"a": 1} == {"b": 1}"#, false),
+            ("{} == {}", true),
+            (r#"{1: "1"} == {"1": "1"}"#, false),
+            (
+                r#"{"xs": [1, {"y": [2]}], 'c': true} == {'c': true, "xs": [1, {"y": [2]}]}"#,
+                true,
+            ),
+            (
+                r#"{"xs": [1, {"y": [2]}]} == {"xs": [1, {"y": [3]}]}"#,
+                false,
+            ),
+            (r#"[{"a": 1}] == [{"a": 1}]"#, true),
+            (r#"{"a": 1}["a"] == 1"#, true),
+            // no function is compared when the key sets already differ
+            (r#"{"f": fn(x) { x }} == {"g": fn(x) { x }}"#, false),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), Object::Bool(expected), "{}", input);
+        }
+
+        // a function is unequal to any other value, also inside a container
+        let tests = [
+            ("[fn() {}] == [1]", false),
+            ("[fn() {}] != [1]", true),
+            (r#"{"f": fn() {}} == {"f": "f"}"#, false),
+            ("[fn() {}, 1] == [len, 1]", false),
+            ("[len] == [len]", true),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), Object::Bool(expected), "{}", input);
+        }
+
+        // but two functions have no equality, even behind a container or a
+        // value that differs, and the error names the operands compared
+        let unknown = |left, right| MonkeyError::UnknownOperator {
+            left,
+            operator: ast::Infix::Eq,
+            right,
+        };
+        let tests = [
+            (
+                r#"let f = fn(x) { x }; {"f": f} == {"f": f}"#,
+                unknown(ObjectType::Hash, ObjectType::Hash),
+            ),
+            (
+                r#"let f = fn(x) { x }; {"a": 1, "f": f} == {"a": 2, "f": f}"#,
+                unknown(ObjectType::Hash, ObjectType::Hash),
+            ),
+            (
+                "let f = fn(x) { x }; [f] == [f]",
+                unknown(ObjectType::Array, ObjectType::Array),
+            ),
+            (
+                "let f = fn(x) { x }; f == f",
+                unknown(ObjectType::Function, ObjectType::Function),
+            ),
+            (
+                r#"{"a": 1} == [1]"#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Eq,
+                    left: ObjectType::Hash,
+                    right: ObjectType::Array,
+                },
+            ),
+            (
+                "{} != 0",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::NotEq,
+                    left: ObjectType::Hash,
+                    right: ObjectType::Integer,
+                },
+            ),
+            (
+                "{} + {}",
+                MonkeyError::UnknownOperator {
+                    left: ObjectType::Hash,
+                    operator: ast::Infix::Plus,
+                    right: ObjectType::Hash,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_hash_keys() {
+        let a = Object::String("a".to_string());
+        assert_eq!(a.hash_key(), Object::String("a".to_string()).hash_key());
+        assert_ne!(a.hash_key(), Object::String("b".to_string()).hash_key());
+        assert_ne!(
+            Object::Integer(1).hash_key(),
+            Object::String("1".to_string()).hash_key()
+        );
+        assert_ne!(Object::Integer(1).hash_key(), Object::Bool(true).hash_key());
+        assert_eq!(
+            Object::Null.hash_key(),
+            Err(MonkeyError::UnusableAsHashKey(ObjectType::Null))
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let values = [
+            Object::Integer(1),
+            Object::String("a".to_string()),
+            Object::Char('a'),
+            Object::Bool(true),
+            Object::Null,
+            Object::Array(vec![]),
+            Object::Hash(HashMap::default()),
+            evaluate_program("fn(x) { x }"),
+        ];
+        // only integers, strings and chars are ordered, and only against their own type
+        for left in values.iter() {
+            for right in values.iter() {
+                let ordered = matches!(
+                    (left, right),
+                    (Object::Integer(_), Object::Integer(_))
+                        | (Object::String(_), Object::String(_))
+                        | (Object::Char(_), Object::Char(_))
+                );
+                assert_eq!(
+                    left.compare(right).is_some(),
+                    ordered,
+                    "{:?} vs {:?}",
+                    left,
+                    right
+                );
+            }
+        }
+
+        let tests = [
+            (Object::Integer(-2), Object::Integer(1), Ordering::Less),
+            (Object::Integer(3), Object::Integer(3), Ordering::Equal),
+            (
+                Object::String("b".to_string()),
+                Object::String("abc".to_string()),
+                Ordering::Greater,
+            ),
+            (
+                Object::String("ab".to_string()),
+                Object::String("abc".to_string()),
+                Ordering::Less,
+            ),
+            (
+                Object::String("Z".to_string()),
+                Object::String("a".to_string()),
+                Ordering::Less,
+            ),
+            (
+                Object::String("é".to_string()),
+                Object::String("z".to_string()),
+                Ordering::Greater,
+            ),
+        ];
+        for (left, right, expected) in tests {
+            assert_eq!(left.compare(&right), Some(expected));
+            assert_eq!(right.compare(&left), Some(expected.reverse()));
+        }
+    }
+
+    #[test]
+    fn test_char_literals() {
+        let tests = [
+            ("'a'", Object::Char('a')),
+            ("'é'", Object::Char('é')),
+            ("'a' == 'a'", Object::Bool(true)),
+            ("'a' != 'a'", Object::Bool(false)),
+            ("'a' < 'b'", Object::Bool(true)),
+            ("'Z' > 'a'", Object::Bool(false)),
+            ("{'k': 1}['k']", Object::Integer(1)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected);
+        }
+        assert_eq!(format!("{}", evaluate_program("['a', 'b']")), "[a, b]");
+
+        let tests = [
+            (
+                r#"'a' < "a""#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::LT,
+                    left: ObjectType::Char,
+                    right: ObjectType::String,
+                },
+            ),
+            (
+                "'a' + 'b'",
+                MonkeyError::UnknownOperator {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Char,
+                    right: ObjectType::Char,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected);
+        }
+        assert_eq!(
+            Evaluator::new().eval_source("let c = 'ab';"),
+            Err(MonkeyError::MalformedChar("ab".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_string_equality() {
+        let tests = [
+            (r#""a" == "a""#, true),
+            (r#""a" == "b""#, false),
+            (r#""a" != "b""#, true),
+            (r#""a" != "a""#, false),
+            (r#""" == """#, true),
+            (r#""" != "a""#, true),
+            (r#""ab" == "a""#, false),
+            (r#""é" == "é""#, true),
+            (r#"let s = "monkey"; s == "monkey""#, true),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), Object::Bool(expected), "{}", input);
+        }
+
+        let tests = [
+            (
+                r#""1" == 1"#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Eq,
+                    left: ObjectType::String,
+                    right: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"true != "true""#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::NotEq,
+                    left: ObjectType::Bool,
+                    right: ObjectType::String,
+                },
+            ),
+            (
+                r#""a" + 1"#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::String,
+                    right: ObjectType::Integer,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let tests = [
+            (r#""a" < "b""#, true),
+            (r#""b" < "a""#, false),
+            (r#""abc" > "ab""#, true),
+            (r#""a" > "a""#, false),
+            (r#""Z" < "a""#, true),
+            (r#""apple" < "banana""#, true),
+            ("\"\" < \"a\"", true),
+            (r#""é" > "z""#, true),
+            (r#""a" <= "a""#, true),
+            (r#""b" <= "a""#, false),
+            (r#""ab" >= "abc""#, false),
+            ("'b' >= 'a'", true),
+            ("'a' <= 'a'", true),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), Object::Bool(expected));
+        }
+
+        let tests = [
+            (
+                r#"1 < "a""#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::LT,
+                    left: ObjectType::Integer,
+                    right: ObjectType::String,
+                },
+            ),
+            (
+                r#""a" > [1]"#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::GT,
+                    left: ObjectType::String,
+                    right: ObjectType::Array,
+                },
+            ),
+            (
+                "[1] < [2]",
+                MonkeyError::UnknownOperator {
+                    operator: ast::Infix::LT,
+                    left: ObjectType::Array,
+                    right: ObjectType::Array,
+                },
+            ),
+            (
+                "true > false",
+                MonkeyError::UnknownOperator {
+                    operator: ast::Infix::GT,
+                    left: ObjectType::Bool,
+                    right: ObjectType::Bool,
+                },
+            ),
+            (
+                "true <= false",
+                MonkeyError::UnknownOperator {
+                    operator: ast::Infix::LTEq,
+                    left: ObjectType::Bool,
+                    right: ObjectType::Bool,
+                },
+            ),
+            (
+                r#"1 >= "a""#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::GTEq,
+                    left: ObjectType::Integer,
+                    right: ObjectType::String,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected);
+        }
+    }
+
+    /// the parser never produces this, but embedders can build it by hand
+    #[test]
+    fn test_malformed_ast() {
+        let let_integer = Program {
+            statements: vec![ast::Statement::Let {
+                identifier: ast::Expression::Integer(1),
+                value: ast::Expression::Integer(2),
+                doc: None,
+            }],
+        };
+        assert_eq!(
+            Evaluator::new().evaluate(&let_integer),
+            Err(MonkeyError::InvalidIdentifier)
+        );
+    }
+
+    #[test]
+    fn test_eval_as() {
+        let mut eval = Evaluator::new();
+        assert_eq!(eval.eval_as::<i64>("6 * 7"), Ok(42));
+        assert_eq!(eval.eval_as::<bool>("1 < 2"), Ok(true));
+        assert_eq!(eval.eval_as::<String>(r#""a""#), Ok("a".to_string()));
+        assert_eq!(eval.eval_as::<Vec<Object>>("[]"), Ok(vec![]));
+
+        let tests = [
+            (
+                eval.eval_as::<i64>(r#""42""#).map(|_| ()),
+                "42",
+                ObjectType::String,
+                "i64",
+            ),
+            (
+                eval.eval_as::<bool>("0").map(|_| ()),
+                "0",
+                ObjectType::Integer,
+                "bool",
+            ),
+            (
+                eval.eval_as::<String>("[1]").map(|_| ()),
+                "[1]",
+                ObjectType::Array,
+                "String",
+            ),
+        ];
+        for (actual, value, object_type, target) in tests {
+            assert_eq!(
+                actual,
+                Err(MonkeyError::ConversionFailed {
+                    value: value.to_string(),
+                    object_type,
+                    target,
+                })
+            );
+        }
+        assert_eq!(
+            eval.eval_as::<Vec<Object>>("null_value"),
+            Err(MonkeyError::IdentifierNotFound("null_value".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // on the test's own thread, 2 MiB by default
+        let depth = crate::parser::MAX_NESTING_DEPTH - 2;
+        let tests = [
+            (
+                format!("1{}", " + 1".repeat(depth)),
+                Object::Integer(depth as i64 + 1),
+            ),
+            (format!("{}1", "-".repeat(depth)), Object::Integer(1)),
+            (
+                format!("{}1{}", "if (true) { ".repeat(depth), " }".repeat(depth)),
+                Object::Integer(1),
+            ),
+            (
+                format!("{}1{}", "[".repeat(depth), "]".repeat(depth)),
+                (0..depth).fold(Object::Integer(1), |obj, _| Object::Array(vec![obj])),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(&input), expected);
+        }
+        // chains leaning left evaluate in a loop, whatever their length
+        let length = 50_000;
+        let tests = [
+            (
+                format!("1{}", " + 1".repeat(length - 1)),
+                Object::Integer(length as i64),
+            ),
+            (
+                format!("[1, 2]{}[1]", "[:]".repeat(length)),
+                Object::Integer(2),
+            ),
+            (
+                format!(r#"{{}}["missing"]{}"#, "?[0]".repeat(length)),
+                Object::Null,
+            ),
+            (
+                format!(
+                    "let calls = 0; let f = fn() {{ calls += 1; f }}; f{}; calls",
+                    "()".repeat(length)
+                ),
+                Object::Integer(length as i64),
+            ),
+            (
+                format!("let inc = fn(n) {{ n + 1 }}; 0{}", " |> inc".repeat(length)),
+                Object::Integer(length as i64),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(crate::interpret(&input), Ok(expected));
+        }
+        assert_eq!(
+            crate::interpret(&format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000))),
+            Err(MonkeyError::NestingTooDeep(
+                crate::parser::MAX_NESTING_DEPTH
+            ))
+        );
+    }
+
+    #[test]
+    fn test_truthiness_levels() {
+        // condition, permissive result, type reported by warn/strict
+        let tests = [
+            ("true", Object::Integer(1), None),
+            ("false", Object::Integer(2), None),
+            ("1 < 2", Object::Integer(1), None),
+            ("0", Object::Integer(1), Some(ObjectType::Integer)),
+            ("5", Object::Integer(1), Some(ObjectType::Integer)),
+            (r#""""#, Object::Integer(1), Some(ObjectType::String)),
+            (r#""a""#, Object::Integer(1), Some(ObjectType::String)),
+            (
+                "if (false) { 1 }",
+                Object::Integer(2),
+                Some(ObjectType::Null),
+            ),
+            ("fn() { 1 }", Object::Integer(1), Some(ObjectType::Function)),
+        ];
+        for (condition, expected, non_bool) in tests {
+            let program = generate_program(&format!("if ({}) {{ 1 }} else {{ 2 }}", condition));
+
+            let mut eval = Evaluator::new();
+            assert_eq!(eval.evaluate(&program), Ok(expected.clone()));
+            assert_eq!(eval.take_diagnostics(), vec![]);
+
+            let mut eval = Evaluator::new();
+            eval.set_truthiness(Truthiness::Warn);
+            assert_eq!(eval.evaluate(&program), Ok(expected.clone()));
+            let diagnostics = eval
+                .take_diagnostics()
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<_>>();
+            match &non_bool {
+                Some(ty) => assert_eq!(
+                    diagnostics,
+                    vec![format!("condition is {}, not BOOLEAN", ty)]
+                ),
+                None => assert!(diagnostics.is_empty()),
+            }
+
+            let mut eval = Evaluator::new();
+            eval.set_truthiness(Truthiness::Strict);
+            match non_bool {
+                Some(ty) => assert_eq!(
+                    eval.evaluate(&program),
+                    Err(MonkeyError::ConditionTypeMismatch(ty))
+                ),
+                None => assert_eq!(eval.evaluate(&program), Ok(expected)),
+            }
+        }
+
+        // settings and diagnostics carry through function calls
+        let program = generate_program("let f = fn(x) { if (x) { 1 } }; f(5)");
+        let mut eval = Evaluator::new();
+        eval.set_truthiness(Truthiness::Warn);
+        assert_eq!(eval.evaluate(&program), Ok(Object::Integer(1)));
+        assert_eq!(eval.take_diagnostics().len(), 1);
+        eval.set_truthiness(Truthiness::Strict);
+        assert_eq!(
+            eval.evaluate(&program),
+            Err(MonkeyError::ConditionTypeMismatch(ObjectType::Integer))
+        );
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = [
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            ("let a = 5; let b = a; b;", Object::Integer(5)),
+            (
+                "let a = 5; let b = a; let c = a + b + 5; c;",
+                Object::Integer(15),
+            ),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(actual, expected)
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = [
+            ("return 10;", Object::Integer(10)),
+            ("return 10; 9;", Object::Integer(10)),
+            ("return 2 * 5; 9;", Object::Integer(10)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            (
+                "if (10 > 1) {
+                if (10 > 1) {
+                    return 10;
+                }
+                return 1;
+            }
+            ",
+                Object::Integer(10),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(actual, expected)
+        }
+    }
+
+    #[test]
+    fn test_return_through_expressions() {
+        let tests = [
+            (
+                "let f = fn() { let x = if (true) { return 3; }; 4 }; f()",
+                Object::Integer(3),
+            ),
+            (
+                "let f = fn() { 1 + if (true) { return 5; } else { 0 } }; f()",
+                Object::Integer(5),
+            ),
+            ("let f = fn() { [1, loop { return 2; }, 3] }; f()", Object::Integer(2)),
+            (
+                r#"let f = fn() { let y = match (1) { 1 => { return "early"; } }; "late" }; f()"#,
+                Object::String("early".to_string()),
+            ),
+            (
+                "let f = fn(n) { let x = loop { if (n > 2) { break n; }; n += 1; }; x * 10 }; f(0)",
+                Object::Integer(30),
+            ),
+            (
+                "let f = fn(a) { if (a > 0) { if (a > 5) { return 2; }; return 1; }; 0 }; [f(9), f(3), f(-1)]",
+                Object::Array(vec![
+                    Object::Integer(2),
+                    Object::Integer(1),
+                    Object::Integer(0),
+                ]),
+            ),
+            (
+                "let f = fn() { for (i in [1, 2]) { let x = if (i > 0) { return i; }; } }; f()",
+                Object::Integer(1),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        // the return leaves the program before `x` is bound
+        let mut evaluator = Evaluator::new();
+        assert_eq!(
+            evaluator.eval_source("let x = if (true) { return 3; }; 4"),
+            Ok(Object::Integer(3))
+        );
+        assert_eq!(evaluator.get("x"), None);
+
+        let mut evaluator = Evaluator::new();
+        evaluator
+            .eval_source("let f = fn() { if (true) { return 3; } }; let x = f();")
+            .unwrap();
+        assert_eq!(evaluator.get("x"), Some(Object::Integer(3)));
+
+        assert_eq!(
+            evaluate_error_program(
+                "let x = loop { if (true) { break; } }; let y = if (true) { break; }; 1"
+            ),
+            MonkeyError::BreakOutsideLoop
+        );
+    }
+
+    #[test]
+    fn test_top_level_return() {
+        let tests = [
+            ("if (true) { return 1; } 2;", Object::Integer(1)),
+            (
+                "if (true) { if (true) { return 1; } 2; } 3;",
+                Object::Integer(1),
+            ),
+            (
+                "let x = 0; loop { x += 1; if (x == 3) { return x * 10; } }; 99",
+                Object::Integer(30),
+            ),
+            (
+                "for (i in [1, 2, 3]) { if (i == 2) { return i; } }; 0",
+                Object::Integer(2),
+            ),
+            ("match (1) { 1 => { return 5; } }; 0", Object::Integer(5)),
+            // inside a function body it only leaves the function
+            ("let f = fn() { return 1; 2 }; f(); 3", Object::Integer(3)),
+            (
+                "let f = fn() { loop { return 4; } }; f() + 1",
+                Object::Integer(5),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let mut evaluator = Evaluator::new();
+        assert_eq!(
+            evaluator.eval_source("let x = 1; if (true) { return x; }; x = 2;"),
+            Ok(Object::Integer(1))
+        );
+        assert_eq!(evaluator.get("x"), Some(Object::Integer(1)));
+    }
+
+    #[test]
+    fn test_for_statements() {
+        let tests = [
+            (
+                "let total = 0; for (x in [1, 2, 3, 4]) { total += x; }; total",
+                Object::Integer(10),
+            ),
+            (
+                "let total = 0; for (i in 0..5) { total += i * i; }; total",
+                Object::Integer(30),
+            ),
+            (
+                "let n = 0; for (x in []) { n += 1; }; n",
+                Object::Integer(0),
+            ),
+            (
+                r#"let chars = []; for (c in "abc") { chars = [c] + chars; }; chars"#,
+                Object::Array(vec![
+                    Object::Char('c'),
+                    Object::Char('b'),
+                    Object::Char('a'),
+                ]),
+            ),
+            (
+                r#"let h = {"b": 2, "a": 1}; let keys = []; let sum = 0;
+                for (pair in h) { keys = keys + [pair[0]]; sum += pair[1]; }; [keys, sum]"#,
+                Object::Array(vec![
+                    Object::Array(vec![
+                        Object::String("a".to_string()),
+                        Object::String("b".to_string()),
+                    ]),
+                    Object::Integer(3),
+                ]),
+            ),
+            // each iteration gets a fresh scope that closures can keep
+            (
+                "let fs = []; for (i in 0..3) { fs = fs + [fn() { i }]; }; fs[0]() + fs[2]()",
+                Object::Integer(2),
+            ),
+            (
+                "let seen = 0; for (x in 1..10) { seen += 1; if (x == 3) { break; } }; seen",
+                Object::Integer(3),
+            ),
+            (
+                "let find = fn(xs, y) { for (x in xs) { if (x == y) { return true; } }; false };
+                [find([1, 2], 2), find([1, 2], 3)]",
+                Object::Array(vec![Object::Bool(true), Object::Bool(false)]),
+            ),
+            ("for (x in [1]) { x }", Object::Null),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        assert_eq!(
+            evaluate_program("let x = 0; for (x in [5]) { x }; x"),
+            Object::Integer(0)
+        );
+        assert_eq!(
+            evaluate_error_program("for (x in 5) { x }"),
+            MonkeyError::NotIterable(ObjectType::Integer)
+        );
+        assert_eq!(
+            evaluate_error_program("for (x in [1]) { x }; x"),
+            MonkeyError::IdentifierNotFound("x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_expressions() {
+        let tests = [
+            (
+                r#"let name = fn(n) { match (n) { 1 => "one", 2 => "two", _ => "many" } }; [name(1), name(2), name(9)]"#,
+                Object::Array(vec![
+                    Object::String("one".to_string()),
+                    Object::String("two".to_string()),
+                    Object::String("many".to_string()),
+                ]),
+            ),
+            (r#"match ("b") { "a" => 1, "b" => 2 }"#, Object::Integer(2)),
+            (
+                "match (1 < 2) { false => 0, true => 1 }",
+                Object::Integer(1),
+            ),
+            (
+                "match ([1, 2]) { [1] => 1, [1, 2] => 2 }",
+                Object::Integer(2),
+            ),
+            (
+                "let x = 3; match (x) { 1 + 2 => x * 10, _ => 0 }",
+                Object::Integer(30),
+            ),
+            ("match (5) { 1 => 1 }", Object::Null),
+            ("match (5) { }", Object::Null),
+            // patterns of other types just do not match
+            (
+                r#"match (1) { "1" => 1, true => 2, _ => 3 }"#,
+                Object::Integer(3),
+            ),
+            (
+                "match (1) { 1 => { let y = 2; y * 3 } }",
+                Object::Integer(6),
+            ),
+            // only the matching arm runs, and patterns stop at the first match
+            (
+                "match (2) { 1 => missing, 2 => 20, missing => 30 }",
+                Object::Integer(20),
+            ),
+            ("match (1) { 1 => 1, _ => 1 / 0 }", Object::Integer(1)),
+            (
+                "let f = fn(x) { match (x) { 0 => { return -1; } }; x }; [f(0), f(4)]",
+                Object::Array(vec![Object::Integer(-1), Object::Integer(4)]),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        assert_eq!(
+            evaluate_error_program("match (1) { 2 => 2, missing => 3 }"),
+            MonkeyError::IdentifierNotFound("missing".to_string())
+        );
+        assert_eq!(
+            evaluate_error_program("let f = fn() { 1 }; match (f) { f => 1 }"),
+            MonkeyError::UnknownOperator {
+                operator: ast::Infix::Eq,
+                left: ObjectType::Function,
+                right: ObjectType::Function,
+            }
+        );
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        let area = r#"let area = fn(shape) {
+            match (shape) {
+                [w, h] => w * h,
+                {"kind": "circle", "r": r} => 3 * r * r,
+                {"kind": "square", "side": s} => s * s,
+                _ => 0
+            }
+        };"#;
+        let tests = [
+            ("area([2, 3])", "6"),
+            ("area([2, 3, 4])", "0"),
+            (r#"area({"kind": "circle", "r": 2})"#, "12"),
+            (
+                r#"area({"kind": "square", "side": 5, "color": "red"})"#,
+                "25",
+            ),
+            (r#"area({"kind": "square"})"#, "0"),
+            (r#"area({"kind": "hexagon", "r": 2})"#, "0"),
+            ("area(7)", "0"),
+        ];
+        for (input, expected) in tests {
+            let input = format!("{} {}", area, input);
+            assert_eq!(evaluate_program(&input).to_string(), expected, "{}", input);
+        }
+
+        let tests = [
+            ("match ([1, 2, 3]) { [x, ...rest] => [x, rest] }", "[1, [2, 3]]"),
+            ("match ([1]) { [x, ...rest] => rest }", "[]"),
+            ("match ([]) { [x, ...rest] => 1, [...all] => all }", "[]"),
+            ("match ([1, 2]) { [_, y] => y }", "2"),
+            ("match ([1, 2]) { [..._] => 0 }", "0"),
+            ("match ([1, 2]) { [1, y] => y, _ => 0 }", "2"),
+            ("match ([3, 2]) { [1, y] => y, _ => 0 }", "0"),
+            (r#"match ("ab") { [x, y] => 1, _ => 0 }"#, "0"),
+            (
+                r#"match ({"a": [1, {"b": [2, 3]}]}) { {"a": [x, {"b": [_, y]}]} => x + y }"#,
+                "4",
+            ),
+            ("match ([[1, 2], [3]]) { [[a, b], [c]] => a + b + c }", "6"),
+            ("let one = 1; match ([1, 2]) { [one + 0, two] => two }", "2"),
+            ("match ({1: 'x', true: 'y'}) { {true: v} => v }", "y"),
+            ("match ({}) { {} => 1 }", "1"),
+            ("match ([1]) { [] => 0, _ => 1 }", "1"),
+            // bindings are only seen by the arm's body
+            ("let x = 0; let y = match ([5]) { [x] => x * 2 }; [x, y]", "[0, 10]"),
+            (
+                "let x = 0; match ([5]) { [x] => { let z = x; z } }; x",
+                "0",
+            ),
+            (
+                "let f = fn(xs) { match (xs) { [] => 0, [x, ...rest] => { return x + f(rest); } } }; f([1, 2, 3])",
+                "6",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input).to_string(), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "match ([1, 2]) { [x] => 1, _ => 0 }; x",
+                MonkeyError::IdentifierNotFound("x".to_string()),
+            ),
+            (
+                "match ([1]) { [x] => 1 }; x",
+                MonkeyError::IdentifierNotFound("x".to_string()),
+            ),
+            (
+                "match ({}) { {[1]: x} => 1 }",
+                MonkeyError::UnusableAsHashKey(ObjectType::Array),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_block_scoping() {
+        let tests = [
+            ("let x = 1; if (true) { let x = 2; }; x", Object::Integer(1)),
+            (
+                "let x = 1; if (true) { let x = 2; x } else { 0 }",
+                Object::Integer(2),
+            ),
+            (
+                "let x = 1; if (false) { 0 } else { let x = 3; }; x",
+                Object::Integer(1),
+            ),
+            ("let x = 1; if (true) { x = 2; }; x", Object::Integer(2)),
+            (
+                "let x = 1; match (1) { 1 => { let x = 5; } }; x",
+                Object::Integer(1),
+            ),
+            (
+                "let x = 1; for (i in [1]) { let x = i + 10; }; x",
+                Object::Integer(1),
+            ),
+            (
+                "let n = 0; loop { let step = 2; n += step; if (n > 5) { break; } }; n",
+                Object::Integer(6),
+            ),
+            // closures keep the block scope they were made in alive
+            (
+                "let get = if (true) { let hidden = 5; fn() { hidden } }; get()",
+                Object::Integer(5),
+            ),
+            (
+                "let f = fn() { let y = 1; if (true) { y = 2; }; y }; f()",
+                Object::Integer(2),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            ("if (true) { let tmp = 1; }; tmp", "tmp"),
+            ("loop { let tmp = 1; break; }; tmp", "tmp"),
+            ("for (x in [1]) { let tmp = x; }; tmp", "tmp"),
+            ("let f = fn() { if (true) { let y = 1; }; y }; f()", "y"),
+            ("if (true) { let a = 1; }; if (true) { a }", "a"),
+        ];
+        for (input, name) in tests {
+            assert_eq!(
+                evaluate_error_program(input),
+                MonkeyError::IdentifierNotFound(name.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_loop_expressions() {
+        let tests = [
+            ("loop { break; }", Object::Null),
+            ("loop { break 42; }", Object::Integer(42)),
+            (
+                "let x = loop { if (true) { break 1 + 1; } }; x * 10",
+                Object::Integer(20),
+            ),
+            ("loop { loop { break 1; }; break 2; }", Object::Integer(2)),
+            (
+                "let f = fn() { loop { return 7; } }; f()",
+                Object::Integer(7),
+            ),
+            (
+                // search for the first square above 50
+                "let i = 0;
+                let found = loop {
+                    if (50 < i * i) { break i; }
+                    i = i + 1;
+                };
+                found",
+                Object::Integer(8),
+            ),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_program(input);
+            assert_eq!(actual, expected)
+        }
+
+        let tests = ["break;", "if (true) { break 1; }", "fn() { break; }()"];
+        for input in tests {
+            let actual = evaluate_error_program(input);
+            assert_eq!(actual, MonkeyError::BreakOutsideLoop)
+        }
+    }
+
+    #[test]
+    fn test_continue_statements() {
+        let tests = [
+            // a while-style loop that stops at 5
+            (
+                "let i = 0; loop { i += 1; if (i == 5) { break i; } }",
+                Object::Integer(5),
+            ),
+            // odd numbers only
+            (
+                "let total = 0; for (x in 1..10) { if (x % 2 == 0) { continue; } total += x; }; total",
+                Object::Integer(25),
+            ),
+            (
+                "let i = 0; let odd = 0; loop { i += 1; if (i > 6) { break odd; } if (i % 2 == 0) { continue } odd += 1; }",
+                Object::Integer(3),
+            ),
+            // break and continue only affect the innermost loop
+            (
+                "let pairs = 0; for (i in 0..3) { for (j in 0..3) { if (j > i) { break; } pairs += 1; }; }; pairs",
+                Object::Integer(6),
+            ),
+            (
+                "let n = 0; for (i in 0..3) { for (j in 0..3) { if (j == 1) { continue; } n += 1; }; n += 10; }; n",
+                Object::Integer(36),
+            ),
+            (
+                "let outer = 0; loop { outer += 1; for (x in [1, 2]) { break; }; if (outer == 2) { break outer; } }",
+                Object::Integer(2),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            "continue;",
+            "if (true) { continue; }",
+            "let f = fn() { continue; }; for (x in [1]) { f() }",
+        ];
+        for input in tests {
+            assert_eq!(
+                evaluate_error_program(input),
+                MonkeyError::ContinueOutsideLoop,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = [
+            (
+                "5 + true;",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Bool,
+                },
+            ),
+            (
+                "5 + true; 5",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Bool,
+                },
+            ),
+            (
+                "[1] * 2",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Asterisk,
+                    left: ObjectType::Array,
+                    right: ObjectType::Integer,
+                },
+            ),
+            (
+                "[1] - 1",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Minus,
+                    left: ObjectType::Array,
+                    right: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"1 + [1]"#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Integer,
+                    right: ObjectType::Array,
+                },
+            ),
+            (
+                r#"[1] + {"a": 1}"#,
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Array,
+                    right: ObjectType::Hash,
+                },
+            ),
+            (
+                "[1] + 'a'",
+                MonkeyError::TypeMismatch {
+                    operator: ast::Infix::Plus,
+                    left: ObjectType::Array,
+                    right: ObjectType::Char,
+                },
+            ),
+            (
+                r#""a" + "b""#,
+                MonkeyError::UnknownOperator {
+                    left: ObjectType::String,
+                    operator: ast::Infix::Plus,
+                    right: ObjectType::String,
+                },
+            ),
+            (
+                "'a' - 'b'",
+                MonkeyError::UnknownOperator {
+                    left: ObjectType::Char,
+                    operator: ast::Infix::Minus,
+                    right: ObjectType::Char,
+                },
+            ),
+            (
+                "foobar",
+                MonkeyError::IdentifierNotFound("foobar".to_string()),
+            ),
+            (
+                "let add = fn(x, y) { x + y }; add(1, 2, 3)",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 3,
+                },
+            ),
+            ("10 / 0", MonkeyError::DivisionByZero),
+            (
+                "let zero = 0; 1 + 10 / zero * 2",
+                MonkeyError::DivisionByZero,
+            ),
+            (
+                "let div = fn(x, y) { x / y }; div(1, 1) + div(4, 2 - 2)",
+                MonkeyError::DivisionByZero,
+            ),
+            (
+                "let f = fn() { if (true) { return [1, 2 / 0]; } }; f()",
+                MonkeyError::DivisionByZero,
+            ),
+            ("5 % 0", MonkeyError::DivisionByZero),
+            ("2 ** -1", MonkeyError::NegativeExponent("-1".to_string())),
+            ("2 ** 63", MonkeyError::IntegerOverflow),
+            ("2 ** 9999999999", MonkeyError::IntegerOverflow),
+            (
+                "let rem = fn(x, y) { x % y }; 1 + rem(3, 2 - 2)",
+                MonkeyError::DivisionByZero,
+            ),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_error_program(input);
+            assert_eq!(actual, expected)
+        }
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        assert_eq!(evaluate_program("1_000 + 1"), Object::Integer(1001));
+        assert_eq!(
+            Evaluator::new().eval_source("let x = 1_;"),
+            Err(MonkeyError::MalformedNumber("1_".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        let tests = [
+            ("9223372036854775807 + 1", MonkeyError::IntegerOverflow),
+            ("-9223372036854775807 - 2", MonkeyError::IntegerOverflow),
+            ("4611686018427387904 * 2", MonkeyError::IntegerOverflow),
+            ("9223372036854775808", MonkeyError::InvalidInteger),
+        ];
+        for (input, expected) in tests {
+            let actual = evaluate_error_program(input);
+            assert_eq!(actual, expected)
+        }
+    }
+
+    #[test]
+    fn test_eval_pure() {
+        let mut eval = Evaluator::new();
+        eval.eval_source(
+            "let prices = [3, 4, 5]; let rate = 2; let total = fn(xs) { let sum = 0; for (x in xs) { sum += x; } sum };",
+        )
+        .unwrap();
+        let before = eval.env.borrow().clone();
+
+        let tests = [
+            ("total(prices) * rate", Object::Integer(24)),
+            ("map(prices, fn(p) { p * rate })[2]", Object::Integer(10)),
+            (
+                "[rate, prices[0]] + [1]",
+                Object::Array(vec![
+                    Object::Integer(2),
+                    Object::Integer(3),
+                    Object::Integer(1),
+                ]),
+            ),
+            (
+                "if (rate > 1) { let doubled = rate * 2; doubled }",
+                Object::Integer(4),
+            ),
+            ("fn(n) { n += 1; n }(rate)", Object::Integer(3)),
+            ("push(prices, 6)[3]", Object::Integer(6)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval.eval_pure(input), Ok(expected), "{}", input);
+        }
+
+        let tests = [
+            ("let rate = 3;", "rate"),
+            ("let fresh = 1;", "fresh"),
+            ("rate = 3;", "rate"),
+            ("prices[0] = 0;", "prices"),
+            ("rate += 1;", "rate"),
+            ("let bump = fn() { rate = 10; }; bump()", "bump"),
+            ("fn() { rate = 10; }()", "rate"),
+            ("for (p in prices) { rate += p; }", "rate"),
+        ];
+        for (input, name) in tests {
+            assert_eq!(
+                eval.eval_pure(input),
+                Err(MonkeyError::EnvironmentFrozen(name.to_string())),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(*eval.env.borrow(), before);
+
+        // outside `eval_pure` the environment is mutable again
+        assert_eq!(
+            eval.eval_source("rate = 3; total(prices) * rate"),
+            Ok(Object::Integer(36))
+        );
+
+        // the scopes an environment is enclosed in are frozen too
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .set("limit".to_string(), Object::Integer(10));
+        let mut eval = Evaluator::from_env(Environment::new_enclosed(Rc::clone(&globals)));
+        eval.set("local".to_string(), Object::Integer(1));
+        assert_eq!(eval.eval_pure("limit + local"), Ok(Object::Integer(11)));
+        assert_eq!(
+            eval.eval_pure("limit = 0;"),
+            Err(MonkeyError::EnvironmentFrozen("limit".to_string()))
+        );
+        assert_eq!(globals.borrow_mut().get("limit"), Some(Object::Integer(10)));
+        assert!(!globals.borrow().is_frozen());
+        assert_eq!(eval.eval_source("limit = 0; limit"), Ok(Object::Integer(0)));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_mode() {
+        // the recursive factorial needs more than a 2 MiB test thread in debug builds
+        let tests = [
+            (
+                "let fact = fn(f, n) { if (n == 0) { 1 } else { n * f(f, n - 1) } }; fact(fact, 30)",
+                "265252859812191058636308480000000",
+            ),
+            ("9223372036854775807 + 1", "9223372036854775808"),
+            ("-9223372036854775807 - 2", "-9223372036854775809"),
+            ("99999999999999999999 - 99999999999999999998", "1"),
+            ("(9223372036854775807 + 1) - 1 == 9223372036854775807", "true"),
+            ("9223372036854775807 + 1 > 9223372036854775807", "true"),
+            ("-99999999999999999999 % 7", "-1"),
+            ("(-9223372036854775807 - 1) % -1", "0"),
+            ("2 ** 64", "18446744073709551616"),
+            ("(2 ** 64) ** 2 / 2 ** 64", "18446744073709551616"),
+            ("len(str(3 ** 100000))", "47713"),
+            ("1 ** 99999999999999999999", "1"),
+            ("0 ** 99999999999999999999", "0"),
+            ("(-1) ** 99999999999999999999", "-1"),
+            ("(-1) ** (99999999999999999999 + 1)", "1"),
+            (r#"int("-99999999999999999999")"#, "-99999999999999999999"),
+            ("to_hex(2 ** 64 + 255)", "100000000000000ff"),
+            ("format_int(-(2 ** 64), 23)", "-0018446744073709551616"),
+            // promoted integers are hash keys like small ones
+            ("{99999999999999999999: 1}[99999999999999999999]", "1"),
+            (r#"let h = {}; h[2 ** 64] = "big"; h[2 ** 65 / 2]"#, "big"),
+            ("2 ** 64 in {18446744073709551616: true}", "true"),
+            ("{2 ** 64: 1, 2: 2}", "{2: 2, 18446744073709551616: 1}"),
+            (
+                r#"{(9223372036854775807 + 1) - 1: "small"}[9223372036854775807]"#,
+                "small",
+            ),
+        ];
+        for (input, expected) in tests {
+            let program = generate_program(input);
+            let mut eval = Evaluator::new();
+            eval.set_bigint(true);
+            let actual = eval.evaluate(&program).unwrap();
+            assert_eq!(format!("{}", actual), expected)
+        }
+
+        let program = generate_program("(9223372036854775807 + 1) - 1");
+        let mut eval = Evaluator::new();
+        eval.set_bigint(true);
+        assert_eq!(
+            eval.evaluate(&program).unwrap(),
+            Object::Integer(9223372036854775807)
+        );
+
+        assert_eq!(
+            eval.eval_source("99999999999999999999 % 0"),
+            Err(MonkeyError::DivisionByZero)
+        );
+        // powers past `MAX_POW_BITS` are refused before they are computed
+        for input in [
+            "3 ** 100000000",
+            "3 ** 4294967295",
+            "2 ** 99999999999999999999",
+            "99999999999999999999 ** 100000",
+        ] {
+            assert_eq!(
+                eval.eval_source(input),
+                Err(MonkeyError::IntegerOverflow),
+                "{}",
+                input
+            );
+        }
+
+        let big = eval.eval_source("9223372036854775807 + 1").unwrap();
+        assert_eq!(big.compare(&Object::Integer(1)), Some(Ordering::Greater));
+        assert_eq!(Object::Integer(1).compare(&big), Some(Ordering::Less));
+
+        // a host-built BigInteger in the i64 range keys like the small form
+        assert_eq!(
+            Object::BigInteger(5.into()).hash_key(),
+            Object::Integer(5).hash_key()
+        );
+        assert_eq!(
+            big.hash_key(),
+            Ok(HashKey::BigInteger(num_bigint::BigInt::from(1u64 << 63)))
+        );
+    }
+
+    #[test]
+    fn test_function_object() {
+        let tests = [("fn(x) { x + 2;};")];
+        for input in tests {
+            let actual = evaluate_program(input);
+            if let Object::Function { parameters, .. } = actual {
+                assert_eq!(parameters.len(), 1);
+                assert_eq!(format!("{}", parameters[0]), "x".to_string());
+                // assert_eq!(format!("{}", body), "(x + 2)".to_string())
+            }
+        }
+    }
+
+    #[test]
+    fn test_self_referential_function() {
+        // `f`'s scope holds `f`, so neither may walk into it
+        let mut eval = Evaluator::new();
+        let f = eval.eval_source("let f = fn(x) { f(x) }; f").unwrap();
+        let debug = format!("{:?}", f);
+        assert!(debug.starts_with("Function {"), "{}", debug);
+        assert!(debug.ends_with("env: <scope> }"), "{}", debug);
+        assert_eq!(f, f.clone());
+        assert_eq!(eval.eval_source("f").unwrap(), f);
+        assert_eq!(
+            format!("{:?}", Object::Array(vec![f.clone()])),
+            format!("Array([{}])", debug)
+        );
+
+        // the same literal closing over another scope is another function
+        let g = eval
+            .eval_source("let g = fn() { fn(x) { f(x) } }; g()")
+            .unwrap();
+        assert_ne!(g, eval.eval_source("g()").unwrap());
+        assert_ne!(f, Object::Null);
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let input = "
+        /// doubles x
+        let double = fn(x) { x * 2 };
+        let triple = fn(x) { x * 3 };
+        ";
+        let program = generate_program(input);
+        let mut eval = Evaluator::new();
+        eval.evaluate(&program).unwrap();
+        assert_eq!(eval.get_doc("double"), Some("doubles x".to_string()));
+        assert_eq!(eval.get_doc("triple"), None);
+
+        let program = generate_program("let double = 2;");
+        eval.evaluate(&program).unwrap();
+        assert_eq!(eval.get_doc("double"), None);
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let tests = [
+            (
+                "let countdown = fn(n) { if (n == 0) { return 0; }; return countdown(n - 1); }; countdown(100000)",
+                Object::Integer(0),
+            ),
+            (
+                "let countdown = fn(n) { if (n == 0) { 0 } else { countdown(n - 1) } }; countdown(100000)",
+                Object::Integer(0),
+            ),
+            (
+                "let sum = fn(n, acc) { if (n == 0) { acc } else { sum(n - 1, acc + n) } }; sum(100000, 0)",
+                Object::Integer(5000050000),
+            ),
+            (
+                "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } };
+                 let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } };
+                 even(100001)",
+                Object::Bool(false),
+            ),
+            (
+                "let find = fn(n) { for (i in [1, 2]) { if (n > 0) { return find(n - 1); } }; n }; find(100000)",
+                Object::Integer(0),
+            ),
+            // the tail call gets the arguments and scope it was made with
+            (
+                "let f = fn(n) { let m = n * 2; if (n > 0) { let m = 0; f(n - 1) } else { m } }; f(3)",
+                Object::Integer(0),
+            ),
+            (
+                "let add = fn(a) { fn(b) { a + b } }; let g = fn(x) { add(x)(1) }; g(2)",
+                Object::Integer(3),
+            ),
+            ("let f = fn() { return fn() { 7 }(); }; f()", Object::Integer(7)),
+            ("let f = fn(n) { if (n > 0) { f(n - 1) } }; f(3)", Object::Null),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "let f = fn(n) { if (n == 0) { g() } else { f(n - 1) } }; f(5)",
+                MonkeyError::IdentifierNotFound("g".to_string()),
+            ),
+            (
+                "let f = fn() { return 1(); }; f()",
+                MonkeyError::NotCallable(ObjectType::Integer),
+            ),
+            (
+                "let f = fn(n) { if (n == 0) { fn(a) { a }() } else { f(n - 1) } }; f(1)",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_len() {
+        let tests = [
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("hello world")"#, Object::Integer(11)),
+            (r#"len("héllo")"#, Object::Integer(5)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("len([])", Object::Integer(0)),
+            (r#"len({"a": 1, "b": 2})"#, Object::Integer(2)),
+            ("let a = [1, 2]; len(a + a)", Object::Integer(4)),
+            ("[1, 2, 3] |> len", Object::Integer(3)),
+            ("let len = fn(x) { 42 }; len([])", Object::Integer(42)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "len(1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "len",
+                    position: 1,
+                    expected: &[ObjectType::String, ObjectType::Array, ObjectType::Hash],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"len("one", "two")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+            (
+                "len()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_first_last_rest() {
+        let tests = [
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            (
+                "rest([1, 2, 3])",
+                Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+            ),
+            ("rest([1])", Object::Array(vec![])),
+            ("rest([])", Object::Null),
+            (
+                "let a = [1, 2]; rest(a); a",
+                Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            ),
+            (
+                "let map = fn(arr, f) {
+                     if (len(arr) == 0) { [] } else { [f(first(arr))] + map(rest(arr), f) }
+                 };
+                 map([1, 2, 3], fn(x) { x * x })",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(4),
+                    Object::Integer(9),
+                ]),
+            ),
+            (
+                "let sum = fn(arr) { if (len(arr) == 0) { 0 } else { first(arr) + sum(rest(arr)) } };
+                 sum([1, 2, 3, 4]) + last([10])",
+                Object::Integer(20),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"first("abc")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "first",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "last(1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "last",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                "rest({})",
+                MonkeyError::UnsupportedArgument {
+                    function: "rest",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::Hash,
+                },
+            ),
+            (
+                "rest([1], [2])",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+            (
+                "first()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_push() {
+        let tests = [
+            ("push([], 1)", Object::Array(vec![Object::Integer(1)])),
+            (
+                "let a = [1]; let b = push(a, 2); [len(a), len(b)]",
+                Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            ),
+            (
+                "let a = [1]; push(a, [2]); a",
+                Object::Array(vec![Object::Integer(1)]),
+            ),
+            (
+                "push([1], [2])",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Array(vec![Object::Integer(2)]),
+                ]),
+            ),
+            (
+                "let reverse = fn(arr) { if (len(arr) == 0) { [] } else { push(reverse(rest(arr)), first(arr)) } };
+                 reverse([1, 2, 3])",
+                Object::Array(vec![
+                    Object::Integer(3),
+                    Object::Integer(2),
+                    Object::Integer(1),
+                ]),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"push("ab", 'c')"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "push",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "push([1])",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_puts() {
+        let tests = [
+            (r#"puts("hello", "world")"#, "hello\nworld\n"),
+            (
+                r#"puts(1, true, 'c', [1, "two"], {"k": 3}, null_value)"#,
+                "1\ntrue\nc\n[1, two]\n{k: 3}\nnull\n",
+            ),
+            ("puts()", ""),
+            (
+                "let log = fn(x) { puts(x); x * 2 }; puts(log(log(1)))",
+                "1\n2\n4\n",
+            ),
+        ];
+        for (input, expected) in tests {
+            let output = Rc::new(RefCell::new(vec![]));
+            let mut evaluator = Evaluator::new();
+            evaluator.set_output(Rc::clone(&output));
+            evaluator.set("null_value".to_string(), Object::Null);
+            assert_eq!(evaluator.eval_source(input), Ok(Object::Null), "{}", input);
+            assert_eq!(
+                String::from_utf8(output.borrow().clone()).unwrap(),
+                expected,
+                "{}",
+                input
+            );
+        }
+
+        struct Closed;
+        impl std::io::Write for Closed {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Rc::new(RefCell::new(Closed)));
+        assert_eq!(
+            evaluator.eval_source(r#"puts("lost")"#),
+            Err(MonkeyError::OutputFailed("broken pipe".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_builtin_dbg() {
+        let tests = [
+            ("dbg(42)", Object::Integer(42), "[dbg] 42\n"),
+            (
+                r#"dbg("hi")"#,
+                Object::String("hi".to_string()),
+                "[dbg] \"hi\"\n",
+            ),
+            ("dbg('c')", Object::Char('c'), "[dbg] 'c'\n"),
+            (
+                r#"dbg([1, "a"])"#,
+                Object::Array(vec![Object::Integer(1), Object::String("a".to_string())]),
+                "[dbg] [1, \"a\"]\n",
+            ),
+            ("dbg(if (false) { 1 })", Object::Null, "[dbg] null\n"),
+            (
+                "let f = fn(x) { x + 1 }; let y = dbg(f(1)) * 2; y",
+                Object::Integer(4),
+                "[dbg] 2\n",
+            ),
+            (
+                "let f = fn(x) { dbg(x) }; f(1); f(2)",
+                Object::Integer(2),
+                "[dbg] 1\n[dbg] 2\n",
+            ),
+        ];
+        for (input, expected, traced) in tests {
+            let output = Rc::new(RefCell::new(vec![]));
+            let debug_output = Rc::new(RefCell::new(vec![]));
+            let mut evaluator = Evaluator::new();
+            evaluator.set_output(Rc::clone(&output));
+            evaluator.set_debug_output(Rc::clone(&debug_output));
+            assert_eq!(evaluator.eval_source(input), Ok(expected), "{}", input);
+            assert_eq!(
+                String::from_utf8(debug_output.borrow().clone()).unwrap(),
+                traced,
+                "{}",
+                input
+            );
+            assert!(output.borrow().is_empty(), "{}", input);
+        }
+        assert_eq!(
+            evaluate_error_program("dbg(1, 2)"),
+            MonkeyError::IncorrectNumberOfArguments {
+                expected: 1,
+                actual: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_builtin_argument_errors() {
+        // every builtin checks its arguments the same way, naming the
+        // position of a wrong one and the types it takes
+        let tests = [
+            ("len()", "wrong number of arguments: expected 1, got 0"),
+            (
+                "len([], [])",
+                "wrong number of arguments: expected 1, got 2",
+            ),
+            ("sort()", "wrong number of arguments: expected 1, got 0"),
+            (
+                "sort([], 1, 2)",
+                "wrong number of arguments: expected 2, got 3",
+            ),
+            ("format()", "wrong number of arguments: expected 1, got 0"),
+            (
+                "pad_start(\"a\")",
+                "wrong number of arguments: expected 2, got 1",
+            ),
+            (
+                "len(1)",
+                "argument 1 to `len` must be STRING, ARRAY or HASH, got INTEGER",
+            ),
+            (
+                "take([1], \"a\")",
+                "argument 2 to `take` must be INTEGER, got STRING",
+            ),
+            (
+                "pad_start(\"a\", 3, 1)",
+                "argument 3 to `pad_start` must be CHAR or STRING, got INTEGER",
+            ),
+            (
+                "sort([1], 2)",
+                "argument 2 to `sort` must be FUNCTION, got INTEGER",
+            ),
+            (
+                "map(1, str)",
+                "argument 1 to `map` must be ARRAY, STRING or HASH, got INTEGER",
+            ),
+            (
+                "is_digit(1)",
+                "argument 1 to `is_digit` must be STRING or CHAR, got INTEGER",
+            ),
+            (
+                "format(1, 2)",
+                "argument 1 to `format` must be STRING, got INTEGER",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                evaluate_error_program(input).to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_register_builtin() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_builtin("shout", |args| match args.as_slice() {
+            [Object::String(text)] => Ok(Object::String(text.to_uppercase())),
+            [arg] => Err(MonkeyError::UnsupportedArgument {
+                function: "shout",
+                position: 1,
+                expected: &[ObjectType::String],
+                argument: arg.object_type(),
+            }),
+            _ => Err(MonkeyError::IncorrectNumberOfArguments {
+                expected: 1,
+                actual: args.len(),
+            }),
+        });
+        let calls = Rc::new(RefCell::new(0));
+        let counter = Rc::clone(&calls);
+        evaluator.register_builtin("tick", move |args| {
+            *counter.borrow_mut() += 1;
+            Ok(Object::Integer(args.len() as i64))
+        });
+
+        let tests = [
+            (r#"shout("hi")"#, "HI"),
+            (
+                r#"let greet = fn(name) { shout(name) }; greet("monkey")"#,
+                "MONKEY",
+            ),
+            (r#"["a", "b"] |> first |> shout"#, "A"),
+            ("tick(1, 2, 3)", "3"),
+            ("shout", "builtin shout"),
+            (
+                "[shout == shout, shout == tick, len == len, len == first]",
+                "[true, false, true, false]",
+            ),
+            (
+                "try { shout(1) } catch (e) { e }",
+                "argument 1 to `shout` must be STRING, got INTEGER",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                evaluator.eval_source(input).map(|obj| obj.to_string()),
+                Ok(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(*calls.borrow(), 1);
+        assert_eq!(
+            evaluator.eval_source("shout()"),
+            Err(MonkeyError::IncorrectNumberOfArguments {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_builtin_map_filter_reduce() {
+        let tests = [
+            (
+                "map([1, 2, 3], fn(x) { x * x })",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(4),
+                    Object::Integer(9),
+                ]),
+            ),
+            ("map([], fn(x) { x })", Object::Array(vec![])),
+            (
+                r#"map([[1], [2, 3]], len)"#,
+                Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            ),
+            (
+                "filter([1, 2, 3, 4, 5], fn(x) { x % 2 == 1 })",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(3),
+                    Object::Integer(5),
+                ]),
+            ),
+            (
+                "reduce([1, 2, 3, 4], 0, fn(acc, x) { acc + x })",
+                Object::Integer(10),
+            ),
+            ("reduce([], 7, fn(acc, x) { acc + x })", Object::Integer(7)),
+            (
+                "reduce([1, 2, 3], [], fn(acc, x) { [x] + acc })",
+                Object::Array(vec![
+                    Object::Integer(3),
+                    Object::Integer(2),
+                    Object::Integer(1),
+                ]),
+            ),
+            (
+                "let offset = 10; [1, 2] |> map(fn(x) { x + offset }) |> reduce(0, fn(a, b) { a + b })",
+                Object::Integer(23),
+            ),
+            (
+                "let a = [1, 2]; map(a, fn(x) { x * 2 }); a",
+                Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            ),
+            (
+                r#"try { map([1, 0], fn(x) { 10 / x }) } catch (e) { e }"#,
+                Object::String("division by zero".to_string()),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"map([1, 2], fn(x) { if (x == 2) { error("two") }; x })"#,
+                MonkeyError::Raised("two".to_string()),
+            ),
+            (
+                "filter([1], fn(x, y) { true })",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+            (
+                "map([1], 5)",
+                MonkeyError::UnsupportedArgument {
+                    function: "map",
+                    position: 2,
+                    expected: &[ObjectType::Function],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"reduce([], 0, "add")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "reduce",
+                    position: 3,
+                    expected: &[ObjectType::Function],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "filter(1, fn(x) { x })",
+                MonkeyError::UnsupportedArgument {
+                    function: "filter",
+                    position: 1,
+                    expected: &[ObjectType::Array, ObjectType::String, ObjectType::Hash],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                "reduce([1], fn(a, b) { a })",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 3,
+                    actual: 2,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+
+        let mut evaluator = Evaluator::new();
+        evaluator.set_truthiness(Truthiness::Strict);
+        assert_eq!(
+            evaluator.eval_source("filter([1], fn(x) { x })"),
+            Err(MonkeyError::ConditionTypeMismatch(ObjectType::Integer))
+        );
+    }
+
+    #[test]
+    fn test_iterables() {
+        // `for` and the iterating builtins see the same items for every kind
+        let iterables = [
+            ("[1, 2, 3]", "[1, 2, 3]"),
+            ("1..4", "[1, 2, 3]"),
+            (r#""abc""#, "['a', 'b', 'c']"),
+            (r#"{"b": 2, "a": 1}"#, r#"[["a", 1], ["b", 2]]"#),
+            ("[]", "[]"),
+            (r#""""#, "[]"),
+            ("{}", "[]"),
+        ];
+        for (iterable, items) in iterables {
+            let expected = evaluate_program(items);
+            let through_for = format!(
+                "let xs = []; for (x in {}) {{ xs = xs + [x]; }}; xs",
+                iterable
+            );
+            let through_map = format!("map({}, fn(x) {{ x }})", iterable);
+            let through_filter = format!("filter({}, fn(x) {{ true }})", iterable);
+            let through_reduce = format!("reduce({}, [], fn(xs, x) {{ xs + [x] }})", iterable);
+            for input in [through_for, through_map, through_filter, through_reduce] {
+                assert_eq!(evaluate_program(&input), expected, "{}", input);
+            }
+        }
+
+        let tests = [
+            (
+                r#"map("ab", str)"#,
+                Object::Array(vec![
+                    Object::String("a".to_string()),
+                    Object::String("b".to_string()),
+                ]),
+            ),
+            (
+                r#"filter({"a": 1, "b": 2}, fn(pair) { pair[1] > 1 })"#,
+                Object::Array(vec![Object::Array(vec![
+                    Object::String("b".to_string()),
+                    Object::Integer(2),
+                ])]),
+            ),
+            (
+                r#"reduce({"a": 1, "b": 2}, 0, fn(total, pair) { total + pair[1] })"#,
+                Object::Integer(3),
+            ),
+            // a range `for` walks is counted out, not built, so it has no
+            // `MAX_RANGE_LEN`
+            (
+                "let n = 0; for (i in 0..9223372036854775807) { if (i == 3) { break; } n += 1; }; n",
+                Object::Integer(3),
+            ),
+            (
+                "let last = 0; for (i in -3..-1) { last = i; }; last",
+                Object::Integer(-2),
+            ),
+            ("let n = 0; for (i in 3..0) { n += 1; }; n", Object::Integer(0)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+        assert_eq!(
+            evaluate_error_program(r#"for (i in 0.."a") { }"#),
+            MonkeyError::InvalidRange {
+                start: ObjectType::Integer,
+                end: ObjectType::String,
+            }
+        );
+
+        for (input, function) in [
+            ("map(5, fn(x) { x })", "map"),
+            ("filter(true, fn(x) { x })", "filter"),
+            ("reduce(fn() { 1 }, 0, fn(a, x) { a })", "reduce"),
+        ] {
+            assert!(
+                matches!(
+                    evaluate_error_program(input),
+                    MonkeyError::UnsupportedArgument { function: f, .. } if f == function
+                ),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin_sort() {
+        let ints =
+            |values: &[i64]| Object::Array(values.iter().map(|v| Object::Integer(*v)).collect());
+        let tests = [
+            ("sort([3, 1, 2])", ints(&[1, 2, 3])),
+            ("sort([])", ints(&[])),
+            ("sort([5, -1, 5, 0])", ints(&[-1, 0, 5, 5])),
+            ("sort([3, 1, 2], fn(a, b) { b - a })", ints(&[3, 2, 1])),
+            ("let a = [2, 1]; sort(a); a", ints(&[2, 1])),
+            (
+                r#"sort(["pear", "apple", "Fig"])"#,
+                Object::Array(vec![
+                    Object::String("Fig".to_string()),
+                    Object::String("apple".to_string()),
+                    Object::String("pear".to_string()),
+                ]),
+            ),
+            // stable: equal keys keep their order
+            (
+                "let pairs = [[2, 1], [1, 2], [2, 3], [1, 4]];
+                 map(sort(pairs, fn(a, b) { a[0] - b[0] }), fn(p) { p[1] })",
+                ints(&[2, 4, 1, 3]),
+            ),
+            ("sort([1, 2, 3], fn(a, b) { 0 })", ints(&[1, 2, 3])),
+            // an inconsistent comparator gives some order, not a panic
+            (
+                "len(sort([4, 1, 3, 2], fn(a, b) { 1 }))",
+                Object::Integer(4),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"sort([1, "two"])"#,
+                MonkeyError::Incomparable {
+                    left: ObjectType::Integer,
+                    left_index: 0,
+                    right: ObjectType::String,
+                    right_index: 1,
+                },
+            ),
+            (
+                "sort([[1], [2]])",
+                MonkeyError::Incomparable {
+                    left: ObjectType::Array,
+                    left_index: 0,
+                    right: ObjectType::Array,
+                    right_index: 1,
+                },
+            ),
+            (
+                "sort([1, 2], fn(a, b) { a < b })",
+                MonkeyError::InvalidComparator(ObjectType::Bool),
+            ),
+            (
+                r#"sort([1, 2], fn(a, b) { error("no") })"#,
+                MonkeyError::Raised("no".to_string()),
+            ),
+            (
+                "sort([1], 2)",
+                MonkeyError::UnsupportedArgument {
+                    function: "sort",
+                    position: 2,
+                    expected: &[ObjectType::Function],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"sort("cba")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "sort",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "sort()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+            (
+                "sort([], fn(a, b) { 0 }, 1)",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 3,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+        // the indexes are where the clashing elements sit in the argument
+        assert_eq!(
+            evaluate_error_program(r#"sort([3, 1, 2, "four", 0])"#).to_string(),
+            "cannot sort STRING at index 3 against INTEGER at index 4"
+        );
+    }
+
+    #[test]
+    fn test_builtin_reverse_contains() {
+        let tests = [
+            (
+                "reverse([1, 2, 3])",
+                Object::Array(vec![
+                    Object::Integer(3),
+                    Object::Integer(2),
+                    Object::Integer(1),
+                ]),
+            ),
+            ("reverse([])", Object::Array(vec![])),
+            (
+                "let a = [1, 2]; reverse(a); a",
+                Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            ),
+            (r#"reverse("abc")"#, Object::String("cba".to_string())),
+            (
+                r#"reverse("héllo, 世界")"#,
+                Object::String("界世 ,olléh".to_string()),
+            ),
+            (r#"reverse("")"#, Object::String("".to_string())),
+            ("contains([1, 2, 3], 2)", Object::Bool(true)),
+            ("contains([1, 2, 3], 4)", Object::Bool(false)),
+            ("contains([], 1)", Object::Bool(false)),
+            ("contains([[1]], [1])", Object::Bool(true)),
+            (
+                r#"contains([{"a": [1, 2]}], {"a": [1, 2]})"#,
+                Object::Bool(true),
+            ),
+            (
+                r#"contains([{"a": [1, 2]}], {"a": [2, 1]})"#,
+                Object::Bool(false),
+            ),
+            (r#"contains([1, "1"], "1")"#, Object::Bool(true)),
+            (r#"contains(["1"], 1)"#, Object::Bool(false)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "reverse(12)",
+                MonkeyError::UnsupportedArgument {
+                    function: "reverse",
+                    position: 1,
+                    expected: &[ObjectType::Array, ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                "contains(123, 1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "contains",
+                    position: 1,
+                    expected: &[ObjectType::Array, ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                "contains([1])",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+            (
+                "reverse([1], [2])",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+            (
+                "let f = fn() { 1 }; contains([[f]], [f])",
+                MonkeyError::UnknownOperator {
+                    left: ObjectType::Array,
+                    operator: ast::Infix::Eq,
+                    right: ObjectType::Array,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_keys_values() {
+        let tests = [
+            (
+                r#"keys({"b": 2, "a": 1})"#,
+                Object::Array(vec![
+                    Object::String("a".to_string()),
+                    Object::String("b".to_string()),
+                ]),
+            ),
+            (
+                r#"values({"b": 2, "a": 1})"#,
+                Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+            ),
+            (
+                "keys({3: true, 1: false, 2: true})",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]),
+            ),
+            ("keys({})", Object::Array(vec![])),
+            ("values({})", Object::Array(vec![])),
+            (
+                r#"let h = {"x": [1], "y": {"z": 2}}; values(h)[1]["z"]"#,
+                Object::Integer(2),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "keys([1, 2])",
+                MonkeyError::UnsupportedArgument {
+                    function: "keys",
+                    position: 1,
+                    expected: &[ObjectType::Hash],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                r#"values("ab")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "values",
+                    position: 1,
+                    expected: &[ObjectType::Hash],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "keys({}, {})",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+            (
+                "values()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_has_key_delete() {
+        let tests = [
+            (r#"has_key({"a": 1}, "a")"#, Object::Bool(true)),
+            (r#"has_key({"a": 1}, "b")"#, Object::Bool(false)),
+            ("has_key({1: 1}, 1)", Object::Bool(true)),
+            (r#"has_key({1: 1}, "1")"#, Object::Bool(false)),
+            ("has_key({true: 1}, true)", Object::Bool(true)),
+            ("has_key({true: 1}, false)", Object::Bool(false)),
+            ("has_key({}, 1)", Object::Bool(false)),
+            (
+                r#"delete({"a": 1, "b": 2}, "a") == {"b": 2}"#,
+                Object::Bool(true),
+            ),
+            ("delete({1: 1, 2: 2}, 2) == {1: 1}", Object::Bool(true)),
+            (
+                "delete({true: 1, false: 0}, true) == {false: 0}",
+                Object::Bool(true),
+            ),
+            (r#"delete({"a": 1}, "b") == {"a": 1}"#, Object::Bool(true)),
+            ("delete({}, 1) == {}", Object::Bool(true)),
+            (
+                r#"let h = {"a": 1}; delete(h, "a"); has_key(h, "a")"#,
+                Object::Bool(true),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "has_key({}, [1])",
+                MonkeyError::UnusableAsHashKey(ObjectType::Array),
+            ),
+            (
+                "delete({}, fn() { 1 })",
+                MonkeyError::UnusableAsHashKey(ObjectType::Function),
+            ),
+            (
+                r#"has_key([1], 0)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "has_key",
+                    position: 1,
+                    expected: &[ObjectType::Hash],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                r#"delete("a", "a")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "delete",
+                    position: 1,
+                    expected: &[ObjectType::Hash],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "delete({})",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_conversions() {
+        let tests = [
+            ("int(str(42))", Object::Integer(42)),
+            ("int(str(-42))", Object::Integer(-42)),
+            (r#"int("+7")"#, Object::Integer(7)),
+            (r#"int("9223372036854775807")"#, Object::Integer(i64::MAX)),
+            ("int(5)", Object::Integer(5)),
+            ("int(true)", Object::Integer(1)),
+            ("int(false)", Object::Integer(0)),
+            (r#"str("a")"#, Object::String("a".to_string())),
+            ("str(true)", Object::String("true".to_string())),
+            ("str('c')", Object::String("c".to_string())),
+            (r#"str([1, "a"])"#, Object::String("[1, a]".to_string())),
+            (r#"str({"a": 1})"#, Object::String("{a: 1}".to_string())),
+            ("str(if (false) { 1 })", Object::String("null".to_string())),
+            ("bool(0)", Object::Bool(true)),
+            (r#"bool("")"#, Object::Bool(true)),
+            ("bool([])", Object::Bool(true)),
+            ("bool(false)", Object::Bool(false)),
+            ("bool(if (false) { 1 })", Object::Bool(false)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"int("12abc")"#,
+                MonkeyError::NotAnInteger("12abc".to_string()),
+            ),
+            (r#"int("")"#, MonkeyError::NotAnInteger("".to_string())),
+            (r#"int(" 1")"#, MonkeyError::NotAnInteger(" 1".to_string())),
+            (
+                r#"int("1_000")"#,
+                MonkeyError::NotAnInteger("1_000".to_string()),
+            ),
+            (
+                r#"int("9223372036854775808")"#,
+                MonkeyError::IntegerOverflow,
+            ),
+            (
+                "int([1])",
+                MonkeyError::UnsupportedArgument {
+                    function: "int",
+                    position: 1,
+                    expected: &[ObjectType::Integer, ObjectType::Bool, ObjectType::String],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                "str()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+            (
+                "bool(1, 2)",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_split_join() {
+        let strings = |parts: &[&str]| {
+            Object::Array(
+                parts
+                    .iter()
+                    .map(|part| Object::String(part.to_string()))
+                    .collect(),
+            )
+        };
+        let tests = [
+            (r#"split("a,b,c", ",")"#, strings(&["a", "b", "c"])),
+            (r#"split("a, b", ", ")"#, strings(&["a", "b"])),
+            (r#"split(",a,", ",")"#, strings(&["", "a", ""])),
+            (r#"split("abc", ";")"#, strings(&["abc"])),
+            (r#"split("", ",")"#, strings(&[""])),
+            (r#"split("", "")"#, Object::Array(vec![])),
+            (
+                r#"split("héllo", "")"#,
+                Object::Array("héllo".chars().map(Object::Char).collect()),
+            ),
+            (r#"split("東京→大阪", "→")"#, strings(&["東京", "大阪"])),
+            (
+                r#"join(["a", "b"], "-")"#,
+                Object::String("a-b".to_string()),
+            ),
+            (r#"join([], "-")"#, Object::String("".to_string())),
+            (r#"join(["a"], "-")"#, Object::String("a".to_string())),
+            (r#"join(["a", "b"], "")"#, Object::String("ab".to_string())),
+            (
+                r#"join([1, true, 'c', [2, 3]], " ")"#,
+                Object::String("1 true c [2, 3]".to_string()),
+            ),
+            (
+                r#"join(split("東京,大阪", ","), "・")"#,
+                Object::String("東京・大阪".to_string()),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        // splitting on "" gives the same chars as walking the string
+        for string in [r#""""#, r#""héllo""#, r#""東京""#] {
+            let input = format!(
+                "let cs = []; for (c in {0}) {{ cs = cs + [c]; }}; cs == split({0}, \"\")",
+                string
+            );
+            assert_eq!(evaluate_program(&input), Object::Bool(true), "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"split(["a"], ",")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "split",
+                    position: 1,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                r#"split("a,b", 1)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "split",
+                    position: 2,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"join("ab", "-")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "join",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "join([1], 0)",
+                MonkeyError::UnsupportedArgument {
+                    function: "join",
+                    position: 2,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"split("a")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_upper_lower_trim() {
+        let tests = [
+            (r#"upper("hello")"#, "HELLO"),
+            (r#"upper("straße")"#, "STRASSE"),
+            (r#"upper("ǆ")"#, "Ǆ"),
+            (r#"lower("HeLLo")"#, "hello"),
+            (r#"lower("ΣΑΣ")"#, "σας"),
+            (r#"lower("İ")"#, "i\u{307}"),
+            (r#"upper("")"#, ""),
+            (r#"upper("東京 1")"#, "東京 1"),
+            ("trim(\" \t a b \n\")", "a b"),
+            ("trim_start(\" \t a b \n\")", "a b \n"),
+            ("trim_end(\" \t a b \n\")", " \t a b"),
+            ("trim(\"\u{3000}全角\u{3000}\")", "全角"),
+            (r#"trim("   ")"#, ""),
+            (r#"trim("")"#, ""),
+            ("upper('a')", "A"),
+            ("trim(' ')", ""),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                evaluate_program(input),
+                Object::String(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+
+        let tests = [
+            (
+                "upper(1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "upper",
+                    position: 1,
+                    expected: &[ObjectType::String, ObjectType::Char],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"lower(["A"])"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "lower",
+                    position: 1,
+                    expected: &[ObjectType::String, ObjectType::Char],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                "trim_end(1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "trim_end",
+                    position: 1,
+                    expected: &[ObjectType::String, ObjectType::Char],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"trim(" a", "b")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+            (
+                "trim_start()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_string_search() {
+        let tests = [
+            (r#"contains("hello", "ell")"#, Object::Bool(true)),
+            (r#"contains("hello", "elo")"#, Object::Bool(false)),
+            (r#"contains("hello", "")"#, Object::Bool(true)),
+            (r#"contains("", "")"#, Object::Bool(true)),
+            (r#"contains("", "a")"#, Object::Bool(false)),
+            (r#"contains("aaa", "aa")"#, Object::Bool(true)),
+            (r#"contains("東京タワー", "タワ")"#, Object::Bool(true)),
+            (r#"index_of("hello", "l")"#, Object::Integer(2)),
+            (r#"index_of("hello", "lo")"#, Object::Integer(3)),
+            (r#"index_of("hello", "z")"#, Object::Integer(-1)),
+            (r#"index_of("hello", "")"#, Object::Integer(0)),
+            (r#"index_of("", "")"#, Object::Integer(0)),
+            (r#"index_of("aaaa", "aa")"#, Object::Integer(0)),
+            (r#"index_of("héllo", "l")"#, Object::Integer(2)),
+            (
+                r#"let s = "東京タワー"; s[index_of(s, "タ")]"#,
+                Object::Char('タ'),
+            ),
+            (
+                r#"replace("a-b-c", "-", "+")"#,
+                Object::String("a+b+c".to_string()),
+            ),
+            (
+                r#"replace("abc", "-", "+")"#,
+                Object::String("abc".to_string()),
+            ),
+            (
+                r#"replace("aaa", "aa", "b")"#,
+                Object::String("ba".to_string()),
+            ),
+            (
+                r#"replace("aaaa", "aa", "a")"#,
+                Object::String("aa".to_string()),
+            ),
+            (
+                r#"replace("a-b", "-", "")"#,
+                Object::String("ab".to_string()),
+            ),
+            (
+                r#"replace("ab", "", "-")"#,
+                Object::String("-a-b-".to_string()),
+            ),
+            (r#"replace("", "", "-")"#, Object::String("-".to_string())),
+            (
+                r#"replace("día día", "í", "i")"#,
+                Object::String("dia dia".to_string()),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"contains("abc", ['a'])"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "contains",
+                    position: 2,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                r#"index_of([1], 1)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "index_of",
+                    position: 1,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                r#"index_of("abc", 'b')"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "index_of",
+                    position: 2,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Char,
+                },
+            ),
+            (
+                r#"replace("abc", "b", 1)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "replace",
+                    position: 3,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"replace("abc", true, "c")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "replace",
+                    position: 2,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Bool,
+                },
+            ),
+            (
+                r#"replace("abc", "b")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 3,
+                    actual: 2,
+                },
+            ),
+            (
+                r#"index_of("abc")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_format() {
+        let tests = [
+            (
+                r#"let name = "ann"; let score = 7; format("{} scored {} points", name, score)"#,
+                "ann scored 7 points",
+            ),
+            (r#"format("no placeholders")"#, "no placeholders"),
+            (r#"format("")"#, ""),
+            (r#"format("{}{}", 1, 2)"#, "12"),
+            (r#"format("{{}} is {}", "literal")"#, "{} is literal"),
+            (r#"format("{{{}}}", 1)"#, "{1}"),
+            (r#"format("}}{{")"#, "}{"),
+            (
+                r#"format("{} and {}", [1, "a"], {"k": true})"#,
+                "[1, a] and {k: true}",
+            ),
+            (r#"format("{}", fn(x) { x })"#, "fn(x){x;}"),
+            (r#"format("{}", if (false) { 1 })"#, "null"),
+            (r#"format("→{}←", "東京")"#, "→東京←"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                evaluate_program(input),
+                Object::String(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+
+        let tests = [
+            (
+                r#"format("{} and {}", 1)"#,
+                MonkeyError::FormatArgumentMismatch {
+                    placeholders: 2,
+                    arguments: 1,
+                },
+            ),
+            (
+                r#"format("{}", 1, 2)"#,
+                MonkeyError::FormatArgumentMismatch {
+                    placeholders: 1,
+                    arguments: 2,
+                },
+            ),
+            (
+                r#"format("{{}}", 1)"#,
+                MonkeyError::FormatArgumentMismatch {
+                    placeholders: 0,
+                    arguments: 1,
+                },
+            ),
+            (
+                r#"format("{x}", 1)"#,
+                MonkeyError::UnmatchedBrace("{x}".to_string()),
+            ),
+            (
+                r#"format("a }", 1)"#,
+                MonkeyError::UnmatchedBrace("a }".to_string()),
+            ),
+            (
+                r#"format("{")"#,
+                MonkeyError::UnmatchedBrace("{".to_string()),
+            ),
+            (
+                "format(1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "format",
+                    position: 1,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                "format()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_is_empty() {
+        let containers = [
+            (r#""""#, 0),
+            (r#""ab""#, 2),
+            (r#""東京""#, 2),
+            ("[]", 0),
+            ("[[]]", 1),
+            ("{}", 0),
+            (r#"{"a": 1, "b": 2}"#, 2),
+        ];
+        for (value, size) in containers {
+            let input = format!("[len({0}), is_empty({0})]", value);
+            assert_eq!(
+                evaluate_program(&input),
+                Object::Array(vec![Object::Integer(size), Object::Bool(size == 0)]),
+                "{}",
+                input
+            );
+        }
+
+        let others = [
+            ("0", ObjectType::Integer),
+            ("false", ObjectType::Bool),
+            ("'a'", ObjectType::Char),
+            ("if (false) { 1 }", ObjectType::Null),
+            ("fn() { [] }", ObjectType::Function),
+            ("len", ObjectType::Function),
+        ];
+        for (value, argument) in others {
+            for function in ["len", "is_empty"] {
+                let input = format!("{}({})", function, value);
+                assert_eq!(
+                    evaluate_error_program(&input),
+                    MonkeyError::UnsupportedArgument {
+                        function,
+                        position: 1,
+                        expected: &[ObjectType::String, ObjectType::Array, ObjectType::Hash],
+                        argument: argument.clone(),
+                    },
+                    "{}",
+                    input
+                );
+            }
+        }
+        assert_eq!(
+            evaluate_error_program("is_empty([], [])"),
+            MonkeyError::IncorrectNumberOfArguments {
+                expected: 1,
+                actual: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_builtin_take_drop_chunk() {
+        let tests = [
+            ("take([1, 2, 3], 2)", "[1, 2]"),
+            ("take([1, 2, 3], 0)", "[]"),
+            ("take([1, 2, 3], 5)", "[1, 2, 3]"),
+            ("take([1, 2, 3], -1)", "[1, 2]"),
+            ("take([], 2)", "[]"),
+            ("drop([1, 2, 3], 2)", "[3]"),
+            ("drop([1, 2, 3], 0)", "[1, 2, 3]"),
+            ("drop([1, 2, 3], 5)", "[]"),
+            ("drop([1, 2, 3], -1)", "[3]"),
+            (
+                "let a = [1, 2, 3]; [take(a, 1), drop(a, 1), a]",
+                "[[1], [2, 3], [1, 2, 3]]",
+            ),
+            ("let a = [1, 2, 3, 4]; take(a, 3) == a[:3]", "true"),
+            ("chunk([1, 2, 3, 4, 5], 2)", "[[1, 2], [3, 4], [5]]"),
+            ("chunk([1, 2, 3, 4], 2)", "[[1, 2], [3, 4]]"),
+            ("chunk([1, 2], 5)", "[[1, 2]]"),
+            ("chunk([], 3)", "[]"),
+            (
+                "map(chunk(0..6, 3), fn(page) { take(page, 1) })",
+                "[[0], [3]]",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input).to_string(), expected, "{}", input);
+        }
+
+        let tests = [
+            ("chunk([1], 0)", MonkeyError::InvalidChunkSize(0)),
+            ("chunk([1], -2)", MonkeyError::InvalidChunkSize(-2)),
+            (
+                r#"take("abc", 1)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "take",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "drop([1], true)",
+                MonkeyError::UnsupportedArgument {
+                    function: "drop",
+                    position: 2,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::Bool,
+                },
+            ),
+            (
+                "chunk([1])",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_aggregates() {
+        let tests = [
+            ("sum([1, 2, 3])", "6"),
+            ("sum([])", "0"),
+            ("sum([-5, 5])", "0"),
+            ("product([2, 3, 4])", "24"),
+            ("product([])", "1"),
+            ("product([7, 0])", "0"),
+            ("min_of([3, -1, 2])", "-1"),
+            ("max_of([3, -1, 2])", "3"),
+            ("min_of([4])", "4"),
+            ("min_of([])", "null"),
+            ("max_of([])", "null"),
+            ("sum(1..101)", "5050"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input).to_string(), expected, "{}", input);
+        }
+
+        // each agrees with its reduce
+        let arrays = ["[4]", "[3, -1, 2]", "[10, 20, 30, -7]"];
+        for array in arrays {
+            let input = format!(
+                "let xs = {};
+                 [sum(xs) == reduce(xs, 0, fn(a, b) {{ a + b }}),
+                  product(xs) == reduce(xs, 1, fn(a, b) {{ a * b }}),
+                  max_of(xs) == reduce(xs, first(xs), fn(a, b) {{ if (b > a) {{ b }} else {{ a }} }}),
+                  min_of(xs) == reduce(xs, first(xs), fn(a, b) {{ if (b < a) {{ b }} else {{ a }} }})]",
+                array
+            );
+            assert_eq!(
+                evaluate_program(&input).to_string(),
+                "[true, true, true, true]",
+                "{}",
+                array
+            );
+        }
+
+        let tests = [
+            (
+                "sum([9223372036854775807, 1])",
+                MonkeyError::IntegerOverflow,
+            ),
+            (
+                "product([4611686018427387904, 2])",
+                MonkeyError::IntegerOverflow,
+            ),
+            (
+                r#"sum([1, "2"])"#,
+                MonkeyError::NonNumericElement {
+                    function: "sum",
+                    index: 1,
+                    element: ObjectType::String,
+                },
+            ),
+            (
+                "max_of([1, 2, [3]])",
+                MonkeyError::NonNumericElement {
+                    function: "max_of",
+                    index: 2,
+                    element: ObjectType::Array,
+                },
+            ),
+            (
+                "min_of({1: 1})",
+                MonkeyError::UnsupportedArgument {
+                    function: "min_of",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::Hash,
+                },
+            ),
+            (
+                "product(2)",
+                MonkeyError::UnsupportedArgument {
+                    function: "product",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::Integer,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_pad_repeat() {
+        let tests = [
+            (r#"pad_start("7", 3)"#, "  7"),
+            (r#"pad_start("7", 3, "0")"#, "007"),
+            (r#"pad_start("7", 3, '0')"#, "007"),
+            (r#"pad_end("ab", 4, "·")"#, "ab··"),
+            (r#"pad_start("東京", 4, "＊")"#, "＊＊東京"),
+            (r#"pad_end("héllo", 6)"#, "héllo "),
+            (r#"pad_start("long", 2)"#, "long"),
+            (r#"pad_end("long", 4)"#, "long"),
+            (r#"pad_start("x", 0)"#, "x"),
+            (r#"pad_end("x", -3)"#, "x"),
+            (r#"pad_start("", 2, "-")"#, "--"),
+            (r#"repeat("ab", 3)"#, "ababab"),
+            (r#"repeat("ab", 1)"#, "ab"),
+            (r#"repeat("ab", 0)"#, ""),
+            (r#"repeat("ab", -1)"#, ""),
+            (r#"repeat("", 1000000000)"#, ""),
+            (r#"repeat("é→", 2)"#, "é→é→"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                evaluate_program(input),
+                Object::String(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+
+        let tests = [
+            (
+                r#"repeat("ab", 10000000)"#,
+                MonkeyError::StringTooLong {
+                    length: 20_000_000,
+                    limit: 1 << 24,
+                },
+            ),
+            (
+                r#"repeat("a", 9223372036854775807)"#,
+                MonkeyError::StringTooLong {
+                    length: 9_223_372_036_854_775_807,
+                    limit: 1 << 24,
+                },
+            ),
+            (
+                r#"pad_start("a", 100000000)"#,
+                MonkeyError::StringTooLong {
+                    length: 100_000_000,
+                    limit: 1 << 24,
+                },
+            ),
+            (
+                r#"pad_start("a", 3, "ab")"#,
+                MonkeyError::InvalidFill("ab".to_string()),
+            ),
+            (
+                r#"pad_end("a", 3, "")"#,
+                MonkeyError::InvalidFill("".to_string()),
+            ),
+            (
+                r#"pad_end("a", 3, 0)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "pad_end",
+                    position: 3,
+                    expected: &[ObjectType::Char, ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"pad_start(1, 3)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "pad_start",
+                    position: 1,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"repeat("a", "3")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "repeat",
+                    position: 2,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                r#"pad_start("a")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+            (
+                r#"pad_end("a", 1, " ", " ")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 3,
+                    actual: 4,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_entries() {
+        let tests = [
+            (r#"entries({"b": 2, "a": 1})"#, "[[a, 1], [b, 2]]"),
+            ("entries({})", "[]"),
+            (
+                r#"let h = {"b": 2, "a": 1}; map(entries(h), first) == keys(h)"#,
+                "true",
+            ),
+            (
+                r#"let h = {"b": 2, "a": 1}; map(entries(h), last) == values(h)"#,
+                "true",
+            ),
+            (
+                r#"let h = {1: "x", true: [1], "k": {"n": 'c'}}; from_entries(entries(h)) == h"#,
+                "true",
+            ),
+            (
+                r#"from_entries([["a", 1], [2, "b"]]) == {"a": 1, 2: "b"}"#,
+                "true",
+            ),
+            (r#"from_entries([["a", 1], ["a", 2]]) == {"a": 2}"#, "true"),
+            ("from_entries([]) == {}", "true"),
+            (
+                r#"let h = {"a": 1, "b": -1, "c": 2};
+                   keys(from_entries(filter(entries(h), fn(kv) { kv[1] > 0 })))"#,
+                "[a, c]",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input).to_string(), expected, "{}", input);
+        }
+
+        let malformed = |index, entry: &str| MonkeyError::MalformedEntry {
+            index,
+            entry: entry.to_string(),
+        };
+        let tests = [
+            (r#"from_entries([["a", 1], "b"])"#, malformed(1, "b")),
+            (r#"from_entries([["a", 1], ["b"]])"#, malformed(1, "[b]")),
+            ("from_entries([[1, 2, 3]])", malformed(0, "[1, 2, 3]")),
+            ("from_entries([[[1], 2]])", malformed(0, "[[1], 2]")),
+            (
+                "from_entries({})",
+                MonkeyError::UnsupportedArgument {
+                    function: "from_entries",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::Hash,
+                },
+            ),
+            (
+                "entries([])",
+                MonkeyError::UnsupportedArgument {
+                    function: "entries",
+                    position: 1,
+                    expected: &[ObjectType::Hash],
+                    argument: ObjectType::Array,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_with_swap() {
+        let tests = [
+            ("with([1, 2, 3], 1, 9)", "[1, 9, 3]"),
+            ("with([1, 2, 3], -1, 9)", "[1, 2, 9]"),
+            (r#"with({"a": 1}, "a", 2) == {"a": 2}"#, "true"),
+            (r#"with({"a": 1}, "b", 2) == {"a": 1, "b": 2}"#, "true"),
+            ("swap([1, 2, 3], 0, 2)", "[3, 2, 1]"),
+            ("swap([1, 2, 3], 1, 1)", "[1, 2, 3]"),
+            ("swap([1, 2, 3], 0, -1)", "[3, 2, 1]"),
+            (
+                r#"let a = [1, 2]; let h = {"k": 1};
+                   [with(a, 0, 0), swap(a, 0, 1), with(h, "k", 0)["k"], a, h["k"]]"#,
+                "[[0, 2], [2, 1], 0, [1, 2], 1]",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input).to_string(), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "with([1, 2], 2, 0)",
+                MonkeyError::IndexOutOfRange {
+                    index: 2,
+                    length: 2,
+                },
+            ),
+            (
+                "with([], 0, 0)",
+                MonkeyError::IndexOutOfRange {
+                    index: 0,
+                    length: 0,
+                },
+            ),
+            (
+                "swap([1, 2], 0, -3)",
+                MonkeyError::IndexOutOfRange {
+                    index: -3,
+                    length: 2,
+                },
+            ),
+            (
+                "swap([1, 2], 5, 0)",
+                MonkeyError::IndexOutOfRange {
+                    index: 5,
+                    length: 2,
+                },
+            ),
+            (
+                "with({}, [1], 0)",
+                MonkeyError::UnusableAsHashKey(ObjectType::Array),
+            ),
+            (
+                r#"with([1], "0", 0)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "with",
+                    position: 2,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                r#"swap("ab", 0, 1)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "swap",
+                    position: 1,
+                    expected: &[ObjectType::Array],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "swap([1, 2], 0, true)",
+                MonkeyError::UnsupportedArgument {
+                    function: "swap",
+                    position: 3,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::Bool,
+                },
+            ),
+            (
+                "with([1], 0)",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 3,
+                    actual: 2,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_string_scanning() {
+        let tests = [
+            (r#"index_of("hello", "l", 3)"#, "3"),
+            (r#"index_of("hello", "l", 4)"#, "-1"),
+            (r#"index_of("hello", "h", 0)"#, "0"),
+            (r#"index_of("hello", "o", -1)"#, "4"),
+            (r#"index_of("hello", "h", -10)"#, "0"),
+            (r#"index_of("hello", "", 9)"#, "5"),
+            (r#"index_of("héllo héllo", "é", 2)"#, "7"),
+            (r#"index_of("東京と東京", "東京", 1)"#, "3"),
+            (r#"count("banana", "a")"#, "3"),
+            (r#"count("banana", "ana")"#, "1"),
+            (r#"count("aaaa", "aa")"#, "2"),
+            (r#"count("abc", "z")"#, "0"),
+            (r#"count("abc", "")"#, "4"),
+            (r#"count("", "")"#, "1"),
+            (r#"count("東京と東京", "東京")"#, "2"),
+            (r#"split_n("a,b,c", ",", 1)"#, "[a, b,c]"),
+            (r#"split_n("a,b,c", ",", 2)"#, "[a, b, c]"),
+            (r#"split_n("a,b,c", ",", 9)"#, "[a, b, c]"),
+            (r#"split_n("a,b,c", ",", 0)"#, "[a,b,c]"),
+            (r#"split_n("a,b,c", ",", -1)"#, "[a,b,c]"),
+            (r#"split_n("abc", ";", 1)"#, "[abc]"),
+            (r#"split_n("abc", "", 1)"#, "[a, bc]"),
+            (r#"split_n("abc", "", 5) == split("abc", "")"#, "true"),
+            (r#"split_n("", "", 1) == split("", "")"#, "true"),
+            (r#"split_n("東京→大阪→博多", "→", 1)"#, "[東京, 大阪→博多]"),
+            (r#"split_n("héllo", "", 2)"#, "[h, é, llo]"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input).to_string(), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                r#"index_of("abc", "b", "1")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "index_of",
+                    position: 3,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                r#"count(["a"], "a")"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "count",
+                    position: 1,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                r#"split_n("a,b", ',', 1)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "split_n",
+                    position: 2,
+                    expected: &[ObjectType::String],
+                    argument: ObjectType::Char,
+                },
+            ),
+            (
+                r#"index_of("abc", "b", 0, 1)"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 3,
+                    actual: 4,
+                },
+            ),
+            (
+                r#"split_n("a,b", ",")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 3,
+                    actual: 2,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_char_classes() {
+        let tests = [
+            (
+                "is_digit",
+                [("0123", true), ("12a", false), ("١٢", false), ("", false)],
+            ),
+            (
+                "is_alpha",
+                [
+                    ("abcXYZ", true),
+                    ("héllo", true),
+                    ("東京", true),
+                    ("a1", false),
+                ],
+            ),
+            (
+                "is_alnum",
+                [
+                    ("abc123", true),
+                    ("東京2", true),
+                    ("a_1", false),
+                    ("", false),
+                ],
+            ),
+            (
+                "is_whitespace",
+                [
+                    (" \t\n", true),
+                    ("\u{3000}", true),
+                    (" a ", false),
+                    ("", false),
+                ],
+            ),
+            (
+                "is_upper",
+                [("ABC", true), ("ÉΣ", true), ("AbC", false), ("A1", false)],
+            ),
+            (
+                "is_lower",
+                [("abc", true), ("ßéσ", true), ("abC", false), ("東", false)],
+            ),
+        ];
+        for (function, cases) in tests {
+            for (string, expected) in cases {
+                let input = format!("{}(\"{}\")", function, string);
+                assert_eq!(
+                    evaluate_program(&input),
+                    Object::Bool(expected),
+                    "{}",
+                    input
+                );
+            }
+        }
+        assert_eq!(
+            evaluate_program(r#"filter(split("a1 b2", ""), is_digit)"#).to_string(),
+            "[1, 2]"
+        );
+        assert_eq!(
+            evaluate_program(
+                r#"let n = 0; for (c in "a1 b2") { if (is_digit(c)) { n += 1; } }; n"#
+            ),
+            Object::Integer(2)
+        );
+        assert_eq!(
+            evaluate_program("[is_digit == is_digit, is_digit == is_alpha]").to_string(),
+            "[true, false]"
+        );
+
+        let tests = [
+            (
+                "is_digit([1])",
+                MonkeyError::UnsupportedArgument {
+                    function: "is_digit",
+                    position: 1,
+                    expected: &[ObjectType::String, ObjectType::Char],
+                    argument: ObjectType::Array,
+                },
+            ),
+            (
+                "is_lower(1)",
+                MonkeyError::UnsupportedArgument {
+                    function: "is_lower",
+                    position: 1,
+                    expected: &[ObjectType::String, ObjectType::Char],
+                    argument: ObjectType::Integer,
+                },
+            ),
+            (
+                r#"is_alpha("a", "b")"#,
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 2,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_builtin_number_formatting() {
+        let tests = [
+            ("to_fixed(5, 2)", "5.00"),
+            ("to_fixed(-5, 1)", "-5.0"),
+            ("to_fixed(5, 0)", "5"),
+            ("to_hex(255)", "ff"),
+            ("to_hex(-255)", "-ff"),
+            ("to_hex(0)", "0"),
+            ("to_hex(-9223372036854775807 - 1)", "-8000000000000000"),
+            ("to_bin(5)", "101"),
+            ("to_bin(-5)", "-101"),
+            ("to_oct(64)", "100"),
+            ("to_oct(-8)", "-10"),
+            ("format_int(7, 3)", "007"),
+            ("format_int(-7, 4)", "-007"),
+            ("format_int(7, 3, ' ')", "  7"),
+            ("format_int(-7, 4, \" \")", "  -7"),
+            ("format_int(1234, 2)", "1234"),
+            ("format_int(7, 0)", "7"),
+            ("format_int(42, 5, \"·\")", "···42"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(
+                evaluate_program(input),
+                Object::String(expected.to_string()),
+                "{}",
+                input
+            );
+        }
+
+        let tests = [
+            (
+                "to_fixed(1, -1)",
+                MonkeyError::InvalidDigits {
+                    digits: -1,
+                    limit: 100,
+                },
+            ),
+            (
+                "to_fixed(1, 101)",
+                MonkeyError::InvalidDigits {
+                    digits: 101,
+                    limit: 100,
+                },
+            ),
+            (
+                r#"to_fixed("1", 2)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "to_fixed",
+                    position: 1,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                "to_hex(true)",
+                MonkeyError::UnsupportedArgument {
+                    function: "to_hex",
+                    position: 1,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::Bool,
+                },
+            ),
+            (
+                r#"format_int("7", 3)"#,
+                MonkeyError::UnsupportedArgument {
+                    function: "format_int",
+                    position: 1,
+                    expected: &[ObjectType::Integer],
+                    argument: ObjectType::String,
+                },
+            ),
+            (
+                r#"format_int(7, 3, "00")"#,
+                MonkeyError::InvalidFill("00".to_string()),
+            ),
+            (
+                "to_bin()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let tests = [
+            (
+                r#"try { error("boom") } catch (e) { e }"#,
+                Object::String("boom".to_string()),
+            ),
+            ("try { 1 } catch (e) { 2 }", Object::Integer(1)),
+            (
+                r#"let check = fn(n) { if (n < 0) { error("negative") }; n };
+                   let outer = fn(n) { check(n) * 2 };
+                   let e = "";
+                   let r = try { outer(-1) } catch (err) { e = err; 0 };
+                   [r, e]"#,
+                Object::Array(vec![
+                    Object::Integer(0),
+                    Object::String("negative".to_string()),
+                ]),
+            ),
+            (
+                "try { error(42) } catch (e) { e }",
+                Object::String("42".to_string()),
+            ),
+            (
+                "try { 1 / 0 } catch (e) { e }",
+                Object::String("division by zero".to_string()),
+            ),
+            (
+                "try { missing } catch (e) { e }",
+                Object::String("identifier not found: missing".to_string()),
+            ),
+            // the first error ends the body
+            (
+                r#"let x = 1; try { error("a"); x = 2; } catch (e) { x }"#,
+                Object::Integer(1),
+            ),
+            (
+                r#"try { try { error("inner") } catch (e) { 1 / 0 } } catch (e) { e }"#,
+                Object::String("division by zero".to_string()),
+            ),
+            (
+                r#"try { try { error("inner") } catch (e) { error(e) } } catch (e) { e }"#,
+                Object::String("inner".to_string()),
+            ),
+            // a call in a try body is made there, even after `return`
+            (
+                r#"let f = fn() { try { return error("late"); } catch (e) { e } }; f()"#,
+                Object::String("late".to_string()),
+            ),
+            (
+                "let f = fn() { try { return 1; } catch (e) { 2 }; 3 }; f()",
+                Object::Integer(1),
+            ),
+            ("let error = fn(x) { x }; error(5)", Object::Integer(5)),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            (r#"error("boom")"#, MonkeyError::Raised("boom".to_string())),
+            (
+                r#"let f = fn() { error("deep") }; let g = fn() { f() }; g()"#,
+                MonkeyError::Raised("deep".to_string()),
+            ),
+            (
+                r#"try { error("a") } catch (e) { error("b") }"#,
+                MonkeyError::Raised("b".to_string()),
+            ),
+            (
+                r#"try { error("a") } catch (e) { 1 }; e"#,
+                MonkeyError::IdentifierNotFound("e".to_string()),
+            ),
+            (
+                "error()",
+                MonkeyError::IncorrectNumberOfArguments {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_error_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let tests = [
+            (
+                "let fact = fn(n) { if (n == 0) { 1 } else { n * fact(n - 1) } }; fact(5)",
+                Object::Integer(120),
+            ),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+                Object::Integer(610),
+            ),
+            // `odd` is bound after `even` is created
+            (
+                "let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } };
+                let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } };
+                [even(10), odd(10), even(7), odd(7)]",
+                Object::Array(vec![
+                    Object::Bool(true),
+                    Object::Bool(false),
+                    Object::Bool(false),
+                    Object::Bool(true),
+                ]),
+            ),
+            // a recursive closure defined inside another function's call scope
+            (
+                "let sum_to = fn(limit) { let go = fn(n) { if (n > limit) { 0 } else { n + go(n + 1) } }; go(1) };
+                sum_to(10)",
+                Object::Integer(55),
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_call_depth() {
+        // on the test's own thread, 2 MiB by default
+        let count = "let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } };";
+        let depth = MAX_CALL_DEPTH as i64;
+        assert_eq!(
+            evaluate_program(&format!("{} count({})", count, depth - 1)),
+            Object::Integer(depth - 1)
+        );
+        let tests = [
+            format!("{} count({})", count, depth),
+            format!("{} count(1000000)", count),
+            // calls made by builtins count too
+            "let f = fn(n) { map([n], fn(x) { f(x + 1) }) }; f(0)".to_string(),
+        ];
+        for input in tests {
+            assert_eq!(
+                evaluate_error_program(&input),
+                MonkeyError::CallStackExceeded(MAX_CALL_DEPTH),
+                "{}",
+                input
+            );
+        }
+        // the error can be caught, and only calls under way count
+        assert_eq!(
+            evaluate_program(&format!(
+                "{} let caught = try {{ count({}) }} catch (e) {{ -1 }}; [caught, count(10)]",
+                count, depth
+            )),
+            Object::Array(vec![Object::Integer(-1), Object::Integer(10)])
+        );
+
+        // a thread spawned with the default stack, as an embedder's would be,
+        // and one with far less
+        let deepest = move || {
+            crate::interpret(&format!("{} count({})", count, depth - 1)).map(|obj| obj.to_string())
+        };
+        let expected = Ok((depth - 1).to_string());
+        assert_eq!(std::thread::spawn(deepest).join().unwrap(), expected);
+        let small = std::thread::Builder::new().stack_size(256 << 10);
+        assert_eq!(small.spawn(deepest).unwrap().join().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_shared_closure_environment() {
+        let input = "
+            let counter = fn() { let c = 0; fn() { c = c + 1; c } }();
+            [counter(), counter(), counter()]
+        ";
+        assert_eq!(
+            evaluate_program(input),
+            Object::Array(vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+            ])
+        );
+
+        // two closures over one variable, and a copy of a closure
+        let input = "
+            let make = fn() {
+                let balance = 10;
+                [fn(n) { balance = balance + n; }, fn() { balance }]
+            };
+            let account = make();
+            let deposit = account[0];
+            let read = account[1];
+            deposit(5);
+            account[0](1);
+            [read(), make()[1]()]
+        ";
+        assert_eq!(
+            evaluate_program(input),
+            Object::Array(vec![Object::Integer(16), Object::Integer(10)])
+        );
+
+        // each call still gets its own scope for parameters and `let`
+        let input = "
+            let x = 0;
+            let f = fn(n) { let local = n; x = x + local; local };
+            [f(1), f(2), x]
+        ";
+        assert_eq!(
+            evaluate_program(input),
+            Object::Array(vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_rest_parameters() {
+        let tests = [
+            (
+                "fn(...xs) { xs }(1, 2, 3)",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Integer(2),
+                    Object::Integer(3),
+                ]),
+            ),
+            ("fn(...xs) { xs }()", Object::Array(vec![])),
+            (
+                "let f = fn(first, ...rest) { [first, rest] }; f(1, 2, 3)",
+                Object::Array(vec![
+                    Object::Integer(1),
+                    Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+                ]),
+            ),
+            (
+                "let f = fn(first, ...rest) { rest }; f(1)",
+                Object::Array(vec![]),
+            ),
+            (
+                "let sum = fn(...xs) { let total = 0; for (x in xs) { total += x; }; total }; sum(1, 2, 3, 4)",
+                Object::Integer(10),
+            ),
+            (
+                "let count = fn(...xs) { let n = 0; for (x in xs) { n += 1; }; n }; 5 |> count(6, 7)",
+                Object::Integer(3),
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(evaluate_program(input), expected, "{}", input);
+        }
+
+        let tests = [
+            ("fn(a, b, ...rest) { a }(1)", 2, 1),
+            ("fn(a, ...rest) { a }()", 1, 0),
+            ("fn(a, b) { a }(1, 2, 3)", 2, 3),
+        ];
+        for (input, expected, actual) in tests {
+            assert_eq!(
+                evaluate_error_program(input),
+                MonkeyError::IncorrectNumberOfArguments { expected, actual },
+                "{}",
+                input
+            );
         }
     }
 
@@ -1,37 +1,266 @@
-use std::fmt;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, DefaultHasher, Hasher, RandomState},
+    rc::Rc,
+};
 
-use crate::{eval::environment, parser::ast};
+use crate::{
+    error::MonkeyError,
+    eval::{builtins::Builtin, environment},
+    parser::ast,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ObjectType {
     Integer,
+    String,
+    Char,
     Bool,
     Null,
+    Function,
+    Array,
+    Hash,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectType::Integer => write!(f, "INTEGER"),
+            ObjectType::String => write!(f, "STRING"),
+            ObjectType::Char => write!(f, "CHAR"),
+            ObjectType::Bool => write!(f, "BOOLEAN"),
+            ObjectType::Null => write!(f, "NULL"),
+            ObjectType::Function => write!(f, "FUNCTION"),
+            ObjectType::Array => write!(f, "ARRAY"),
+            ObjectType::Hash => write!(f, "HASH"),
+        }
+    }
+}
+
+/// the most pairs a hash holds, so a script cannot make one exhaust memory
+/// by accident
+pub(crate) const MAX_HASH_LEN: usize = 1 << 20;
+
+/// Keys the SipHash hasher of every hash an evaluator builds. It is drawn
+/// from the OS for each evaluator, so script-chosen keys cannot be crafted
+/// to collide, unless `Evaluator::set_hash_seed` fixes it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct HashState {
+    key: u64,
+}
+
+impl HashState {
+    pub(crate) fn random() -> Self {
+        HashState {
+            key: RandomState::new().hash_one(()),
+        }
+    }
+
+    pub(crate) fn seeded(seed: u64) -> Self {
+        HashState { key: seed }
+    }
+}
+
+impl Default for HashState {
+    fn default() -> Self {
+        HashState::random()
+    }
+}
+
+impl BuildHasher for HashState {
+    type Hasher = DefaultHasher;
+
+    /// std's SipHash-1-3, keyed by hashing our key ahead of each value
+    fn build_hasher(&self) -> DefaultHasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.key);
+        hasher
+    }
+}
+
+/// `pairs.insert(key, value)`, failing with `HashTooLarge` instead when a
+/// new key would take `pairs` past `MAX_HASH_LEN`
+pub(crate) fn insert_pair(
+    pairs: &mut HashMap<HashKey, Object, HashState>,
+    key: HashKey,
+    value: Object,
+) -> Result<(), MonkeyError> {
+    if pairs.len() >= MAX_HASH_LEN && !pairs.contains_key(&key) {
+        return Err(MonkeyError::HashTooLarge(MAX_HASH_LEN));
+    }
+    pairs.insert(key, value);
+    Ok(())
+}
+
+/// The values allowed as keys of `Object::Hash`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum HashKey {
+    Integer(i64),
+    /// only for values outside the i64 range, so each integer has one key
+    #[cfg(feature = "bigint")]
+    BigInteger(num_bigint::BigInt),
+    Bool(bool),
+    String(String),
+    Char(char),
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::Integer(val) => write!(f, "{}", val),
+            #[cfg(feature = "bigint")]
+            HashKey::BigInteger(val) => write!(f, "{}", val),
+            HashKey::Bool(val) => write!(f, "{}", val),
+            HashKey::String(val) => write!(f, "{}", val),
+            HashKey::Char(val) => write!(f, "{}", val),
+        }
+    }
+}
+
+impl HashKey {
+    /// the key as `Object::inspect` shows it
+    pub fn inspect(&self) -> String {
+        match self {
+            HashKey::String(val) => format!("{:?}", val),
+            HashKey::Char(val) => format!("{:?}", val),
+            key => key.to_string(),
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Integer(val) => Object::Integer(val),
+            #[cfg(feature = "bigint")]
+            HashKey::BigInteger(val) => Object::BigInteger(val),
+            HashKey::Bool(val) => Object::Bool(val),
+            HashKey::String(val) => Object::String(val),
+            HashKey::Char(val) => Object::Char(val),
+        }
+    }
+}
+
+/// `Debug` and `PartialEq` are written out below: a function's environment
+/// can hold the function itself, so it is compared by identity and printed
+/// opaquely rather than walked.
+#[derive(Clone)]
 pub enum Object {
     Integer(i64),
+    /// only produced in bigint mode, for values outside the i64 range
+    #[cfg(feature = "bigint")]
+    BigInteger(num_bigint::BigInt),
     String(String),
+    Char(char),
     Bool(bool),
     Null,
-    Return(Box<Object>),
+    Array(Vec<Object>),
+    /// hashed as `HashState` says
+    Hash(HashMap<HashKey, Object, HashState>),
     Function {
-        parameters: Vec<ast::Expression>,
-        body: ast::Statement,
-        env: environment::Environment,
+        parameters: Vec<ast::Parameter>,
+        body: Rc<ast::Statement>,
+        /// the scope the literal was evaluated in, shared rather than copied,
+        /// so every call's scope encloses the same bindings
+        env: Rc<RefCell<environment::Environment>>,
     },
+    Builtin(Builtin),
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(val) => f.debug_tuple("Integer").field(val).finish(),
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(val) => f.debug_tuple("BigInteger").field(val).finish(),
+            Object::String(val) => f.debug_tuple("String").field(val).finish(),
+            Object::Char(val) => f.debug_tuple("Char").field(val).finish(),
+            Object::Bool(val) => f.debug_tuple("Bool").field(val).finish(),
+            Object::Null => write!(f, "Null"),
+            Object::Array(elements) => f.debug_tuple("Array").field(elements).finish(),
+            Object::Hash(pairs) => f.debug_tuple("Hash").field(pairs).finish(),
+            Object::Function {
+                parameters, body, ..
+            } => f
+                .debug_struct("Function")
+                .field("parameters", parameters)
+                .field("body", body)
+                .field("env", &format_args!("<scope>"))
+                .finish(),
+            Object::Builtin(builtin) => f.debug_tuple("Builtin").field(builtin).finish(),
+        }
+    }
 }
 
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Integer(left), Object::Integer(right)) => left == right,
+            #[cfg(feature = "bigint")]
+            (Object::BigInteger(left), Object::BigInteger(right)) => left == right,
+            (Object::String(left), Object::String(right)) => left == right,
+            (Object::Char(left), Object::Char(right)) => left == right,
+            (Object::Bool(left), Object::Bool(right)) => left == right,
+            (Object::Null, Object::Null) => true,
+            (Object::Array(left), Object::Array(right)) => left == right,
+            (Object::Hash(left), Object::Hash(right)) => left == right,
+            (
+                Object::Function {
+                    parameters,
+                    body,
+                    env,
+                },
+                Object::Function {
+                    parameters: other_parameters,
+                    body: other_body,
+                    env: other_env,
+                },
+            ) => parameters == other_parameters && body == other_body && Rc::ptr_eq(env, other_env),
+            (Object::Builtin(left), Object::Builtin(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Object {}
+
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Object::Integer(val) => write!(f, "{}", val),
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(val) => write!(f, "{}", val),
             Object::String(val) => write!(f, "{}", val),
+            Object::Char(val) => write!(f, "{}", val),
             Object::Bool(val) => write!(f, "{}", val),
-            Object::Return(val) => write!(f, "{}", val),
             Object::Null => write!(f, "null"),
-            Object::Return(val) => write!(f, "{}", val),
+            Object::Array(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|obj| format!("{}", obj))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Object::Hash(pairs) => {
+                // sorted by key so the output is stable
+                let mut pairs = pairs.iter().collect::<Vec<_>>();
+                pairs.sort_by_key(|(key, _)| *key);
+                write!(
+                    f,
+                    "{{{}}}",
+                    pairs
+                        .iter()
+                        .map(|(key, value)| format!("{}: {}", key, value))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
+            Object::Builtin(builtin) => write!(f, "builtin {}", builtin.name),
             Object::Function {
                 parameters, body, ..
             } => {
@@ -40,7 +269,7 @@ impl fmt::Display for Object {
                     "fn({}){{{}}}",
                     parameters
                         .iter()
-                        .map(|expr| format!("{}", expr))
+                        .map(|param| format!("{}", param))
                         .collect::<Vec<_>>()
                         .join(","),
                     body
@@ -50,7 +279,248 @@ impl fmt::Display for Object {
     }
 }
 
+/// Limits for `Object::inspect_limited`. The default has none.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct InspectOptions {
+    /// containers nested deeper than this are shown as `[...]` or `{...}`
+    pub max_depth: usize,
+    /// elements shown per container before the rest is elided as `...`
+    pub max_width: usize,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        InspectOptions {
+            max_depth: usize::MAX,
+            max_width: usize::MAX,
+        }
+    }
+}
+
+/// chars of a function body shown by `Object::inspect`
+const FUNCTION_PREVIEW_LEN: usize = 24;
+
+fn inspect_items(items: impl ExactSizeIterator<Item = String>, opts: &InspectOptions) -> String {
+    let len = items.len();
+    let mut shown = items.take(opts.max_width).collect::<Vec<_>>();
+    if len > opts.max_width {
+        shown.push("...".to_string());
+    }
+    shown.join(", ")
+}
+
+/// every pair is compared, so a function anywhere gives `None` no matter
+/// the (hash) order the pairs come in
+fn all_equal<'a>(mut pairs: impl Iterator<Item = (&'a Object, &'a Object)>) -> Option<bool> {
+    pairs.try_fold(true, |all, (left, right)| Some(left.equals(right)? && all))
+}
+
+fn conversion_failed(obj: Object, target: &'static str) -> MonkeyError {
+    MonkeyError::ConversionFailed {
+        value: obj.to_string(),
+        object_type: obj.object_type(),
+        target,
+    }
+}
+
+impl TryFrom<Object> for i64 {
+    type Error = MonkeyError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::Integer(int) => Ok(int),
+            obj => Err(conversion_failed(obj, "i64")),
+        }
+    }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = MonkeyError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::Bool(bool) => Ok(bool),
+            obj => Err(conversion_failed(obj, "bool")),
+        }
+    }
+}
+
+impl TryFrom<Object> for String {
+    type Error = MonkeyError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::String(str) => Ok(str),
+            obj => Err(conversion_failed(obj, "String")),
+        }
+    }
+}
+
+impl TryFrom<Object> for Vec<Object> {
+    type Error = MonkeyError;
+
+    fn try_from(obj: Object) -> Result<Self, Self::Error> {
+        match obj {
+            Object::Array(elements) => Ok(elements),
+            obj => Err(conversion_failed(obj, "Vec<Object>")),
+        }
+    }
+}
+
 impl Object {
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            Object::Integer(_) => ObjectType::Integer,
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(_) => ObjectType::Integer,
+            Object::String(_) => ObjectType::String,
+            Object::Char(_) => ObjectType::Char,
+            Object::Bool(_) => ObjectType::Bool,
+            Object::Null => ObjectType::Null,
+            Object::Array(_) => ObjectType::Array,
+            Object::Hash(_) => ObjectType::Hash,
+            Object::Function { .. } | Object::Builtin(_) => ObjectType::Function,
+        }
+    }
+
+    /// The one ordering shared by every comparison: integers compare
+    /// numerically, strings lexicographically by char and chars by code
+    /// point. There is no case folding or locale, so `"Z" < "a"` and
+    /// `"é" > "z"`. Any other pair,
+    /// including values of different types, is unordered and gives `None`.
+    pub fn compare(&self, other: &Object) -> Option<Ordering> {
+        match (self, other) {
+            (Object::Integer(left), Object::Integer(right)) => Some(left.cmp(right)),
+            #[cfg(feature = "bigint")]
+            (Object::BigInteger(left), Object::BigInteger(right)) => Some(left.cmp(right)),
+            #[cfg(feature = "bigint")]
+            (Object::Integer(left), Object::BigInteger(right)) => {
+                Some(num_bigint::BigInt::from(*left).cmp(right))
+            }
+            #[cfg(feature = "bigint")]
+            (Object::BigInteger(left), Object::Integer(right)) => {
+                Some(left.cmp(&num_bigint::BigInt::from(*right)))
+            }
+            (Object::String(left), Object::String(right)) => Some(left.cmp(right)),
+            (Object::Char(left), Object::Char(right)) => Some(left.cmp(right)),
+            _ => None,
+        }
+    }
+
+    /// Equality behind `==` on arrays and hashes: values of one type
+    /// compare by value, arrays element by element and hashes by key set
+    /// and then value by value, whatever their insertion order. Values of
+    /// different types, inside a container, are simply unequal.
+    ///
+    /// Functions have no equality, so comparing two of them gives `None`,
+    /// also inside containers unless their lengths or key sets already
+    /// differ. A function and any other value are unequal.
+    pub fn equals(&self, other: &Object) -> Option<bool> {
+        match (self, other) {
+            (Object::Array(left), Object::Array(right)) if left.len() == right.len() => {
+                all_equal(left.iter().zip(right))
+            }
+            (Object::Hash(left), Object::Hash(right))
+                if left.len() == right.len() && left.keys().all(|key| right.contains_key(key)) =>
+            {
+                all_equal(left.iter().map(|(key, value)| (value, &right[key])))
+            }
+            (Object::Array(_), Object::Array(_)) | (Object::Hash(_), Object::Hash(_)) => {
+                Some(false)
+            }
+            (Object::Function { .. }, Object::Function { .. }) => None,
+            (left, right) => Some(left == right),
+        }
+    }
+
+    /// Key under which this value is stored in a hash. Equal values give
+    /// equal keys; only integers, booleans, strings and chars are hashable.
+    pub fn hash_key(&self) -> Result<HashKey, MonkeyError> {
+        match self {
+            Object::Integer(val) => Ok(HashKey::Integer(*val)),
+            // one key per value, however the host built the integer
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(val) => Ok(match i64::try_from(val) {
+                Ok(int) => HashKey::Integer(int),
+                Err(_) => HashKey::BigInteger(val.clone()),
+            }),
+            Object::Bool(val) => Ok(HashKey::Bool(*val)),
+            Object::String(val) => Ok(HashKey::String(val.to_owned())),
+            Object::Char(val) => Ok(HashKey::Char(*val)),
+            obj => Err(MonkeyError::UnusableAsHashKey(obj.object_type())),
+        }
+    }
+
+    /// Unambiguous form for tools such as the REPL: strings and chars are
+    /// quoted and escaped, and functions show a preview of their body.
+    /// `Display` stays the plain form meant for output.
+    pub fn inspect(&self) -> String {
+        self.inspect_limited(InspectOptions::default())
+    }
+
+    /// `inspect`, cutting containers off past `opts.max_depth` levels and
+    /// `opts.max_width` elements.
+    pub fn inspect_limited(&self, opts: InspectOptions) -> String {
+        self.inspect_at(&opts, 0)
+    }
+
+    fn inspect_at(&self, opts: &InspectOptions, depth: usize) -> String {
+        match self {
+            Object::String(val) => format!("{:?}", val),
+            Object::Char(val) => format!("{:?}", val),
+            Object::Array(elements) if elements.is_empty() => "[]".to_string(),
+            Object::Array(_) if depth >= opts.max_depth => "[...]".to_string(),
+            Object::Array(elements) => format!(
+                "[{}]",
+                inspect_items(
+                    elements.iter().map(|obj| obj.inspect_at(opts, depth + 1)),
+                    opts
+                )
+            ),
+            Object::Hash(pairs) if pairs.is_empty() => "{}".to_string(),
+            Object::Hash(_) if depth >= opts.max_depth => "{...}".to_string(),
+            Object::Hash(pairs) => {
+                let mut pairs = pairs.iter().collect::<Vec<_>>();
+                pairs.sort_by_key(|(key, _)| *key);
+                format!(
+                    "{{{}}}",
+                    inspect_items(
+                        pairs.iter().map(|(key, value)| format!(
+                            "{}: {}",
+                            key.inspect(),
+                            value.inspect_at(opts, depth + 1)
+                        )),
+                        opts
+                    )
+                )
+            }
+            Object::Function {
+                parameters, body, ..
+            } => {
+                let body = body.to_string();
+                let preview = if body.chars().count() > FUNCTION_PREVIEW_LEN {
+                    let mut preview = body.chars().take(FUNCTION_PREVIEW_LEN).collect::<String>();
+                    preview.push_str("...");
+                    preview
+                } else {
+                    body
+                };
+                format!(
+                    "fn({}) {{ {} }}",
+                    parameters
+                        .iter()
+                        .map(|param| format!("{}", param))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    preview
+                )
+            }
+            obj => obj.to_string(),
+        }
+    }
+
+    /// Permissive truthiness: only `false` and `null` are falsy. Empty
+    /// strings are deliberately truthy, as in the book.
     pub fn is_truthy(&self) -> bool {
         match self {
             Object::Null => false,
@@ -58,4 +528,16 @@ impl Object {
             _ => true,
         }
     }
+
+    /// Chars of a string, elements of an array or pairs of a hash; `None`
+    /// for the others, which are not containers. `len` and `is_empty` are
+    /// this.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Object::String(string) => Some(string.chars().count()),
+            Object::Array(elements) => Some(elements.len()),
+            Object::Hash(pairs) => Some(pairs.len()),
+            _ => None,
+        }
+    }
 }
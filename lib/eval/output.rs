@@ -0,0 +1,36 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+};
+
+/// Host sink for what `puts` prints or `dbg` traces, shared with the
+/// evaluators of function bodies.
+#[derive(Clone)]
+pub(crate) struct Output(Rc<RefCell<dyn Write>>);
+
+impl Output {
+    pub(crate) fn new(output: Rc<RefCell<dyn Write>>) -> Self {
+        Output(output)
+    }
+
+    pub(crate) fn write(&self, text: &str) -> io::Result<()> {
+        self.0.borrow_mut().write_all(text.as_bytes())
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output")
+    }
+}
+
+/// outputs are equal only when they are the same installed sink
+impl PartialEq for Output {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Output {}
@@ -4,11 +4,12 @@ use crate::lexer::token::*;
 #[derive(Debug, Clone)]
 pub struct Lexer {
     input: String,
-    /// current
+    /// byte offset of `ch`
     position: usize,
-    /// next
+    /// byte offset of the char after `ch`
     read_position: usize,
-    ch: u8,
+    /// `'\0'` at the end of input
+    ch: char,
 }
 
 impl Lexer {
@@ -17,43 +18,78 @@ impl Lexer {
             input,
             position: 0,
             read_position: 0,
-            ch: 0,
+            ch: '\0',
         };
         l.read_char();
         l
     }
 
+    /// All remaining tokens, ending with `Token::EOF`.
+    pub fn tokenize(mut self) -> Vec<Token> {
+        let mut tokens = vec![];
+        loop {
+            let tok = self.next_token();
+            let is_eof = tok == Token::EOF;
+            tokens.push(tok);
+            if is_eof {
+                return tokens;
+            }
+        }
+    }
+
+    /// Advance by one char, so multibyte text is never split.
     fn read_char(&mut self) {
-        let is_eof = self.read_position >= self.input.len();
-        if is_eof {
-            self.ch = 0
-        } else {
-            self.ch = self.input.as_bytes()[self.read_position]
-        };
         self.position = self.read_position;
-        self.read_position += 1;
+        match self.char_at(self.read_position) {
+            Some(ch) => {
+                self.ch = ch;
+                self.read_position += ch.len_utf8();
+            }
+            None => {
+                self.ch = '\0';
+                self.read_position += 1;
+            }
+        }
+    }
+
+    fn char_at(&self, position: usize) -> Option<char> {
+        self.input.get(position..)?.chars().next()
+    }
+
+    /// identifiers start with a letter in any script or `_`
+    fn is_letter(&mut self, ch: char) -> bool {
+        ch.is_alphabetic() || ch == '_'
     }
 
-    fn is_letter(&mut self, ch: u8) -> bool {
-        (b'a'..=b'z').contains(&ch) || (b'A'..=b'Z').contains(&ch) || ch == b'_'
+    fn is_digit(&mut self, ch: char) -> bool {
+        ch.is_ascii_digit()
     }
 
-    fn is_digit(&mut self, ch: u8) -> bool {
-        (b'0'..=b'9').contains(&ch)
+    /// `_` may separate digits, as in `1_000_000`, but not end the literal
+    /// or appear twice in a row.
+    fn read_int(&mut self) -> Token {
+        let literal = self.read_number();
+        if literal.ends_with('_') || literal.contains("__") {
+            return Token::MalformedNumber(literal);
+        }
+        let int = literal.replace('_', "");
+        match int.parse::<i64>() {
+            Ok(int) => Token::IntLiteral(int),
+            Err(_) => Token::BigIntLiteral(int),
+        }
     }
 
-    fn read_int(&mut self) -> i64 {
+    fn read_number(&mut self) -> String {
         let position = self.position;
-        while self.is_digit(self.ch) {
+        while self.is_digit(self.ch) || self.ch == '_' {
             self.read_char()
         }
-        let int = &self.input[position..self.position];
-        int.parse::<i64>().unwrap()
+        self.input[position..self.position].to_string()
     }
 
     fn read_identifier(&mut self) -> String {
         let position = self.position;
-        while self.is_letter(self.ch) {
+        while self.is_letter(self.ch) || self.is_digit(self.ch) {
             self.read_char()
         }
         self.input[position..self.position].to_string()
@@ -63,16 +99,58 @@ impl Lexer {
         let position = self.position + 1;
         loop {
             self.read_char();
-            if self.ch == b'"' || self.ch == 0 {
-                break
+            if self.ch == '"' || self.ch == '\0' {
+                break;
             }
         }
         let string = self.input[position..self.position].to_string();
         Token::StringLiteral(string)
     }
 
+    /// `'a'`; like strings, no escapes. `''`, `'ab'` and an unterminated
+    /// `'a` are malformed.
+    fn read_char_literal(&mut self) -> Token {
+        let position = self.position + 1;
+        loop {
+            self.read_char();
+            if self.ch == '\'' || self.ch == '\0' {
+                break;
+            }
+        }
+        let literal = &self.input[position..self.position];
+        let mut chars = literal.chars();
+        match (self.ch, chars.next(), chars.next()) {
+            ('\'', Some(ch), None) => Token::CharLiteral(ch),
+            _ => Token::MalformedChar(literal.to_string()),
+        }
+    }
+
+    /// `//` starts a line comment, `///` a doc comment attached to the next
+    /// `let`; four or more slashes, as in a `////` banner, are a plain comment.
+    /// Runs of plain comment lines are skipped in a loop, however long.
+    fn read_comment(&mut self) -> Token {
+        loop {
+            let position = self.position;
+            while self.ch != '\n' && self.ch != '\0' {
+                self.read_char()
+            }
+            let comment = &self.input[position..self.position];
+            match comment.strip_prefix("///") {
+                Some(doc) if !doc.starts_with('/') => {
+                    let doc = doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string();
+                    return Token::DocComment(doc);
+                }
+                _ => {}
+            }
+            self.skip_whitespace();
+            if self.ch != '/' || self.peek_char() != '/' {
+                return self.next_token();
+            }
+        }
+    }
+
     fn skip_whitespace(&mut self) {
-        while let b' ' | b'\t' | b'\n' | b'\r' = self.ch {
+        while let ' ' | '\t' | '\n' | '\r' = self.ch {
             self.read_char()
         }
     }
@@ -81,52 +159,156 @@ impl Lexer {
         self.skip_whitespace();
 
         let tok = match self.ch {
-            b'=' => {
-                if let b'=' = self.peek_char() {
+            '=' => match self.peek_char() {
+                '=' => {
                     self.read_char();
                     Token::Eq
+                }
+                '>' => {
+                    self.read_char();
+                    Token::FatArrow
+                }
+                _ => Token::Assign,
+            },
+            '+' => {
+                if let '=' = self.peek_char() {
+                    self.read_char();
+                    Token::PlusAssign
                 } else {
-                    Token::Assign
+                    Token::Plus
                 }
             }
-            b'+' => Token::Plus,
-            b'-' => Token::Minus,
-            b'!' => {
-                if let b'=' = self.peek_char() {
+            '-' => {
+                if let '=' = self.peek_char() {
+                    self.read_char();
+                    Token::MinusAssign
+                } else {
+                    Token::Minus
+                }
+            }
+            '!' => {
+                if let '=' = self.peek_char() {
                     self.read_char();
                     Token::NotEq
                 } else {
                     Token::Bang
                 }
             }
-            b'/' => Token::Slash,
-            b'*' => Token::Asterisk,
-            b'<' => Token::LT,
-            b'>' => Token::GT,
-            b';' => Token::SemiColon,
-            b',' => Token::Comma,
-            b'(' => Token::LParen,
-            b')' => Token::RParen,
-            b'{' => Token::LBrace,
-            b'}' => Token::RBrace,
-            b'"' => self.read_string(),
-            0 => Token::EOF,
+            '/' => match self.peek_char() {
+                '/' => return self.read_comment(),
+                '=' => {
+                    self.read_char();
+                    Token::SlashAssign
+                }
+                _ => Token::Slash,
+            },
+            '*' => match self.peek_char() {
+                '*' => {
+                    self.read_char();
+                    Token::Pow
+                }
+                '=' => {
+                    self.read_char();
+                    Token::AsteriskAssign
+                }
+                _ => Token::Asterisk,
+            },
+            '%' => Token::Percent,
+            '<' => {
+                if let '=' = self.peek_char() {
+                    self.read_char();
+                    Token::LTEq
+                } else {
+                    Token::LT
+                }
+            }
+            '>' => {
+                if let '=' = self.peek_char() {
+                    self.read_char();
+                    Token::GTEq
+                } else {
+                    Token::GT
+                }
+            }
+            ':' => Token::Colon,
+            // `c?[x]:y` is a safe index, so a ternary choosing an array
+            // literal needs a space after its `?`, as `c ? [x] : y`
+            '?' => match self.peek_char() {
+                '?' => {
+                    self.read_char();
+                    Token::QuestionQuestion
+                }
+                '[' => {
+                    self.read_char();
+                    Token::QuestionBracket
+                }
+                '.' => {
+                    self.read_char();
+                    Token::QuestionDot
+                }
+                _ => Token::Question,
+            },
+            '|' => {
+                if let '>' = self.peek_char() {
+                    self.read_char();
+                    Token::Pipe
+                } else {
+                    Token::Illegal
+                }
+            }
+            '.' => {
+                if let '.' = self.peek_char() {
+                    self.read_char();
+                    if let '.' = self.peek_char() {
+                        self.read_char();
+                        Token::Ellipsis
+                    } else {
+                        Token::DotDot
+                    }
+                } else {
+                    Token::Illegal
+                }
+            }
+            ';' => Token::SemiColon,
+            ',' => Token::Comma,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '"' => self.read_string(),
+            '\'' => self.read_char_literal(),
+            '\0' => Token::EOF,
             _ => {
                 if self.is_letter(self.ch) {
                     let literal = self.read_identifier();
+                    // `_1` is a number with a leading separator, not an identifier
+                    if literal.chars().all(|ch| ch == '_' || ch.is_ascii_digit())
+                        && literal.chars().any(|ch| ch.is_ascii_digit())
+                    {
+                        return Token::MalformedNumber(literal);
+                    }
                     return match literal.as_str() {
                         "fn" => Token::Function,
                         "let" => Token::Let,
                         "if" => Token::If,
                         "else" => Token::Else,
+                        "loop" => Token::Loop,
+                        "break" => Token::Break,
+                        "continue" => Token::Continue,
+                        "for" => Token::For,
+                        "match" => Token::Match,
+                        "try" => Token::Try,
+                        "catch" => Token::Catch,
                         "return" => Token::Return,
+                        "in" => Token::In,
                         "true" => Token::True,
                         "false" => Token::False,
                         _ => Token::Identifier(literal),
                     };
                 } else if self.is_digit(self.ch) {
-                    let int = self.read_int();
-                    return Token::IntLiteral(int);
+                    return self.read_int();
                 }
                 Token::Illegal
             }
@@ -136,14 +318,9 @@ impl Lexer {
     }
 
     /// want to peek only. pre-read
-    fn peek_char(&mut self) -> u8 {
-        if self.read_position >= self.input.len() {
-            0
-        } else {
-            self.input.as_bytes()[self.read_position]
-        }
+    fn peek_char(&mut self) -> char {
+        self.char_at(self.read_position).unwrap_or('\0')
     }
-
 }
 
 #[cfg(test)]
@@ -163,6 +340,11 @@ mod tests {
         5 < 10 > 5;
         "foobar"
         "foo bar"
+        loop { continue; break 1; }
+        [1, 2];
+        {"one": 1}
+        9223372036854775807
+        9223372036854775808
         "#;
         let expected_tokens = vec![
             Token::Let,
@@ -215,6 +397,27 @@ mod tests {
             Token::SemiColon,
             Token::StringLiteral(String::from("foobar")),
             Token::StringLiteral(String::from("foo bar")),
+            Token::Loop,
+            Token::LBrace,
+            Token::Continue,
+            Token::SemiColon,
+            Token::Break,
+            Token::IntLiteral(1),
+            Token::SemiColon,
+            Token::RBrace,
+            Token::LBracket,
+            Token::IntLiteral(1),
+            Token::Comma,
+            Token::IntLiteral(2),
+            Token::RBracket,
+            Token::SemiColon,
+            Token::LBrace,
+            Token::StringLiteral(String::from("one")),
+            Token::Colon,
+            Token::IntLiteral(1),
+            Token::RBrace,
+            Token::IntLiteral(9223372036854775807),
+            Token::BigIntLiteral(String::from("9223372036854775808")),
             Token::EOF,
         ];
 
@@ -229,4 +432,343 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn digit_separators() {
+        let tests = [
+            ("1_000", Token::IntLiteral(1000)),
+            ("1_0_0", Token::IntLiteral(100)),
+            (
+                "9_223_372_036_854_775_808",
+                Token::BigIntLiteral(String::from("9223372036854775808")),
+            ),
+            ("_1", Token::MalformedNumber(String::from("_1"))),
+            ("__1_0", Token::MalformedNumber(String::from("__1_0"))),
+            ("1__0", Token::MalformedNumber(String::from("1__0"))),
+            ("1_", Token::MalformedNumber(String::from("1_"))),
+        ];
+        for (input, expected) in tests {
+            let mut l = Lexer::new(input.to_string());
+            assert_eq!(l.next_token(), expected, "{}", input);
+            assert_eq!(l.next_token(), Token::EOF, "{}", input);
+        }
+
+        let mut l = Lexer::new("_ x_ _x1 x_1".to_string());
+        assert_eq!(l.next_token(), Token::Identifier(String::from("_")));
+        assert_eq!(l.next_token(), Token::Identifier(String::from("x_")));
+        assert_eq!(l.next_token(), Token::Identifier(String::from("_x1")));
+        assert_eq!(l.next_token(), Token::Identifier(String::from("x_1")));
+    }
+
+    #[test]
+    fn char_literals() {
+        let tests = [
+            ("'a'", Token::CharLiteral('a')),
+            ("' '", Token::CharLiteral(' ')),
+            ("'\"'", Token::CharLiteral('"')),
+            ("'é'", Token::CharLiteral('é')),
+            ("''", Token::MalformedChar(String::from(""))),
+            ("'ab'", Token::MalformedChar(String::from("ab"))),
+            ("'a", Token::MalformedChar(String::from("a"))),
+        ];
+        for (input, expected) in tests {
+            let mut l = Lexer::new(input.to_string());
+            assert_eq!(l.next_token(), expected, "{}", input);
+            assert_eq!(l.next_token(), Token::EOF, "{}", input);
+        }
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let input = "a <= b >= c < = d<e>f";
+        let expected_tokens = vec![
+            Token::Identifier(String::from("a")),
+            Token::LTEq,
+            Token::Identifier(String::from("b")),
+            Token::GTEq,
+            Token::Identifier(String::from("c")),
+            Token::LT,
+            Token::Assign,
+            Token::Identifier(String::from("d")),
+            Token::LT,
+            Token::Identifier(String::from("e")),
+            Token::GT,
+            Token::Identifier(String::from("f")),
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn coalesce() {
+        let input = "a ?? b ??? c ?";
+        let expected_tokens = vec![
+            Token::Identifier(String::from("a")),
+            Token::QuestionQuestion,
+            Token::Identifier(String::from("b")),
+            Token::QuestionQuestion,
+            Token::Question,
+            Token::Identifier(String::from("c")),
+            Token::Question,
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn safe_navigation() {
+        let input = "a?[0]?.b ? [c] : d";
+        let expected_tokens = vec![
+            Token::Identifier(String::from("a")),
+            Token::QuestionBracket,
+            Token::IntLiteral(0),
+            Token::RBracket,
+            Token::QuestionDot,
+            Token::Identifier(String::from("b")),
+            Token::Question,
+            Token::LBracket,
+            Token::Identifier(String::from("c")),
+            Token::RBracket,
+            Token::Colon,
+            Token::Identifier(String::from("d")),
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn pipe() {
+        let input = "xs |> f(1) | |>> g";
+        let expected_tokens = vec![
+            Token::Identifier(String::from("xs")),
+            Token::Pipe,
+            Token::Identifier(String::from("f")),
+            Token::LParen,
+            Token::IntLiteral(1),
+            Token::RParen,
+            Token::Illegal,
+            Token::Pipe,
+            Token::GT,
+            Token::Identifier(String::from("g")),
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let input = "x += 1; y -= 2 *= 3 /= 4 + =5 **= // comment";
+        let expected_tokens = vec![
+            Token::Identifier(String::from("x")),
+            Token::PlusAssign,
+            Token::IntLiteral(1),
+            Token::SemiColon,
+            Token::Identifier(String::from("y")),
+            Token::MinusAssign,
+            Token::IntLiteral(2),
+            Token::AsteriskAssign,
+            Token::IntLiteral(3),
+            Token::SlashAssign,
+            Token::IntLiteral(4),
+            Token::Plus,
+            Token::Assign,
+            Token::IntLiteral(5),
+            Token::Pow,
+            Token::Assign,
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn try_catch() {
+        let input = "try { error(1) } catch (e) { e } trying";
+        let expected_tokens = vec![
+            Token::Try,
+            Token::LBrace,
+            Token::Identifier(String::from("error")),
+            Token::LParen,
+            Token::IntLiteral(1),
+            Token::RParen,
+            Token::RBrace,
+            Token::Catch,
+            Token::LParen,
+            Token::Identifier(String::from("e")),
+            Token::RParen,
+            Token::LBrace,
+            Token::Identifier(String::from("e")),
+            Token::RBrace,
+            Token::Identifier(String::from("trying")),
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn match_arms() {
+        let input = "match (x) { 1 => a, _ => b } = =>= ==>";
+        let expected_tokens = vec![
+            Token::Match,
+            Token::LParen,
+            Token::Identifier(String::from("x")),
+            Token::RParen,
+            Token::LBrace,
+            Token::IntLiteral(1),
+            Token::FatArrow,
+            Token::Identifier(String::from("a")),
+            Token::Comma,
+            Token::Identifier(String::from("_")),
+            Token::FatArrow,
+            Token::Identifier(String::from("b")),
+            Token::RBrace,
+            Token::Assign,
+            Token::FatArrow,
+            Token::Assign,
+            Token::Eq,
+            Token::GT,
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn power() {
+        let input = "2 ** 3 * 4 *** 5";
+        let expected_tokens = vec![
+            Token::IntLiteral(2),
+            Token::Pow,
+            Token::IntLiteral(3),
+            Token::Asterisk,
+            Token::IntLiteral(4),
+            Token::Pow,
+            Token::Asterisk,
+            Token::IntLiteral(5),
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let input = "0..10 a..b ...rest .... .";
+        let expected_tokens = vec![
+            Token::IntLiteral(0),
+            Token::DotDot,
+            Token::IntLiteral(10),
+            Token::Identifier(String::from("a")),
+            Token::DotDot,
+            Token::Identifier(String::from("b")),
+            Token::Ellipsis,
+            Token::Identifier(String::from("rest")),
+            Token::Ellipsis,
+            Token::Illegal,
+            Token::Illegal,
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+    }
+
+    #[test]
+    fn unicode() {
+        let input = "\"日本語\" \"👍\" 'é' 👍 /// ドキュメント\nx";
+        let expected_tokens = vec![
+            Token::StringLiteral(String::from("日本語")),
+            Token::StringLiteral(String::from("👍")),
+            Token::CharLiteral('é'),
+            // one token per char, not per byte
+            Token::Illegal,
+            Token::DocComment(String::from("ドキュメント")),
+            Token::Identifier(String::from("x")),
+            Token::EOF,
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+
+        for input in ["\"日本", "'👍", "// 日本語", "日本語"] {
+            let tokens = Lexer::new(input.to_string()).tokenize();
+            assert_eq!(tokens.last(), Some(&Token::EOF), "{}", input);
+        }
+    }
+
+    #[test]
+    fn identifiers() {
+        let input = "café 名前 x1 x2y λ ſelf lét iF Let inside In";
+        let expected = [
+            "café", "名前", "x1", "x2y", "λ", "ſelf", "lét", "iF", "Let", "inside", "In",
+        ];
+        let mut l = Lexer::new(input.to_string());
+        for ident in expected {
+            assert_eq!(l.next_token(), Token::Identifier(String::from(ident)));
+        }
+        assert_eq!(l.next_token(), Token::EOF);
+
+        // a leading digit is still a number
+        let mut l = Lexer::new("1x".to_string());
+        assert_eq!(l.next_token(), Token::IntLiteral(1));
+        assert_eq!(l.next_token(), Token::Identifier(String::from("x")));
+    }
+
+    #[test]
+    fn comments() {
+        let input = r#"// not documentation
+        ////////////
+        //// banner
+        /// adds one
+        ///to x
+        let x = 1 / 2; // trailing
+        "#;
+        let expected_tokens = vec![
+            Token::DocComment(String::from("adds one")),
+            Token::DocComment(String::from("to x")),
+            Token::Let,
+            Token::Identifier(String::from("x")),
+            Token::Assign,
+            Token::IntLiteral(1),
+            Token::Slash,
+            Token::IntLiteral(2),
+            Token::SemiColon,
+            Token::EOF,
+        ];
+
+        let mut l = Lexer::new(input.to_string());
+
+        for expected in expected_tokens {
+            assert_eq!(expected, l.next_token())
+        }
+
+        // a long run of comment lines is skipped without recursing per line
+        let input = format!("{}x", "// comment\n".repeat(1_000_000));
+        let mut l = Lexer::new(input);
+        assert_eq!(l.next_token(), Token::Identifier(String::from("x")));
+        assert_eq!(l.next_token(), Token::EOF);
+    }
 }
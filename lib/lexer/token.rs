@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::parser::ast;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -8,10 +10,29 @@ pub enum Token {
     // identifier and literal
     Identifier(String),
     StringLiteral(String),
+    CharLiteral(char),
+    /// quoted text that is not exactly one character, as written
+    MalformedChar(String),
     IntLiteral(i64),
+    /// integer literal too large for i64, kept as its digits
+    BigIntLiteral(String),
+    /// number with misplaced `_` separators, as written
+    MalformedNumber(String),
     BoolLitral(bool),
+    /// /// text
+    DocComment(String),
     /// =
     Assign,
+    /// =>
+    FatArrow,
+    /// +=
+    PlusAssign,
+    /// -=
+    MinusAssign,
+    /// *=
+    AsteriskAssign,
+    /// /=
+    SlashAssign,
     /// +
     Plus,
     /// -
@@ -20,19 +41,41 @@ pub enum Token {
     Bang,
     /// *
     Asterisk,
+    /// **
+    Pow,
+    /// ?
+    Question,
+    /// ??
+    QuestionQuestion,
+    /// ?[
+    QuestionBracket,
+    /// ?.
+    QuestionDot,
+    /// |>
+    Pipe,
     /// /
     Slash,
+    /// %
+    Percent,
 
     /// <
     LT,
     /// >
     GT,
+    /// <=
+    LTEq,
+    /// >=
+    GTEq,
 
     // delimiter
     /// ,
     Comma,
     /// :
     Colon,
+    /// ..
+    DotDot,
+    /// ...
+    Ellipsis,
     /// ;
     SemiColon,
 
@@ -45,6 +88,10 @@ pub enum Token {
     LBrace,
     /// "}"
     RBrace,
+    /// "["
+    LBracket,
+    /// "]"
+    RBracket,
     // reserved
     Function,
     Let,
@@ -53,29 +100,139 @@ pub enum Token {
     False,
     If,
     Else,
+    Loop,
+    Break,
+    Continue,
+    For,
+    Match,
+    Try,
+    Catch,
+    In,
 
     Eq,
     NotEq,
 }
 
+/// source-like rendering used in error messages
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Illegal => write!(f, "illegal token"),
+            Token::EOF => write!(f, "end of input"),
+            Token::Identifier(ident) if ident.is_empty() => write!(f, "identifier"),
+            Token::Identifier(ident) => write!(f, "identifier '{}'", ident),
+            Token::StringLiteral(str) => write!(f, "string \"{}\"", str),
+            Token::CharLiteral(ch) => write!(f, "character '{}'", ch),
+            Token::MalformedChar(literal) => write!(f, "malformed character '{}'", literal),
+            Token::IntLiteral(int) => write!(f, "integer {}", int),
+            Token::BigIntLiteral(int) => write!(f, "integer {}", int),
+            Token::MalformedNumber(literal) => write!(f, "malformed number {}", literal),
+            Token::BoolLitral(bool) => write!(f, "'{}'", bool),
+            Token::DocComment(_) => write!(f, "doc comment"),
+            Token::Assign => write!(f, "'='"),
+            Token::FatArrow => write!(f, "'=>'"),
+            Token::PlusAssign => write!(f, "'+='"),
+            Token::MinusAssign => write!(f, "'-='"),
+            Token::AsteriskAssign => write!(f, "'*='"),
+            Token::SlashAssign => write!(f, "'/='"),
+            Token::Plus => write!(f, "'+'"),
+            Token::Minus => write!(f, "'-'"),
+            Token::Bang => write!(f, "'!'"),
+            Token::Asterisk => write!(f, "'*'"),
+            Token::Pow => write!(f, "'**'"),
+            Token::Question => write!(f, "'?'"),
+            Token::QuestionQuestion => write!(f, "'??'"),
+            Token::QuestionBracket => write!(f, "'?['"),
+            Token::QuestionDot => write!(f, "'?.'"),
+            Token::Pipe => write!(f, "'|>'"),
+            Token::Slash => write!(f, "'/'"),
+            Token::Percent => write!(f, "'%'"),
+            Token::LT => write!(f, "'<'"),
+            Token::GT => write!(f, "'>'"),
+            Token::Comma => write!(f, "','"),
+            Token::Colon => write!(f, "':'"),
+            Token::DotDot => write!(f, "'..'"),
+            Token::Ellipsis => write!(f, "'...'"),
+            Token::SemiColon => write!(f, "';'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::LBrace => write!(f, "'{{'"),
+            Token::RBrace => write!(f, "'}}'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+            Token::Function => write!(f, "'fn'"),
+            Token::Let => write!(f, "'let'"),
+            Token::Return => write!(f, "'return'"),
+            Token::In => write!(f, "'in'"),
+            Token::True => write!(f, "'true'"),
+            Token::False => write!(f, "'false'"),
+            Token::If => write!(f, "'if'"),
+            Token::Else => write!(f, "'else'"),
+            Token::Loop => write!(f, "'loop'"),
+            Token::Break => write!(f, "'break'"),
+            Token::Continue => write!(f, "'continue'"),
+            Token::For => write!(f, "'for'"),
+            Token::Match => write!(f, "'match'"),
+            Token::Try => write!(f, "'try'"),
+            Token::Catch => write!(f, "'catch'"),
+            Token::Eq => write!(f, "'=='"),
+            Token::LTEq => write!(f, "'<='"),
+            Token::GTEq => write!(f, "'>='"),
+            Token::NotEq => write!(f, "'!='"),
+        }
+    }
+}
+
 impl Token {
-    pub fn precedence(&self) -> ast::Precedence {
+    /// Binding power of the token as an infix operator:
+    ///
+    /// | level | precedence    | operators              | associativity |
+    /// |-------|---------------|------------------------|---------------|
+    /// | 0     | `Lowest`      | anything else          |               |
+    /// | 1     | `Ternary`     | `? :`                  | right         |
+    /// | 2     | `Pipe`        | `|>`                   | left          |
+    /// | 3     | `Coalesce`    | `??`                   | right         |
+    /// | 4     | `Range`       | `..`                   | left          |
+    /// | 5     | `Equals`      | `==` `!=`              | left          |
+    /// | 6     | `LessGreater` | `<` `>` `<=` `>=` `in` | left          |
+    /// | 7     | `Sum`         | `+` `-`                | left          |
+    /// | 8     | `Product`     | `*` `/` `%`            | left          |
+    /// | 9     | `Prefix`      | unary `!` `-`          |               |
+    /// | 10    | `Power`       | `**`                   | right         |
+    /// | 11    | `Call`        | `f(x)`                 |               |
+    /// | 12    | `Index`       | `a[i]` `a?[i]` `a?.k`  |               |
+    ///
+    /// A new operator needs a row here, an arm below and in `associativity`,
+    /// and an entry in the parser's pairwise precedence test.
+    pub(crate) fn precedence(&self) -> ast::Precedence {
         match self {
+            Token::Question => ast::Precedence::Ternary,
+            Token::Pipe => ast::Precedence::Pipe,
+            Token::QuestionQuestion => ast::Precedence::Coalesce,
+            Token::DotDot => ast::Precedence::Range,
             Token::Eq => ast::Precedence::Equals,
             Token::NotEq => ast::Precedence::Equals,
             Token::LT => ast::Precedence::LessGreater,
             Token::GT => ast::Precedence::LessGreater,
+            Token::LTEq => ast::Precedence::LessGreater,
+            Token::GTEq => ast::Precedence::LessGreater,
+            Token::In => ast::Precedence::LessGreater,
             Token::Plus => ast::Precedence::Sum,
             Token::Minus => ast::Precedence::Sum,
             Token::Asterisk => ast::Precedence::Product,
             Token::Slash => ast::Precedence::Product,
+            Token::Percent => ast::Precedence::Product,
+            Token::Pow => ast::Precedence::Power,
             Token::LParen => ast::Precedence::Call,
+            Token::LBracket | Token::QuestionBracket | Token::QuestionDot => ast::Precedence::Index,
             _ => ast::Precedence::Lowest,
         }
     }
-}
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub struct Tokens<'a> {
-    pub tok: &'a [Token],
+    pub(crate) fn associativity(&self) -> ast::Associativity {
+        match self {
+            Token::Pow | Token::Question | Token::QuestionQuestion => ast::Associativity::Right,
+            _ => ast::Associativity::Left,
+        }
+    }
 }
@@ -1,4 +1,45 @@
-pub mod error;
-pub mod eval;
-pub mod lexer;
-pub mod parser;
+pub(crate) mod error;
+pub(crate) mod eval;
+pub(crate) mod lexer;
+pub(crate) mod parser;
+pub mod prelude;
+pub(crate) mod repl;
+mod stack;
+
+/// Split `input` into tokens, ending with `Token::EOF`.
+pub fn tokenize(input: &str) -> Vec<lexer::token::Token> {
+    lexer::Lexer::new(input.to_string()).tokenize()
+}
+
+/// Parse `input` into a `Program` without evaluating it. Nesting deeper than
+/// `MAX_NESTING_DEPTH` fails with `NestingTooDeep`; below that, parsing is
+/// safe on any thread, as it grows its own stack when it runs low.
+pub fn parse(input: &str) -> Result<parser::ast::Program, error::MonkeyError> {
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = parser::Parser::new(l);
+    p.parse_program()
+}
+
+/// Lex, parse and evaluate `input` in a fresh `Evaluator`.
+pub fn interpret(input: &str) -> Result<eval::object::Object, error::MonkeyError> {
+    eval::Evaluator::new().eval_source(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_top_level_api() {
+        assert_eq!(
+            tokenize("let x"),
+            vec![Token::Let, Token::Identifier("x".to_string()), Token::EOF]
+        );
+        assert_eq!(parse("1; 2;").unwrap().statements.len(), 2);
+        assert_eq!(interpret("1 + 2"), Ok(Object::Integer(3)));
+
+        let mut eval = Evaluator::new();
+        eval.set("x".to_string(), Object::Integer(2));
+        assert_eq!(eval.eval_source("x * 21"), Ok(Object::Integer(42)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, rc::Rc};
 
 /// Root Node for AST
 #[derive(Debug, Clone)]
@@ -27,40 +27,78 @@ impl fmt::Display for Program {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
-pub struct Node {}
-
-impl Node {
-    /// for debug
-    pub fn token_literal() {}
-}
-
 #[derive(Debug, PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
 pub enum Statement {
     Let {
         /// In Monkeylang, Identifier generates value.
         identifier: Expression,
         value: Expression,
+        /// `///` lines preceding the statement, joined by newlines.
+        doc: Option<String>,
     },
     Return(Expression),
+    /// `break;` or `break value;`
+    Break(Option<Expression>),
+    /// `continue;`, skips to the next iteration of the innermost loop
+    Continue,
     Expression(Expression),
     Block(Vec<Statement>),
+    /// for ($binding in $iterable) { $body }
+    For {
+        binding: String,
+        iterable: Expression,
+        body: Box<Statement>,
+    },
+    /// `$target = $value;`, or `$target op= $value;` with an operator, on an
+    /// existing binding; `target` is an identifier or an index expression into one
+    Assign {
+        target: Expression,
+        operator: Option<Infix>,
+        value: Expression,
+    },
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Statement::Let { identifier, value } => {
-                write!(f, "let {:?} = {:?};", identifier, value)
+            Statement::Let {
+                identifier,
+                value,
+                doc,
+            } => {
+                if let Some(doc) = doc {
+                    for line in doc.lines() {
+                        writeln!(f, "/// {}", line)?;
+                    }
+                }
+                write!(f, "let {} = {};", identifier, value)
             }
-            Statement::Return(value) => write!(f, "return {:?};", value),
-            Statement::Expression(value) => write!(f, "{:?};", value),
+            Statement::Return(value) => write!(f, "return {};", value),
+            Statement::Break(Some(value)) => write!(f, "break {};", value),
+            Statement::Break(None) => write!(f, "break;"),
+            Statement::Continue => write!(f, "continue;"),
+            Statement::Expression(value) => write!(f, "{};", value),
             Statement::Block(statements) => {
                 for stmt in statements.iter() {
                     write!(f, "{}", stmt)?
                 }
                 Ok(())
             }
+            Statement::For {
+                binding,
+                iterable,
+                body,
+            } => write!(f, "for({} in {}){{{}}}", binding, iterable, body),
+            Statement::Assign {
+                target,
+                operator: Some(operator),
+                value,
+            } => write!(f, "{} {}= {};", target, operator, value),
+            Statement::Assign {
+                target,
+                operator: None,
+                value,
+            } => write!(f, "{} = {};", target, value),
         }
     }
 }
@@ -69,7 +107,10 @@ impl fmt::Display for Statement {
 pub enum Expression {
     Identifier(String),
     String(String),
+    Char(char),
     Integer(i64),
+    /// decimal digits of a literal that does not fit in i64
+    BigInteger(String),
     Prefix {
         operator: Prefix,
         right: Box<Expression>,
@@ -80,6 +121,10 @@ pub enum Expression {
         right: Box<Expression>,
     },
     Boolean(bool),
+    /// [1, 2 * 2, "three"]
+    Array(Vec<Expression>),
+    /// {"name": "monkey", 1: true}, pairs in source order
+    Hash(Vec<(Expression, Expression)>),
     /// if ($condition) {
     ///     $consequence
     /// } else {
@@ -90,14 +135,159 @@ pub enum Expression {
         consequence: Box<Statement>,
         alternative: Option<Box<Statement>>,
     },
-    Function {
-        parameters: Vec<Expression>,
+    /// $condition ? $consequence : $alternative
+    Ternary {
+        condition: Box<Expression>,
+        consequence: Box<Expression>,
+        alternative: Box<Expression>,
+    },
+    /// match ($subject) { $pattern => $body, ..., _ => $default }, where each
+    /// body is an expression or a block
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<(Pattern, Statement)>,
+        default: Option<Box<Statement>>,
+    },
+    /// try { $body } catch ($binding) { $handler }, the handler run with the
+    /// message of an error raised in the body bound to `binding`
+    Try {
+        body: Box<Statement>,
+        binding: String,
+        handler: Box<Statement>,
+    },
+    /// loop { $body }, evaluates to the value given to `break`
+    Loop {
         body: Box<Statement>,
     },
+    /// the body is shared with the function objects evaluating it gives
+    Function {
+        parameters: Vec<Parameter>,
+        body: Rc<Statement>,
+    },
     Call {
         function: Box<Expression>, // Identifier or Function
         arguments: Vec<Expression>,
     },
+    /// $left[$index]
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// $left?[$index] or $left?.$key, null when $left is null
+    SafeIndex {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// $left[$start:$end], either bound may be left out
+    Slice {
+        left: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
+    /// $start..$end, end exclusive
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+    },
+}
+
+/// A chain is dropped link by link in a loop, as it was built, rather than
+/// by the recursion a derived drop would do down its left side.
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut next = self.take_chain_left();
+        while let Some(mut link) = next {
+            next = link.take_chain_left();
+        }
+    }
+}
+
+impl Expression {
+    /// The left side of an infix operator, call, index, slice or range.
+    /// The parser chains these in a loop, so a tree of them can lean left
+    /// arbitrarily deep; walk it with this rather than recursing.
+    pub(crate) fn chain_left(&self) -> Option<&Expression> {
+        match self {
+            Expression::Infix { left, .. }
+            | Expression::Index { left, .. }
+            | Expression::SafeIndex { left, .. }
+            | Expression::Slice { left, .. } => Some(left),
+            Expression::Call { function, .. } => Some(function),
+            Expression::Range { start, .. } => Some(start),
+            _ => None,
+        }
+    }
+
+    /// `chain_left`, taken out and replaced by a leaf
+    fn take_chain_left(&mut self) -> Option<Box<Expression>> {
+        match self {
+            Expression::Infix { left, .. }
+            | Expression::Index { left, .. }
+            | Expression::SafeIndex { left, .. }
+            | Expression::Slice { left, .. }
+            | Expression::Call { function: left, .. }
+            | Expression::Range { start: left, .. } => Some(std::mem::replace(
+                left,
+                Box::new(Expression::Boolean(false)),
+            )),
+            _ => None,
+        }
+    }
+
+    /// every link's opening parenthesis, outermost first, the innermost
+    /// left side, then the rest of each link from the innermost out
+    fn fmt_chain(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut links = vec![];
+        let mut root = self;
+        while let Some(left) = root.chain_left() {
+            links.push(root);
+            root = left;
+        }
+        for link in &links {
+            if !matches!(link, Expression::Call { .. }) {
+                write!(f, "(")?;
+            }
+        }
+        write!(f, "{}", root)?;
+        for link in links.into_iter().rev() {
+            match link {
+                // a word, so spaced off the operands it would run into
+                Expression::Infix {
+                    operator: Infix::In,
+                    right,
+                    ..
+                } => write!(f, " {} {})", Infix::In, right)?,
+                Expression::Infix {
+                    operator, right, ..
+                } => write!(f, "{}{})", operator, right)?,
+                Expression::Call { arguments, .. } => write!(
+                    f,
+                    "({})",
+                    arguments
+                        .iter()
+                        .map(|expr| format!("{}", &expr))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                )?,
+                Expression::Index { index, .. } => write!(f, "[{}])", index)?,
+                Expression::SafeIndex { index, .. } => write!(f, "?[{}])", index)?,
+                Expression::Slice { start, end, .. } => {
+                    write!(f, "[")?;
+                    if let Some(start) = start {
+                        write!(f, "{}", start)?;
+                    }
+                    write!(f, ":")?;
+                    if let Some(end) = end {
+                        write!(f, "{}", end)?;
+                    }
+                    write!(f, "])")?
+                }
+                Expression::Range { end, .. } => write!(f, "..{})", end)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for Expression {
@@ -105,14 +295,35 @@ impl fmt::Display for Expression {
         match self {
             Expression::Identifier(value) => write!(f, "{}", &value),
             Expression::String(value) => write!(f, "{}", &value),
+            Expression::Char(value) => write!(f, "'{}'", value),
             Expression::Integer(value) => write!(f, "{}", value),
+            Expression::BigInteger(value) => write!(f, "{}", value),
             Expression::Prefix { operator, right } => write!(f, "({}{})", operator, right),
-            Expression::Infix {
-                operator,
-                left,
-                right,
-            } => write!(f, "({}{}{})", left, operator, right),
+            Expression::Infix { .. }
+            | Expression::Call { .. }
+            | Expression::Index { .. }
+            | Expression::SafeIndex { .. }
+            | Expression::Slice { .. }
+            | Expression::Range { .. } => self.fmt_chain(f),
             Expression::Boolean(value) => write!(f, "{}", value),
+            Expression::Array(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|expr| format!("{}", &expr))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            Expression::Hash(pairs) => write!(
+                f,
+                "{{{}}}",
+                pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", key, value))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
             Expression::If {
                 condition,
                 consequence,
@@ -125,39 +336,108 @@ impl fmt::Display for Expression {
                 ),
                 None => write!(f, "if({}){{{}}}", condition, consequence),
             },
+            Expression::Ternary {
+                condition,
+                consequence,
+                alternative,
+            } => write!(f, "({}?{}:{})", condition, consequence, alternative),
+            Expression::Match {
+                subject,
+                arms,
+                default,
+            } => {
+                let mut arms = arms
+                    .iter()
+                    .map(|(pattern, body)| format!("{}=>{}", pattern, body))
+                    .collect::<Vec<_>>();
+                if let Some(default) = default {
+                    arms.push(format!("_=>{}", default));
+                }
+                write!(f, "match({}){{{}}}", subject, arms.join(","))
+            }
+            Expression::Try {
+                body,
+                binding,
+                handler,
+            } => write!(f, "try{{{}}}catch({}){{{}}}", body, binding, handler),
+            Expression::Loop { body } => write!(f, "loop{{{}}}", body),
             Expression::Function { parameters, body } => {
                 write!(
                     f,
                     "fn({}){{{}}}",
                     parameters
                         .iter()
-                        .map(|expr| -> &str {
-                            match expr {
-                                Expression::Identifier(ident) => ident,
-                                _ => unreachable!(),
-                            }
-                        })
+                        .map(|param| format!("{}", param))
                         .collect::<Vec<_>>()
                         .join(","),
                     body
                 )
             }
-            Expression::Call {
-                function,
-                arguments,
-            } => {
-                write!(
-                    f,
-                    "{}({})",
-                    function,
-                    arguments
-                        .iter()
-                        .map(|expr| format!("{}", &expr))
-                        .collect::<Vec<_>>()
-                        .join(",")
-                )
+        }
+    }
+}
+
+/// What the subject of a `match` is tried against by an arm.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+pub enum Pattern {
+    /// a value `==` to the subject
+    Value(Expression),
+    /// inside an array or hash pattern, a name bound to what is there; `_`
+    /// binds nothing
+    Binding(String),
+    /// [$pattern, ..., ...$rest], an array of exactly as many elements, or
+    /// of at least as many with the others bound to `rest`
+    Array {
+        elements: Vec<Pattern>,
+        rest: Option<String>,
+    },
+    /// {$key: $pattern, ...}, a hash with at least these keys
+    Hash(Vec<(Expression, Pattern)>),
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Value(expr) => write!(f, "{}", expr),
+            Pattern::Binding(name) => write!(f, "{}", name),
+            Pattern::Array { elements, rest } => {
+                let mut elements = elements
+                    .iter()
+                    .map(|pattern| format!("{}", pattern))
+                    .collect::<Vec<_>>();
+                if let Some(rest) = rest {
+                    elements.push(format!("...{}", rest));
+                }
+                write!(f, "[{}]", elements.join(","))
             }
+            Pattern::Hash(pairs) => write!(
+                f,
+                "{{{}}}",
+                pairs
+                    .iter()
+                    .map(|(key, pattern)| format!("{}:{}", key, pattern))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+/// A name in a function literal's parameter list.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Ord, PartialOrd)]
+pub struct Parameter {
+    pub name: String,
+    /// `...name`, bound to an array of the arguments left over after the
+    /// parameters before it; only allowed last
+    pub rest: bool,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rest {
+            write!(f, "...")?;
         }
+        write!(f, "{}", self.name)
     }
 }
 
@@ -182,10 +462,21 @@ pub enum Infix {
     NotEq,
     LT,
     GT,
+    LTEq,
+    GTEq,
     Plus,
     Minus,
     Slash,
     Asterisk,
+    /// remainder, with the sign of the dividend
+    Percent,
+    Pow,
+    /// `$left ?? $right`, the right side evaluated only when the left is null
+    Coalesce,
+    /// element of an array, key of a hash or substring of a string
+    In,
+    /// `$left |> f(a)` calls `f($left, a)`, `$left |> f` calls `f($left)`
+    Pipe,
 }
 
 impl fmt::Display for Infix {
@@ -194,28 +485,77 @@ impl fmt::Display for Infix {
             Infix::Plus => write!(f, "+"),
             Infix::Minus => write!(f, "-"),
             Infix::Asterisk => write!(f, "*"),
+            Infix::Percent => write!(f, "%"),
+            Infix::Pow => write!(f, "**"),
+            Infix::Coalesce => write!(f, "??"),
+            Infix::In => write!(f, "in"),
+            Infix::Pipe => write!(f, "|>"),
             Infix::Slash => write!(f, "/"),
             Infix::Eq => write!(f, "=="),
             Infix::NotEq => write!(f, "!="),
             Infix::LT => write!(f, "<"),
             Infix::GT => write!(f, ">"),
+            Infix::LTEq => write!(f, "<="),
+            Infix::GTEq => write!(f, ">="),
         }
     }
 }
 
+/// Binding power, loosest first. The levels are listed with their
+/// operators at `Token::precedence`.
 #[derive(Debug, PartialEq, Clone, Eq, Hash, Ord, PartialOrd, Copy)]
 pub enum Precedence {
-    Lowest,
+    Lowest = 0,
+    /// a ? b : c
+    Ternary = 1,
+    /// |>
+    Pipe = 2,
+    /// ??
+    Coalesce = 3,
+    /// ..
+    Range = 4,
     /// == or !=
-    Equals,
+    Equals = 5,
     /// > or <
-    LessGreater,
+    LessGreater = 6,
     /// +
-    Sum,
+    Sum = 7,
     /// *
-    Product,
+    Product = 8,
     /// ! or -
-    Prefix,
+    Prefix = 9,
+    /// **, above prefix so `-2 ** 2` is `-(2 ** 2)`
+    Power = 10,
     /// my_function(x)
-    Call,
+    Call = 11,
+    /// array[index]
+    Index = 12,
+}
+
+impl Precedence {
+    /// the next looser level; `Lowest` is its own
+    pub fn below(self) -> Precedence {
+        match self {
+            Precedence::Lowest | Precedence::Ternary => Precedence::Lowest,
+            Precedence::Pipe => Precedence::Ternary,
+            Precedence::Coalesce => Precedence::Pipe,
+            Precedence::Range => Precedence::Coalesce,
+            Precedence::Equals => Precedence::Range,
+            Precedence::LessGreater => Precedence::Equals,
+            Precedence::Sum => Precedence::LessGreater,
+            Precedence::Product => Precedence::Sum,
+            Precedence::Prefix => Precedence::Product,
+            Precedence::Power => Precedence::Prefix,
+            Precedence::Call => Precedence::Power,
+            Precedence::Index => Precedence::Call,
+        }
+    }
+}
+
+/// Which side a chain of operators of one level groups from:
+/// `a - b - c` is `(a - b) - c` because `-` is left-associative.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, Copy)]
+pub enum Associativity {
+    Left,
+    Right,
 }
@@ -1,32 +1,79 @@
+use std::rc::Rc;
+
 use crate::{
     error::MonkeyError,
     lexer::{token, Lexer},
+    stack,
 };
 
 pub mod ast;
 
+/// Deepest nesting the parser accepts. Parsing and evaluating recurse once
+/// per level of parens, blocks, prefix operators, right operands and the
+/// like, so a cap keeps inputs like 10k nested parens from overflowing the
+/// stack; they fail with `NestingTooDeep` instead. Chains leaning left, as
+/// `1 + 1 + …` or `a[0][0]…`, are built and evaluated in loops and do not
+/// count. Both grow their stack as they recurse, so input at the limit is
+/// safe to parse and evaluate on any thread, whatever its stack size.
+pub const MAX_NESTING_DEPTH: usize = 256;
+
+/// what lies past the end of the token buffer
+static EOF: token::Token = token::Token::EOF;
+
 #[derive(Debug, Clone)]
 pub struct Parser {
-    l: Lexer,
-    current_token: token::Token,
-    peek_token: token::Token,
+    /// the whole input, lexed up front so any token ahead can be inspected
+    tokens: Vec<token::Token>,
+    /// index of the current token
+    position: usize,
+    /// nesting of the expression being parsed, see `MAX_NESTING_DEPTH`
+    depth: usize,
 }
 impl Parser {
     pub fn new(l: Lexer) -> Parser {
-        let mut p = Parser {
-            l,
-            current_token: token::Token::Illegal,
-            peek_token: token::Token::Illegal,
-        };
-        // read two tokens
-        p.next_token();
-        p.next_token();
-        p
+        Parser::from_tokens(l.tokenize())
+    }
+
+    /// Parse an already lexed input; anything after the last token reads as EOF.
+    /// Doc comments that do not lead up to a `let` are dropped like plain ones.
+    pub fn from_tokens(tokens: Vec<token::Token>) -> Parser {
+        let mut documents = false;
+        let mut tokens = tokens
+            .into_iter()
+            .rev()
+            .filter(|tok| match tok {
+                token::Token::DocComment(_) => documents,
+                tok => {
+                    documents = *tok == token::Token::Let;
+                    true
+                }
+            })
+            .collect::<Vec<_>>();
+        tokens.reverse();
+        Parser {
+            tokens,
+            position: 0,
+            depth: 0,
+        }
     }
 
     fn next_token(&mut self) {
-        self.current_token = self.peek_token.clone();
-        self.peek_token = self.l.next_token();
+        if self.position < self.tokens.len() {
+            self.position += 1;
+        }
+    }
+
+    fn current_token(&self) -> &token::Token {
+        self.peek_nth(0)
+    }
+
+    fn peek_token(&self) -> &token::Token {
+        self.peek_nth(1)
+    }
+
+    /// the token `n` places after the current one; `peek_nth(1)` is `peek_token()`
+    fn peek_nth(&self, n: usize) -> &token::Token {
+        self.tokens.get(self.position + n).unwrap_or(&EOF)
     }
 
     pub fn parse_program(&mut self) -> Result<ast::Program, MonkeyError> {
@@ -40,33 +87,74 @@ impl Parser {
     }
 
     fn parse_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
-        match self.current_token {
+        match self.current_token() {
             token::Token::Let => self.parse_let_statement(),
             token::Token::Return => self.parse_return_statement(),
+            token::Token::Break => self.parse_break_statement(),
+            token::Token::Continue => {
+                if self.is_peek_token(token::Token::SemiColon) {
+                    self.next_token();
+                }
+                Ok(ast::Statement::Continue)
+            }
+            token::Token::For => self.parse_for_statement(),
+            token::Token::DocComment(_) => self.parse_documented_statement(),
             _ => self.parse_expression_statement(),
         }
     }
 
+    /// doc comments are kept only before a `let`, see `from_tokens`
+    fn parse_documented_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
+        let mut lines = vec![];
+        while let token::Token::DocComment(line) = self.current_token() {
+            lines.push(line.to_owned());
+            self.next_token();
+        }
+        match self.parse_let_statement()? {
+            ast::Statement::Let {
+                identifier, value, ..
+            } => Ok(ast::Statement::Let {
+                identifier,
+                value,
+                doc: Some(lines.join("\n")),
+            }),
+            stmt => Ok(stmt),
+        }
+    }
+
     fn parse_let_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
-        // current_token is token::Token::Let
-        self.next_token();
-        let identifier = if let token::Token::Identifier(ident) = &self.current_token {
-            ast::Expression::Identifier(ident.to_owned())
-        } else {
-            return Err(MonkeyError::UnexpectedToken {
-                expected: token::Token::Identifier("".to_string()),
-                actual: self.current_token.clone(),
-            });
+        // current_token is token::Token::Let, check `<identifier> =` before consuming it
+        let identifier = match (self.peek_nth(1), self.peek_nth(2)) {
+            (token::Token::Identifier(ident), token::Token::Assign) => {
+                ast::Expression::Identifier(ident.to_owned())
+            }
+            (token::Token::Identifier(_), actual) => {
+                return Err(MonkeyError::UnexpectedToken {
+                    expected: token::Token::Assign,
+                    actual: actual.clone(),
+                })
+            }
+            (actual, _) => {
+                return Err(MonkeyError::UnexpectedToken {
+                    expected: token::Token::Identifier("".to_string()),
+                    actual: actual.clone(),
+                })
+            }
         };
-        self.expect_peek(token::Token::Assign)?;
-        self.next_token();
+        for _ in 0..3 {
+            self.next_token();
+        }
         let value = self.parse_expression(ast::Precedence::Lowest)?;
 
         if !self.is_current_token(token::Token::SemiColon) {
             self.next_token()
         }
 
-        Ok(ast::Statement::Let { identifier, value })
+        Ok(ast::Statement::Let {
+            identifier,
+            value,
+            doc: None,
+        })
     }
 
     fn parse_return_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
@@ -76,30 +164,146 @@ impl Parser {
         Ok(ast::Statement::Return(value))
     }
 
+    fn parse_break_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
+        if self.is_peek_token(token::Token::SemiColon) {
+            self.next_token();
+            return Ok(ast::Statement::Break(None));
+        }
+        if self.is_peek_token(token::Token::RBrace) {
+            return Ok(ast::Statement::Break(None));
+        }
+        self.next_token();
+        let value = self.parse_expression(ast::Precedence::Lowest)?;
+        if self.is_peek_token(token::Token::SemiColon) {
+            self.next_token();
+        }
+        Ok(ast::Statement::Break(Some(value)))
+    }
+
+    fn parse_for_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
+        self.expect_peek(token::Token::LParen)?;
+        self.next_token();
+        let binding = match self.current_token() {
+            token::Token::Identifier(ident) => ident.to_owned(),
+            token => {
+                return Err(MonkeyError::UnexpectedToken {
+                    expected: token::Token::Identifier("".to_string()),
+                    actual: token.clone(),
+                })
+            }
+        };
+        self.expect_peek(token::Token::In)?;
+        self.next_token();
+        let iterable = self.parse_expression(ast::Precedence::Lowest)?;
+        self.expect_peek(token::Token::RParen)?;
+        self.expect_peek(token::Token::LBrace)?;
+        let body = self.parse_block_statement()?;
+        if self.is_peek_token(token::Token::SemiColon) {
+            self.next_token();
+        }
+        Ok(ast::Statement::For {
+            binding,
+            iterable,
+            body: Box::new(body),
+        })
+    }
+
     fn parse_expression_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
         // 優先順位について何の知識もないのでLowestを渡す
         let expr = self.parse_expression(ast::Precedence::Lowest)?;
+        let operator = match self.peek_token() {
+            token::Token::Assign => None,
+            token::Token::PlusAssign => Some(ast::Infix::Plus),
+            token::Token::MinusAssign => Some(ast::Infix::Minus),
+            token::Token::AsteriskAssign => Some(ast::Infix::Asterisk),
+            token::Token::SlashAssign => Some(ast::Infix::Slash),
+            _ => {
+                if self.is_peek_token(token::Token::SemiColon) {
+                    self.next_token();
+                }
+                return Ok(ast::Statement::Expression(expr));
+            }
+        };
+        self.next_token();
+        self.parse_assign_statement(expr, operator)
+    }
+
+    /// the rest of `target = value;` or `target op= value;`, with the current
+    /// token on the `=` or `op=`
+    fn parse_assign_statement(
+        &mut self,
+        target: ast::Expression,
+        operator: Option<ast::Infix>,
+    ) -> Result<ast::Statement, MonkeyError> {
+        if !is_assignable(&target) {
+            return Err(MonkeyError::InvalidAssignmentTarget(target.to_string()));
+        }
+        self.next_token();
+        let value = self.parse_expression(ast::Precedence::Lowest)?;
         if self.is_peek_token(token::Token::SemiColon) {
             self.next_token();
         }
-        Ok(ast::Statement::Expression(expr))
+        Ok(ast::Statement::Assign {
+            target,
+            operator,
+            value,
+        })
     }
 
-    // precedenceの値は呼び出し側で把握している情報と文脈によって変化する．
     fn parse_expression(
         &mut self,
         precedence: ast::Precedence,
     ) -> Result<ast::Expression, MonkeyError> {
-        let mut left_exp = match &self.current_token {
+        let depth = self.depth;
+        let expr = stack::grow(|| self.parse_nested_expression(precedence));
+        self.depth = depth;
+        expr
+    }
+
+    /// one level deeper for a nested expression. Operators applied to
+    /// `left_exp` in the loop of `parse_nested_expression` add none: that
+    /// tree grows leftwards without recursing, and the evaluator walks it
+    /// the same way.
+    fn enter_nesting(&mut self) -> Result<(), MonkeyError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(MonkeyError::NestingTooDeep(MAX_NESTING_DEPTH));
+        }
+        Ok(())
+    }
+
+    // precedenceの値は呼び出し側で把握している情報と文脈によって変化する．
+    fn parse_nested_expression(
+        &mut self,
+        precedence: ast::Precedence,
+    ) -> Result<ast::Expression, MonkeyError> {
+        self.enter_nesting()?;
+        let mut left_exp = match self.current_token() {
             token::Token::Identifier(ident) => ast::Expression::Identifier(ident.to_owned()),
             token::Token::StringLiteral(str) => ast::Expression::String(str.to_owned()),
+            token::Token::CharLiteral(ch) => ast::Expression::Char(*ch),
+            token::Token::MalformedChar(literal) => {
+                return Err(MonkeyError::MalformedChar(literal.to_owned()))
+            }
             token::Token::IntLiteral(int) => ast::Expression::Integer(*int),
+            token::Token::BigIntLiteral(int) => ast::Expression::BigInteger(int.to_owned()),
+            token::Token::MalformedNumber(literal) => {
+                return Err(MonkeyError::MalformedNumber(literal.to_owned()))
+            }
             token::Token::True => ast::Expression::Boolean(true),
             token::Token::False => ast::Expression::Boolean(false),
             token::Token::Bang => self.parse_prefix_expression()?,
             token::Token::Minus => self.parse_prefix_expression()?,
             token::Token::LParen => self.parse_grouped_expression()?,
+            token::Token::LBracket => {
+                ast::Expression::Array(self.parse_expression_list(token::Token::RBracket)?)
+            }
+            // blocks only follow `if`, `fn` and `loop`, so `{` here is always a hash
+            token::Token::LBrace => self.parse_hash_literal()?,
             token::Token::If => self.parse_if_expression()?,
+            token::Token::Loop => self.parse_loop_expression()?,
+            token::Token::Match => self.parse_match_expression()?,
+            token::Token::Try => self.parse_try_expression()?,
             token::Token::Function => self.parse_function_expression()?,
             token => return Err(MonkeyError::InvalidToken(token.clone())),
         };
@@ -107,7 +311,7 @@ impl Parser {
         // 中間演算子の処理
         while !self.is_peek_token(token::Token::SemiColon) && precedence < self.peek_precedence() {
             // tokenが見つかったら，対象の中間演算子がcurrent_tokenに来るようにnext_token()を実行
-            match self.peek_token {
+            match self.peek_token() {
                 token::Token::Plus => {
                     self.next_token();
                     left_exp = self.parse_infix_expression(left_exp)?;
@@ -124,6 +328,30 @@ impl Parser {
                     self.next_token();
                     left_exp = self.parse_infix_expression(left_exp)?;
                 }
+                token::Token::Percent => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
+                token::Token::Pow => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
+                token::Token::QuestionQuestion => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
+                token::Token::Pipe => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
+                token::Token::Question => {
+                    self.next_token();
+                    left_exp = self.parse_ternary_expression(left_exp)?;
+                }
+                token::Token::In => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
                 token::Token::Eq => {
                     self.next_token();
                     left_exp = self.parse_infix_expression(left_exp)?;
@@ -140,10 +368,30 @@ impl Parser {
                     self.next_token();
                     left_exp = self.parse_infix_expression(left_exp)?;
                 }
+                token::Token::LTEq => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
+                token::Token::GTEq => {
+                    self.next_token();
+                    left_exp = self.parse_infix_expression(left_exp)?;
+                }
                 token::Token::LParen => {
                     self.next_token();
                     left_exp = self.parse_call_expression(left_exp)?;
                 }
+                token::Token::LBracket => {
+                    self.next_token();
+                    left_exp = self.parse_index_expression(left_exp)?;
+                }
+                token::Token::QuestionBracket | token::Token::QuestionDot => {
+                    self.next_token();
+                    left_exp = self.parse_safe_index_expression(left_exp)?;
+                }
+                token::Token::DotDot => {
+                    self.next_token();
+                    left_exp = self.parse_range_expression(left_exp)?;
+                }
                 // TODO: LParen
                 _ => return Ok(left_exp),
             }
@@ -154,10 +402,10 @@ impl Parser {
     /// 開始：前置演算子のtokenがself.current_tokenにセットされた状態．
     /// 終了：前置演算子式のオペランドの最後のtokenがcurrent_tokenにセットされた状態．
     fn parse_prefix_expression(&mut self) -> Result<ast::Expression, MonkeyError> {
-        let op = match self.current_token {
+        let op = match self.current_token() {
             token::Token::Bang => ast::Prefix::Bang,
             token::Token::Minus => ast::Prefix::Minus,
-            _ => return Err(MonkeyError::InvalidToken(self.current_token.clone())),
+            _ => return Err(MonkeyError::InvalidToken(self.current_token().clone())),
         };
         self.next_token();
         // 優先順位としてPrefix渡す．なぜならこの関数が前置演算子式をparseしている最中だから
@@ -172,18 +420,25 @@ impl Parser {
         &mut self,
         left_expression: ast::Expression,
     ) -> Result<ast::Expression, MonkeyError> {
-        let operator = match self.current_token {
+        let operator = match self.current_token() {
             token::Token::Plus => ast::Infix::Plus,
             token::Token::Minus => ast::Infix::Minus,
             token::Token::Asterisk => ast::Infix::Asterisk,
             token::Token::Slash => ast::Infix::Slash,
+            token::Token::Percent => ast::Infix::Percent,
+            token::Token::Pow => ast::Infix::Pow,
+            token::Token::QuestionQuestion => ast::Infix::Coalesce,
+            token::Token::Pipe => ast::Infix::Pipe,
+            token::Token::In => ast::Infix::In,
             token::Token::Eq => ast::Infix::Eq,
             token::Token::NotEq => ast::Infix::NotEq,
             token::Token::LT => ast::Infix::LT,
             token::Token::GT => ast::Infix::GT,
-            _ => return Err(MonkeyError::InvalidToken(self.current_token.clone())),
+            token::Token::LTEq => ast::Infix::LTEq,
+            token::Token::GTEq => ast::Infix::GTEq,
+            _ => return Err(MonkeyError::InvalidToken(self.current_token().clone())),
         };
-        let precedence = self.current_precedence();
+        let precedence = self.right_operand_precedence();
         self.next_token();
         let right_expression = self.parse_expression(precedence)?;
         Ok(ast::Expression::Infix {
@@ -207,15 +462,22 @@ impl Parser {
         if !self.is_current_token(token::Token::RParen) {
             return Err(MonkeyError::UnexpectedToken {
                 expected: token::Token::RParen,
-                actual: self.current_token.clone(),
+                actual: self.current_token().clone(),
             });
         }
         self.expect_peek(token::Token::LBrace)?;
         let consequence = Box::new(self.parse_block_statement()?);
         let alternative = if self.is_peek_token(token::Token::Else) {
             self.next_token();
-            self.expect_peek(token::Token::LBrace)?;
-            let stmt = self.parse_block_statement()?;
+            // `else if` is an `else` block holding just the inner `if`
+            let stmt = if self.is_peek_token(token::Token::If) {
+                self.next_token();
+                let expr = self.parse_if_expression()?;
+                ast::Statement::Block(vec![ast::Statement::Expression(expr)])
+            } else {
+                self.expect_peek(token::Token::LBrace)?;
+                self.parse_block_statement()?
+            };
             Some(Box::new(stmt))
         } else {
             None
@@ -228,6 +490,153 @@ impl Parser {
         })
     }
 
+    fn parse_loop_expression(&mut self) -> Result<ast::Expression, MonkeyError> {
+        self.expect_peek(token::Token::LBrace)?;
+        let body = self.parse_block_statement()?;
+        Ok(ast::Expression::Loop {
+            body: Box::new(body),
+        })
+    }
+
+    fn parse_try_expression(&mut self) -> Result<ast::Expression, MonkeyError> {
+        self.expect_peek(token::Token::LBrace)?;
+        let body = self.parse_block_statement()?;
+        self.expect_peek(token::Token::Catch)?;
+        self.expect_peek(token::Token::LParen)?;
+        self.next_token();
+        let binding = match self.current_token() {
+            token::Token::Identifier(ident) => ident.to_owned(),
+            token => {
+                return Err(MonkeyError::UnexpectedToken {
+                    expected: token::Token::Identifier("".to_string()),
+                    actual: token.clone(),
+                })
+            }
+        };
+        self.expect_peek(token::Token::RParen)?;
+        self.expect_peek(token::Token::LBrace)?;
+        let handler = self.parse_block_statement()?;
+        Ok(ast::Expression::Try {
+            body: Box::new(body),
+            binding,
+            handler: Box::new(handler),
+        })
+    }
+
+    /// arms are separated by commas; `_` is the default and must come last
+    fn parse_match_expression(&mut self) -> Result<ast::Expression, MonkeyError> {
+        self.expect_peek(token::Token::LParen)?;
+        self.next_token();
+        let subject = self.parse_expression(ast::Precedence::Lowest)?;
+        self.expect_peek(token::Token::RParen)?;
+        self.expect_peek(token::Token::LBrace)?;
+        let mut arms = vec![];
+        let mut default = None;
+        while !self.is_peek_token(token::Token::RBrace) {
+            if default.is_some() {
+                return Err(MonkeyError::UnexpectedToken {
+                    expected: token::Token::RBrace,
+                    actual: self.peek_token().clone(),
+                });
+            }
+            self.next_token();
+            let pattern = match self.current_token() {
+                token::Token::Identifier(ident) if ident == "_" => None,
+                _ => Some(self.parse_pattern(false)?),
+            };
+            self.expect_peek(token::Token::FatArrow)?;
+            let body = self.parse_match_arm_body()?;
+            match pattern {
+                Some(pattern) => arms.push((pattern, body)),
+                None => default = Some(Box::new(body)),
+            }
+            if !self.is_peek_token(token::Token::RBrace) {
+                self.expect_peek(token::Token::Comma)?;
+            }
+        }
+        self.expect_peek(token::Token::RBrace)?;
+        Ok(ast::Expression::Match {
+            subject: Box::new(subject),
+            arms,
+            default,
+        })
+    }
+
+    /// An arm's pattern, from the current token. Array and hash patterns
+    /// look inside the subject; in them, and only there, a lone name binds
+    /// what it stands for instead of being a value to compare with.
+    fn parse_pattern(&mut self, nested: bool) -> Result<ast::Pattern, MonkeyError> {
+        let alone = self.is_peek_token(token::Token::Comma)
+            || self.is_peek_token(token::Token::RBracket)
+            || self.is_peek_token(token::Token::RBrace);
+        match self.current_token() {
+            token::Token::LBracket => self.parse_array_pattern(),
+            token::Token::LBrace => self.parse_hash_pattern(),
+            token::Token::Identifier(name) if nested && alone => {
+                Ok(ast::Pattern::Binding(name.to_owned()))
+            }
+            _ => Ok(ast::Pattern::Value(
+                self.parse_expression(ast::Precedence::Lowest)?,
+            )),
+        }
+    }
+
+    /// `[a, b, ...rest]`, the rest only allowed last
+    fn parse_array_pattern(&mut self) -> Result<ast::Pattern, MonkeyError> {
+        let mut elements = vec![];
+        let mut rest = None;
+        while !self.is_peek_token(token::Token::RBracket) {
+            self.next_token();
+            if self.is_current_token(token::Token::Ellipsis) {
+                self.next_token();
+                match self.current_token() {
+                    token::Token::Identifier(name) => rest = Some(name.to_owned()),
+                    token => {
+                        return Err(MonkeyError::UnexpectedToken {
+                            expected: token::Token::Identifier("".to_string()),
+                            actual: token.clone(),
+                        })
+                    }
+                }
+                break;
+            }
+            elements.push(self.parse_pattern(true)?);
+            if !self.is_peek_token(token::Token::RBracket) {
+                self.expect_peek(token::Token::Comma)?;
+            }
+        }
+        self.expect_peek(token::Token::RBracket)?;
+        Ok(ast::Pattern::Array { elements, rest })
+    }
+
+    fn parse_hash_pattern(&mut self) -> Result<ast::Pattern, MonkeyError> {
+        let mut pairs = vec![];
+        while !self.is_peek_token(token::Token::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(ast::Precedence::Lowest)?;
+            self.expect_peek(token::Token::Colon)?;
+            self.next_token();
+            pairs.push((key, self.parse_pattern(true)?));
+            if !self.is_peek_token(token::Token::RBrace) {
+                self.expect_peek(token::Token::Comma)?;
+            }
+        }
+        self.expect_peek(token::Token::RBrace)?;
+        Ok(ast::Pattern::Hash(pairs))
+    }
+
+    /// a block after `=>`, or else a single expression
+    fn parse_match_arm_body(&mut self) -> Result<ast::Statement, MonkeyError> {
+        if self.is_peek_token(token::Token::LBrace) {
+            self.next_token();
+            return self.parse_block_statement();
+        }
+        self.next_token();
+        Ok(ast::Statement::Expression(
+            self.parse_expression(ast::Precedence::Lowest)?,
+        ))
+    }
+
     fn parse_function_expression(&mut self) -> Result<ast::Expression, MonkeyError> {
         self.expect_peek(token::Token::LParen)?;
         let parameters = self.parse_function_parameters()?;
@@ -236,46 +645,61 @@ impl Parser {
         let body = self.parse_block_statement()?;
         Ok(ast::Expression::Function {
             parameters,
-            body: Box::new(body),
+            body: Rc::new(body),
         })
     }
 
-    fn parse_function_parameters(&mut self) -> Result<Vec<ast::Expression>, MonkeyError> {
-        let mut identifiers = vec![];
+    fn parse_function_parameters(&mut self) -> Result<Vec<ast::Parameter>, MonkeyError> {
+        let mut parameters = vec![];
 
         let no_arguments = self.is_peek_token(token::Token::RParen);
         if no_arguments {
             self.next_token();
-            return Ok(identifiers);
+            return Ok(parameters);
         }
 
         // handle argments
 
         self.next_token();
-        if let token::Token::Identifier(ident) = &self.current_token {
-            identifiers.push(ast::Expression::Identifier(ident.to_owned()));
-        } else {
-            return Err(MonkeyError::InvalidToken(self.current_token.clone()));
-        }
+        parameters.push(self.parse_parameter()?);
 
         while self.is_peek_token(token::Token::Comma) {
+            if parameters.last().is_some_and(|param| param.rest) {
+                return Err(MonkeyError::UnexpectedToken {
+                    expected: token::Token::RParen,
+                    actual: token::Token::Comma,
+                });
+            }
             self.next_token();
             self.next_token();
-            if let token::Token::Identifier(ident) = &self.current_token {
-                identifiers.push(ast::Expression::Identifier(ident.to_owned()));
-            } else {
-                return Err(MonkeyError::InvalidToken(self.current_token.clone()));
-            }
+            parameters.push(self.parse_parameter()?);
         }
         self.expect_peek(token::Token::RParen)?;
-        Ok(identifiers)
+        Ok(parameters)
+    }
+
+    fn parse_parameter(&mut self) -> Result<ast::Parameter, MonkeyError> {
+        let rest = self.is_current_token(token::Token::Ellipsis);
+        if rest {
+            self.next_token();
+        }
+        match self.current_token() {
+            token::Token::Identifier(name) => Ok(ast::Parameter {
+                name: name.to_owned(),
+                rest,
+            }),
+            token => Err(MonkeyError::UnexpectedToken {
+                expected: token::Token::Identifier("".to_string()),
+                actual: token.clone(),
+            }),
+        }
     }
 
     fn parse_call_expression(
         &mut self,
         function: ast::Expression,
     ) -> Result<ast::Expression, MonkeyError> {
-        let arguments = self.parse_call_arguments()?;
+        let arguments = self.parse_expression_list(token::Token::RParen)?;
         let expr = ast::Expression::Call {
             function: Box::new(function),
             arguments,
@@ -283,23 +707,138 @@ impl Parser {
         Ok(expr)
     }
 
-    fn parse_call_arguments(&mut self) -> Result<Vec<ast::Expression>, MonkeyError> {
-        let mut arguments: Vec<ast::Expression> = Vec::new();
-        if self.is_peek_token(token::Token::RParen) {
+    /// `left?[index]`, or `left?.name` as sugar for `left?["name"]`
+    fn parse_safe_index_expression(
+        &mut self,
+        left: ast::Expression,
+    ) -> Result<ast::Expression, MonkeyError> {
+        let dot = self.is_current_token(token::Token::QuestionDot);
+        self.next_token();
+        let index = if dot {
+            match self.current_token() {
+                token::Token::Identifier(name) => ast::Expression::String(name.to_owned()),
+                token => {
+                    return Err(MonkeyError::UnexpectedToken {
+                        expected: token::Token::Identifier("".to_string()),
+                        actual: token.clone(),
+                    })
+                }
+            }
+        } else {
+            let index = self.parse_expression(ast::Precedence::Lowest)?;
+            self.expect_peek(token::Token::RBracket)?;
+            index
+        };
+        Ok(ast::Expression::SafeIndex {
+            left: Box::new(left),
+            index: Box::new(index),
+        })
+    }
+
+    fn parse_index_expression(
+        &mut self,
+        left: ast::Expression,
+    ) -> Result<ast::Expression, MonkeyError> {
+        self.next_token();
+        let start = if self.is_current_token(token::Token::Colon) {
+            None
+        } else {
+            let index = self.parse_expression(ast::Precedence::Lowest)?;
+            if !self.is_peek_token(token::Token::Colon) {
+                self.expect_peek(token::Token::RBracket)?;
+                return Ok(ast::Expression::Index {
+                    left: Box::new(left),
+                    index: Box::new(index),
+                });
+            }
+            self.next_token();
+            Some(Box::new(index))
+        };
+        // `:` of a slice is the current token here
+        let end = if self.is_peek_token(token::Token::RBracket) {
+            None
+        } else {
+            self.next_token();
+            Some(Box::new(self.parse_expression(ast::Precedence::Lowest)?))
+        };
+        self.expect_peek(token::Token::RBracket)?;
+        Ok(ast::Expression::Slice {
+            left: Box::new(left),
+            start,
+            end,
+        })
+    }
+
+    fn parse_range_expression(
+        &mut self,
+        start: ast::Expression,
+    ) -> Result<ast::Expression, MonkeyError> {
+        let precedence = self.right_operand_precedence();
+        self.next_token();
+        let end = self.parse_expression(precedence)?;
+        Ok(ast::Expression::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+        })
+    }
+
+    /// `condition ? consequence : alternative`; the consequence runs up to the
+    /// `:` like a bracketed expression, so a hash literal around it is fine
+    fn parse_ternary_expression(
+        &mut self,
+        condition: ast::Expression,
+    ) -> Result<ast::Expression, MonkeyError> {
+        let precedence = self.right_operand_precedence();
+        self.next_token();
+        let consequence = self.parse_expression(ast::Precedence::Lowest)?;
+        self.expect_peek(token::Token::Colon)?;
+        self.next_token();
+        let alternative = self.parse_expression(precedence)?;
+        Ok(ast::Expression::Ternary {
+            condition: Box::new(condition),
+            consequence: Box::new(consequence),
+            alternative: Box::new(alternative),
+        })
+    }
+
+    /// comma separated expressions up to `end`, e.g. call arguments or array elements
+    fn parse_expression_list(
+        &mut self,
+        end: token::Token,
+    ) -> Result<Vec<ast::Expression>, MonkeyError> {
+        let mut list: Vec<ast::Expression> = Vec::new();
+        if self.is_peek_token(end.clone()) {
             self.next_token();
-            return Ok(arguments);
+            return Ok(list);
         };
         self.next_token();
-        arguments.push(self.parse_expression(ast::Precedence::Lowest)?);
+        list.push(self.parse_expression(ast::Precedence::Lowest)?);
 
         while self.is_peek_token(token::Token::Comma) {
             self.next_token();
             self.next_token();
-            arguments.push(self.parse_expression(ast::Precedence::Lowest)?);
+            list.push(self.parse_expression(ast::Precedence::Lowest)?);
         }
 
-        self.expect_peek(token::Token::RParen)?;
-        Ok(arguments)
+        self.expect_peek(end)?;
+        Ok(list)
+    }
+
+    fn parse_hash_literal(&mut self) -> Result<ast::Expression, MonkeyError> {
+        let mut pairs = vec![];
+        while !self.is_peek_token(token::Token::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(ast::Precedence::Lowest)?;
+            self.expect_peek(token::Token::Colon)?;
+            self.next_token();
+            let value = self.parse_expression(ast::Precedence::Lowest)?;
+            pairs.push((key, value));
+            if !self.is_peek_token(token::Token::RBrace) {
+                self.expect_peek(token::Token::Comma)?;
+            }
+        }
+        self.expect_peek(token::Token::RBrace)?;
+        Ok(ast::Expression::Hash(pairs))
     }
 
     fn parse_block_statement(&mut self) -> Result<ast::Statement, MonkeyError> {
@@ -316,11 +855,11 @@ impl Parser {
     }
 
     fn is_current_token(&mut self, token: token::Token) -> bool {
-        self.current_token == token
+        *self.current_token() == token
     }
 
     fn is_peek_token(&mut self, token: token::Token) -> bool {
-        self.peek_token == token
+        *self.peek_token() == token
     }
 
     fn expect_peek(&mut self, token: token::Token) -> Result<(), MonkeyError> {
@@ -330,17 +869,37 @@ impl Parser {
         } else {
             Err(MonkeyError::UnexpectedToken {
                 expected: token,
-                actual: self.peek_token.clone(),
+                actual: self.peek_token().clone(),
             })
         }
     }
 
     fn current_precedence(&mut self) -> ast::Precedence {
-        self.current_token.precedence()
+        self.current_token().precedence()
     }
 
     fn peek_precedence(&mut self) -> ast::Precedence {
-        self.peek_token.precedence()
+        self.peek_token().precedence()
+    }
+
+    /// Level to parse the right operand of the current binary operator at.
+    /// Its own level stops at the next operator of that level, so the chain
+    /// groups left; one level lower lets it recurse, so the chain groups right.
+    fn right_operand_precedence(&mut self) -> ast::Precedence {
+        let precedence = self.current_precedence();
+        match self.current_token().associativity() {
+            ast::Associativity::Left => precedence,
+            ast::Associativity::Right => precedence.below(),
+        }
+    }
+}
+
+/// a name, or an index into something assignable, like `grid[y][x]`
+fn is_assignable(target: &ast::Expression) -> bool {
+    match target {
+        ast::Expression::Identifier(_) => true,
+        ast::Expression::Index { left, .. } => is_assignable(left),
+        _ => false,
     }
 }
 
@@ -350,7 +909,7 @@ mod tests {
 
     #[test]
     fn test_let_statements() {
-        let tests = vec![
+        let tests = [
             ("let x = 5;", "x", ast::Expression::Integer(5)),
             ("let y = true;", "y", ast::Expression::Boolean(true)),
             (
@@ -374,11 +933,7 @@ mod tests {
                     panic!("expected ast::Statement::Let, but got {:?}", stmt);
                 }
             }
-            if let ast::Statement::Let {
-                identifier: _,
-                value,
-            } = stmt
-            {
+            if let ast::Statement::Let { value, .. } = stmt {
                 match value {
                     ast::Expression::Integer(v) => assert_eq!(ast::Expression::Integer(*v), test.2),
                     ast::Expression::Boolean(v) => assert_eq!(ast::Expression::Boolean(*v), test.2),
@@ -395,19 +950,102 @@ mod tests {
     }
 
     #[test]
-    fn test_return_statements() {
-        let input = "
-        return 5;
-        return 10;
-        return 993322;
-        ";
-        let l = Lexer::new(input.to_string());
-        let mut p = Parser::new(l);
-        let program = p.parse_program().unwrap();
-        assert_eq!(program.statements.len(), 3);
-        for stmt in program.statements.iter() {
-            if let ast::Statement::Return(_) = stmt {
-            } else {
+    fn test_peek_nth() {
+        let mut p = Parser::new(Lexer::new("let x = 5;".to_string()));
+        assert_eq!(p.current_token(), &token::Token::Let);
+        assert_eq!(p.peek_token(), &token::Token::Identifier("x".to_string()));
+        assert_eq!(p.peek_nth(2), &token::Token::Assign);
+        assert_eq!(p.peek_nth(3), &token::Token::IntLiteral(5));
+        assert_eq!(p.peek_nth(5), &token::Token::EOF);
+        assert_eq!(p.peek_nth(100), &token::Token::EOF);
+
+        p.next_token();
+        assert_eq!(p.peek_nth(0), &token::Token::Identifier("x".to_string()));
+        assert_eq!(p.peek_nth(2), &token::Token::IntLiteral(5));
+        for _ in 0..10 {
+            p.next_token();
+        }
+        assert_eq!(p.current_token(), &token::Token::EOF);
+        assert_eq!(p.peek_token(), &token::Token::EOF);
+
+        // a buffer without a trailing EOF still ends in one
+        let mut p = Parser::from_tokens(vec![token::Token::IntLiteral(1)]);
+        assert_eq!(p.peek_token(), &token::Token::EOF);
+        assert_eq!(p.parse_program().unwrap().statements.len(), 1);
+    }
+
+    #[test]
+    fn test_let_statement_errors() {
+        let tests = [
+            (
+                "let = 5;",
+                token::Token::Identifier("".to_string()),
+                token::Token::Assign,
+            ),
+            (
+                "let x 5;",
+                token::Token::Assign,
+                token::Token::IntLiteral(5),
+            ),
+            ("let x", token::Token::Assign, token::Token::EOF),
+        ];
+        for (input, expected, actual) in tests {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::UnexpectedToken { expected, actual }
+            );
+        }
+    }
+
+    #[test]
+    fn test_documented_let_statement() {
+        let input = "/// adds two numbers\n/// together\nlet add = fn(x, y) { x + y };";
+        let l = Lexer::new(input.to_string());
+        let mut p = Parser::new(l);
+        let program = p.parse_program().unwrap();
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            ast::Statement::Let { doc, .. } => {
+                assert_eq!(doc.as_deref(), Some("adds two numbers\ntogether"))
+            }
+            stmt => panic!("expected ast::Statement::Let, but got {:?}", stmt),
+        }
+        assert!(format!("{}", program).starts_with("/// adds two numbers\n/// together\nlet "));
+
+        // on anything but a `let`, a doc comment is a plain comment
+        let tests = [
+            ("/// dangling\n5;", "5;"),
+            ("let x = 1;\n/// trailing", "let x = 1;"),
+            ("if (x) { 1 /// last\n }", "if (x) { 1 }"),
+            ("/// twice\n/// over\nx = 2;", "x = 2;"),
+        ];
+        for (input, uncommented) in tests {
+            let program = Parser::new(Lexer::new(input.to_string())).parse_program();
+            let expected = Parser::new(Lexer::new(uncommented.to_string())).parse_program();
+            assert_eq!(
+                format!("{}", program.unwrap()),
+                format!("{}", expected.unwrap()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let input = "
+        return 5;
+        return 10;
+        return 993322;
+        ";
+        let l = Lexer::new(input.to_string());
+        let mut p = Parser::new(l);
+        let program = p.parse_program().unwrap();
+        assert_eq!(program.statements.len(), 3);
+        for stmt in program.statements.iter() {
+            if let ast::Statement::Return(_) = stmt {
+            } else {
                 panic!("expected ast::Statement::Return, but got {:?}", stmt);
             }
         }
@@ -460,7 +1098,7 @@ mod tests {
             operator: ast::Prefix,
             right: ast::Expression,
         }
-        let prefix_tests = vec![
+        let prefix_tests = [
             PrefixExpressionTest {
                 input: "!5;".to_string(),
                 operator: ast::Prefix::Bang,
@@ -477,11 +1115,13 @@ mod tests {
             let mut p = Parser::new(l);
             let program = p.parse_program().unwrap();
             assert_eq!(program.statements.len(), 1);
-            // TODO:
-            // let expr = match &program.statements[0] {
-            //     ast::Statement::Expression(expr) => expr,
-            //     _ => panic!("program.statements should have Expression, but got {:?}", program.statements[0]),
-            // };
+            match &program.statements[0] {
+                ast::Statement::Expression(ast::Expression::Prefix { operator, right }) => {
+                    assert_eq!(*operator, test.operator);
+                    assert_eq!(**right, test.right);
+                }
+                stmt => panic!("expected prefix expression, but got {:?}", stmt),
+            }
         }
     }
 
@@ -493,7 +1133,7 @@ mod tests {
             operator: ast::Infix,
             right: ast::Expression,
         }
-        let infix_tests = vec![
+        let infix_tests = [
             InfixExpressionTest {
                 input: "5+5;".to_string(),
                 left: ast::Expression::Integer(5),
@@ -518,6 +1158,18 @@ mod tests {
                 operator: ast::Infix::Slash,
                 right: ast::Expression::Integer(5),
             },
+            InfixExpressionTest {
+                input: "5**5;".to_string(),
+                left: ast::Expression::Integer(5),
+                operator: ast::Infix::Pow,
+                right: ast::Expression::Integer(5),
+            },
+            InfixExpressionTest {
+                input: "5%5;".to_string(),
+                left: ast::Expression::Integer(5),
+                operator: ast::Infix::Percent,
+                right: ast::Expression::Integer(5),
+            },
             InfixExpressionTest {
                 input: "5>5;".to_string(),
                 left: ast::Expression::Integer(5),
@@ -530,6 +1182,18 @@ mod tests {
                 operator: ast::Infix::LT,
                 right: ast::Expression::Integer(5),
             },
+            InfixExpressionTest {
+                input: "5>=5;".to_string(),
+                left: ast::Expression::Integer(5),
+                operator: ast::Infix::GTEq,
+                right: ast::Expression::Integer(5),
+            },
+            InfixExpressionTest {
+                input: "5<=5;".to_string(),
+                left: ast::Expression::Integer(5),
+                operator: ast::Infix::LTEq,
+                right: ast::Expression::Integer(5),
+            },
             InfixExpressionTest {
                 input: "5==5;".to_string(),
                 left: ast::Expression::Integer(5),
@@ -550,7 +1214,7 @@ mod tests {
             },
             InfixExpressionTest {
                 input: "true!=false;".to_string(),
-                left: ast::Expression::Boolean(false),
+                left: ast::Expression::Boolean(true),
                 operator: ast::Infix::NotEq,
                 right: ast::Expression::Boolean(false),
             },
@@ -566,7 +1230,18 @@ mod tests {
             let mut p = Parser::new(l);
             let program = p.parse_program().unwrap();
             assert_eq!(program.statements.len(), 1);
-            // TODO: more strict test
+            match &program.statements[0] {
+                ast::Statement::Expression(ast::Expression::Infix {
+                    operator,
+                    left,
+                    right,
+                }) => {
+                    assert_eq!(**left, test.left);
+                    assert_eq!(*operator, test.operator);
+                    assert_eq!(**right, test.right);
+                }
+                stmt => panic!("expected infix expression, but got {:?}", stmt),
+            }
         }
     }
 
@@ -586,6 +1261,37 @@ mod tests {
                 "add(a,b,1,(2*3),(4+5),add(6,(7*8)))",
             ),
             ("add(a+b+c*d/f+g)", "add((((a+b)+((c*d)/f))+g))"),
+            ("a*[1,2,3,4][b*c]*d", "((a*([1,2,3,4][(b*c)]))*d)"),
+            (
+                "add(a*b[2],b[1],2*[1,2][1])",
+                "add((a*(b[2])),(b[1]),(2*([1,2][1])))",
+            ),
+            ("a[0][1]", "((a[0])[1])"),
+            ("x in xs", "(x in xs)"),
+            ("!(x in xs)", "(!(x in xs))"),
+            ("1+1 in xs == true", "(((1+1) in xs)==true)"),
+            ("xs|>f(a)|>g", "((xs|>f(a))|>g)"),
+            ("a+b|>f", "((a+b)|>f)"),
+            ("x??d|>f", "((x??d)|>f)"),
+            ("a??b+c", "(a??(b+c))"),
+            ("a+b??c", "((a+b)??c)"),
+            ("a??b??c", "(a??(b??c))"),
+            ("a==b??c<d", "((a==b)??(c<d))"),
+            ("h[0]??-1", "((h[0])??(-1))"),
+            ("2**3**2", "(2**(3**2))"),
+            ("2*3**2", "(2*(3**2))"),
+            ("2**3*2", "((2**3)*2)"),
+            ("-2**2", "(-(2**2))"),
+            ("2**-1", "(2**(-1))"),
+            ("a**b[0]", "(a**(b[0]))"),
+            ("f(x)**2", "(f(x)**2)"),
+            ("f()[2]", "(f()[2])"),
+            ("-a[0]", "(-(a[0]))"),
+            ("a?[0]?[1]", "((a?[0])?[1])"),
+            ("a?.b?.c", "((a?[b])?[c])"),
+            ("a?.b[0]", "((a?[b])[0])"),
+            ("-a?[i+1]", "(-(a?[(i+1)]))"),
+            ("a?.b??c", "((a?[b])??c)"),
         ];
         for (input, expected) in tests {
             let l = Lexer::new(input.to_string());
@@ -601,6 +1307,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pairwise_precedence() {
+        // the spec, independent of `Token::precedence`: (operator, level, right-associative)
+        let operators = [
+            ("|>", 2, false),
+            ("??", 3, true),
+            ("..", 4, false),
+            ("==", 5, false),
+            ("!=", 5, false),
+            ("<", 6, false),
+            (">", 6, false),
+            ("<=", 6, false),
+            (">=", 6, false),
+            ("in", 6, false),
+            ("+", 7, false),
+            ("-", 7, false),
+            ("*", 8, false),
+            ("/", 8, false),
+            ("%", 8, false),
+            ("**", 10, true),
+        ];
+        // keyword operators are printed with spaces around them
+        let shown = |op: &str| match op.chars().all(char::is_alphabetic) {
+            true => format!(" {} ", op),
+            false => op.to_string(),
+        };
+        for (first, first_level, _) in operators {
+            for (second, second_level, right_associative) in operators {
+                let input = format!("a {} b {} c", first, second);
+                let (first, second) = (shown(first), shown(second));
+                let expected = if first_level > second_level
+                    || (first_level == second_level && !right_associative)
+                {
+                    format!("((a{}b){}c);", first, second)
+                } else {
+                    format!("(a{}(b{}c));", first, second)
+                };
+                let l = Lexer::new(input.clone());
+                let mut p = Parser::new(l);
+                let program = p.parse_program().unwrap();
+                assert_eq!(format!("{}", program.statements[0]), expected, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_precedence_levels() {
+        let levels = [
+            ast::Precedence::Lowest,
+            ast::Precedence::Ternary,
+            ast::Precedence::Pipe,
+            ast::Precedence::Coalesce,
+            ast::Precedence::Range,
+            ast::Precedence::Equals,
+            ast::Precedence::LessGreater,
+            ast::Precedence::Sum,
+            ast::Precedence::Product,
+            ast::Precedence::Prefix,
+            ast::Precedence::Power,
+            ast::Precedence::Call,
+            ast::Precedence::Index,
+        ];
+        for (level, precedence) in levels.iter().enumerate() {
+            assert_eq!(*precedence as usize, level);
+            assert_eq!(precedence.below() as usize, level.saturating_sub(1));
+        }
+    }
+
     #[test]
     fn test_if_expression() {
         let input = "if (x < y) { x } else { y }";
@@ -610,12 +1384,7 @@ mod tests {
         assert_eq!(program.statements.len(), 1);
         let stmt = &program.statements[0];
         if let ast::Statement::Expression(expr) = stmt {
-            if let ast::Expression::If {
-                condition,
-                consequence,
-                alternative,
-            } = expr
-            {
+            if let ast::Expression::If { condition, .. } = expr {
                 assert_eq!(format!("{}", condition), "(x<y)");
                 // assert_eq!(format!("{}", consequence), "x");
                 // if let Some(alternative) = alternative {
@@ -629,6 +1398,286 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_for_statement() {
+        let input = "for (x in [1, 2]) { total += x; }; for (c in 0..n) { c }";
+        let l = Lexer::new(input.to_string());
+        let mut p = Parser::new(l);
+        let program = p.parse_program().unwrap();
+        assert_eq!(program.statements.len(), 2);
+        match &program.statements[0] {
+            ast::Statement::For {
+                binding,
+                iterable,
+                body,
+            } => {
+                assert_eq!(binding, "x");
+                assert_eq!(format!("{}", iterable), "[1,2]");
+                assert_eq!(format!("{}", body), "total += x;");
+            }
+            stmt => panic!("expected ast::Statement::For, but got {:?}", stmt),
+        }
+        assert_eq!(format!("{}", program.statements[1]), "for(c in (0..n)){c;}");
+
+        let tests = [
+            (
+                "for x in xs { x }",
+                token::Token::LParen,
+                token::Token::Identifier("x".to_string()),
+            ),
+            (
+                "for (1 in xs) { x }",
+                token::Token::Identifier("".to_string()),
+                token::Token::IntLiteral(1),
+            ),
+            (
+                "for (x of xs) { x }",
+                token::Token::In,
+                token::Token::Identifier("of".to_string()),
+            ),
+            (
+                "for (x in xs) x",
+                token::Token::LBrace,
+                token::Token::Identifier("x".to_string()),
+            ),
+        ];
+        for (input, expected, actual) in tests {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::UnexpectedToken { expected, actual },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_else_if_expression() {
+        let tests = [
+            (
+                "if (a) { 1 } else if (b) { 2 } else { 3 }",
+                "if(a){1;}else{if(b){2;}else{3;};};",
+            ),
+            (
+                "if (a) { 1 } else if (b) { 2 } else if (c) { 3 }",
+                "if(a){1;}else{if(b){2;}else{if(c){3;};};};",
+            ),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(program.statements.len(), 1);
+            assert_eq!(format!("{}", program.statements[0]), expected);
+        }
+
+        let mut p = Parser::new(Lexer::new("if (a) { 1 } else if { 2 }".to_string()));
+        assert_eq!(
+            p.parse_program().unwrap_err(),
+            MonkeyError::UnexpectedToken {
+                expected: token::Token::LParen,
+                actual: token::Token::LBrace,
+            }
+        );
+    }
+
+    #[test]
+    fn test_match_expression() {
+        let tests = [
+            (
+                r#"match (x) { 1 => "one", 2 => "two", _ => "many" }"#,
+                "match(x){1=>one;,2=>two;,_=>many;};",
+            ),
+            (
+                "match (x + 1) { y * 2 => { a; b } }",
+                "match((x+1)){(y*2)=>a;b;};",
+            ),
+            ("match (x) { true => 1, }", "match(x){true=>1;};"),
+            ("match (x) { _ => 0 }", "match(x){_=>0;};"),
+            ("match (x) {}", "match(x){};"),
+            (
+                "let y = match (x) { 0 => 1, _ => 2 } + 1",
+                "let y = (match(x){0=>1;,_=>2;}+1);",
+            ),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(format!("{}", program.statements[0]), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "match (x) { _ => 0, 1 => 1 }",
+                token::Token::RBrace,
+                token::Token::IntLiteral(1),
+            ),
+            (
+                "match (x) { 1 => 1 2 => 2 }",
+                token::Token::Comma,
+                token::Token::IntLiteral(2),
+            ),
+            (
+                "match (x) { 1: 1 }",
+                token::Token::FatArrow,
+                token::Token::Colon,
+            ),
+            (
+                "match x { 1 => 1 }",
+                token::Token::LParen,
+                token::Token::Identifier("x".to_string()),
+            ),
+        ];
+        for (input, expected, actual) in tests {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::UnexpectedToken { expected, actual },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        let tests = [
+            (
+                r#"match (p) { [x, y] => x + y, {"kind": "circle", "r": r} => r * r, _ => 0 }"#,
+                "match(p){[x,y]=>(x+y);,{kind:circle,r:r}=>(r*r);,_=>0;};",
+            ),
+            (
+                "match (p) { [x, ...rest] => rest }",
+                "match(p){[x,...rest]=>rest;};",
+            ),
+            ("match (p) { [...all] => all }", "match(p){[...all]=>all;};"),
+            ("match (p) { [] => 0, {} => 1 }", "match(p){[]=>0;,{}=>1;};"),
+            (
+                "match (p) { [[x], {1: [_, y]}] => y }",
+                "match(p){[[x],{1:[_,y]}]=>y;};",
+            ),
+            // a name is only a binding alone inside a pattern
+            (
+                "match (p) { [x + 1, -y] => 1 }",
+                "match(p){[(x+1),(-y)]=>1;};",
+            ),
+            ("match (p) { x => 1 }", "match(p){x=>1;};"),
+        ];
+        for (input, expected) in tests {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            let program = p.parse_program().unwrap();
+            assert_eq!(format!("{}", program.statements[0]), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "match (p) { [...rest, x] => 1 }",
+                token::Token::RBracket,
+                token::Token::Comma,
+            ),
+            (
+                "match (p) { [...1] => 1 }",
+                token::Token::Identifier("".to_string()),
+                token::Token::IntLiteral(1),
+            ),
+            (
+                "match (p) { {x} => 1 }",
+                token::Token::Colon,
+                token::Token::RBrace,
+            ),
+        ];
+        for (input, expected, actual) in tests {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::UnexpectedToken { expected, actual },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_try_expression() {
+        let tests = [
+            (
+                r#"try { risky(); 1 } catch (e) { e }"#,
+                "try{risky();1;}catch(e){e;};",
+            ),
+            (
+                "let x = try { f() } catch (err) { 0 } + 1",
+                "let x = (try{f();}catch(err){0;}+1);",
+            ),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(format!("{}", program.statements[0]), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "try { f() } (e) { e }",
+                token::Token::Catch,
+                token::Token::LParen,
+            ),
+            (
+                "try { f() } catch e { e }",
+                token::Token::LParen,
+                token::Token::Identifier("e".to_string()),
+            ),
+            (
+                "try { f() } catch (1) { e }",
+                token::Token::Identifier("".to_string()),
+                token::Token::IntLiteral(1),
+            ),
+            (
+                "try f() catch (e) { e }",
+                token::Token::LBrace,
+                token::Token::Identifier("f".to_string()),
+            ),
+        ];
+        for (input, expected, actual) in tests {
+            let mut p = Parser::new(Lexer::new(input.to_string()));
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::UnexpectedToken { expected, actual },
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_loop_expression() {
+        let input = "loop { break; continue; break 1 + 2; continue }";
+        let l = Lexer::new(input.to_string());
+        let mut p = Parser::new(l);
+        let program = p.parse_program().unwrap();
+        assert_eq!(program.statements.len(), 1);
+        match &program.statements[0] {
+            ast::Statement::Expression(ast::Expression::Loop { body }) => {
+                assert_eq!(
+                    **body,
+                    ast::Statement::Block(vec![
+                        ast::Statement::Break(None),
+                        ast::Statement::Continue,
+                        ast::Statement::Break(Some(ast::Expression::Infix {
+                            operator: ast::Infix::Plus,
+                            left: Box::new(ast::Expression::Integer(1)),
+                            right: Box::new(ast::Expression::Integer(2)),
+                        })),
+                        ast::Statement::Continue,
+                    ])
+                )
+            }
+            stmt => panic!("expected loop expression, but got {:?}", stmt),
+        }
+    }
+
     #[test]
     fn test_function_literal_parsing() {
         let input = "fn(x, y) { x + y }";
@@ -674,6 +1723,14 @@ mod tests {
                 input: "fn(x,y,z) {}".to_string(),
                 expected_params: vec!["x".to_string(), "y".to_string(), "z".to_string()],
             },
+            FunctionalParameterTest {
+                input: "fn(...xs) {}".to_string(),
+                expected_params: vec!["...xs".to_string()],
+            },
+            FunctionalParameterTest {
+                input: "fn(first, ...rest) {}".to_string(),
+                expected_params: vec!["first".to_string(), "...rest".to_string()],
+            },
         ];
         for test in tests {
             let l = Lexer::new(test.input);
@@ -682,7 +1739,7 @@ mod tests {
             let stmt = &program.statements[0];
             if let ast::Statement::Expression(expr) = stmt {
                 match expr {
-                    ast::Expression::Function { parameters, body } => {
+                    ast::Expression::Function { parameters, .. } => {
                         assert_eq!(parameters.len(), test.expected_params.len());
                         for (idx, expected) in test.expected_params.iter().enumerate() {
                             assert_eq!(format!("{}", parameters[idx]), *expected)
@@ -694,6 +1751,33 @@ mod tests {
                 panic!("Incorrect statements.")
             }
         }
+
+        let tests = [
+            ("fn(1) {}", token::Token::IntLiteral(1)),
+            ("fn(x, (y)) {}", token::Token::LParen),
+            ("fn(x,) {}", token::Token::RParen),
+            ("fn(...) {}", token::Token::RParen),
+            ("fn(... ...xs) {}", token::Token::Ellipsis),
+        ];
+        for (input, actual) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::UnexpectedToken {
+                    expected: token::Token::Identifier("".to_string()),
+                    actual,
+                }
+            );
+        }
+        let mut p = Parser::new(Lexer::new("fn(...xs, y) {}".to_string()));
+        assert_eq!(
+            p.parse_program().unwrap_err(),
+            MonkeyError::UnexpectedToken {
+                expected: token::Token::RParen,
+                actual: token::Token::Comma,
+            }
+        );
     }
 
     #[test]
@@ -720,6 +1804,248 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_array_literal_parsing() {
+        let tests = [
+            ("[]", "[]"),
+            ("[1, 2 * 2, 3 + 3]", "[1,(2*2),(3+3)]"),
+            ("[[1], fn(x) { x }]", "[[1],fn(x){x;}]"),
+            ("[add(1, 2), -a]", "[add(1,2),(-a)]"),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(program.statements.len(), 1);
+            match &program.statements[0] {
+                ast::Statement::Expression(expr @ ast::Expression::Array(_)) => {
+                    assert_eq!(format!("{}", expr), expected)
+                }
+                stmt => panic!("expected array literal, but got {:?}", stmt),
+            }
+        }
+
+        let l = Lexer::new("[1, 2".to_string());
+        let mut p = Parser::new(l);
+        assert_eq!(
+            p.parse_program().unwrap_err(),
+            MonkeyError::UnexpectedToken {
+                expected: token::Token::RBracket,
+                actual: token::Token::EOF,
+            }
+        );
+    }
+
+    #[test]
+    fn test_ternary_parsing() {
+        let tests = [
+            ("a ? b : c", "(a?b:c)"),
+            ("x < 1 ? 0 : x * 2", "((x<1)?0:(x*2))"),
+            ("a ? b : c ? d : e", "(a?b:(c?d:e))"),
+            ("a ? b ? c : d : e", "(a?(b?c:d):e)"),
+            ("a == b ? c ?? d : e |> f", "((a==b)?(c??d):(e|>f))"),
+            ("(a ? b : c) + 1", "((a?b:c)+1)"),
+            ("{a ? b : c: d ? e : f}", "{(a?b:c):(d?e:f)}"),
+            ("{\"k\": a ? 1 : 2, \"j\": 3}", "{k:(a?1:2),j:3}"),
+            ("s[a ? 1 : 2]", "(s[(a?1:2)])"),
+            ("f(a ? b : c, d)", "f((a?b:c),d)"),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(
+                format!("{}", program.statements[0]),
+                format!("{};", expected),
+                "{}",
+                input
+            );
+        }
+
+        let l = Lexer::new("a ? b c".to_string());
+        let mut p = Parser::new(l);
+        assert_eq!(
+            p.parse_program().unwrap_err(),
+            MonkeyError::UnexpectedToken {
+                expected: token::Token::Colon,
+                actual: token::Token::Identifier("c".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_assignment_parsing() {
+        let tests = [
+            ("x = 2", "x = 2;"),
+            ("x = y == 1;", "x = (y==1);"),
+            ("xs[0] = fn(a) { a }", "(xs[0]) = fn(a){a;};"),
+            (r#"h["k"] = 1"#, "(h[k]) = 1;"),
+            ("x += 1", "x += 1;"),
+            ("x -= y * 2;", "x -= (y*2);"),
+            ("x *= 3", "x *= 3;"),
+            ("x /= 4", "x /= 4;"),
+            ("xs[i + 1] += 1", "(xs[(i+1)]) += 1;"),
+            ("grid[y][x] *= -1", "((grid[y])[x]) *= (-1);"),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(format!("{}", program.statements[0]), expected, "{}", input);
+        }
+
+        let l = Lexer::new("x += 1; x".to_string());
+        let mut p = Parser::new(l);
+        assert_eq!(p.parse_program().unwrap().statements.len(), 2);
+
+        for (input, target) in [
+            ("a + b += 1", "(a+b)"),
+            ("f(x) += 1", "f(x)"),
+            ("1 -= 1", "1"),
+            ("-x = 1", "(-x)"),
+            ("xs[0:1] = []", "(xs[0:1])"),
+        ] {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            assert_eq!(
+                p.parse_program().unwrap_err(),
+                MonkeyError::InvalidAssignmentTarget(target.to_string()),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_parsing() {
+        let tests = [
+            ("1..5", "(1..5)"),
+            ("0..len - 1", "(0..(len-1))"),
+            ("a * 2..b[0]", "((a*2)..(b[0]))"),
+            ("1..2 == x", "(1..(2==x))"),
+            ("a < b..c", "((a<b)..c)"),
+            ("1..2..3", "((1..2)..3)"),
+            ("[0..n]", "[(0..n)]"),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(
+                format!("{}", program.statements[0]),
+                format!("{};", expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_slice_parsing() {
+        let tests = [
+            ("s[1:3]", "(s[1:3])"),
+            ("s[:n - 1]", "(s[:(n-1)])"),
+            ("s[i + 1:]", "(s[(i+1):])"),
+            ("s[:]", "(s[:])"),
+            ("s[0:2][1]", "((s[0:2])[1])"),
+            ("f(x)[-2:]", "(f(x)[(-2):])"),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(
+                format!("{}", program.statements[0]),
+                format!("{};", expected)
+            );
+        }
+
+        let l = Lexer::new("s[1:2:3]".to_string());
+        let mut p = Parser::new(l);
+        assert_eq!(
+            p.parse_program().unwrap_err(),
+            MonkeyError::UnexpectedToken {
+                expected: token::Token::RBracket,
+                actual: token::Token::Colon,
+            }
+        );
+    }
+
+    #[test]
+    fn test_hash_literal_parsing() {
+        let tests = [
+            ("{}", "{}"),
+            (r#"{"one": 1, "two": 2}"#, "{one:1,two:2}"),
+            (r#"{1: true, "k": 2 * 3,}"#, "{1:true,k:(2*3)}"),
+            (r#"{"f": fn(x) { x }}["f"]"#, "({f:fn(x){x;}}[f])"),
+        ];
+        for (input, expected) in tests {
+            let l = Lexer::new(input.to_string());
+            let mut p = Parser::new(l);
+            let program = p.parse_program().unwrap();
+            assert_eq!(program.statements.len(), 1);
+            match &program.statements[0] {
+                ast::Statement::Expression(expr) => assert_eq!(format!("{}", expr), expected),
+                stmt => panic!("expected hash literal, but got {:?}", stmt),
+            }
+        }
+
+        // an empty hash, not an empty block
+        let l = Lexer::new("let h = {};".to_string());
+        let mut p = Parser::new(l);
+        let program = p.parse_program().unwrap();
+        assert_eq!(
+            program.statements,
+            vec![ast::Statement::Let {
+                identifier: ast::Expression::Identifier("h".to_string()),
+                value: ast::Expression::Hash(vec![]),
+                doc: None,
+            }]
+        );
+
+        let l = Lexer::new(r#"{"one" 1}"#.to_string());
+        let mut p = Parser::new(l);
+        assert_eq!(
+            p.parse_program().unwrap_err(),
+            MonkeyError::UnexpectedToken {
+                expected: token::Token::Colon,
+                actual: token::Token::IntLiteral(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        // on the test's own thread, 2 MiB by default
+        let parse = |input: String| Parser::new(Lexer::new(input)).parse_program();
+
+        let tests = [
+            format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000)),
+            format!("{}1{}", "[".repeat(10_000), "]".repeat(10_000)),
+            format!("{}1", "-".repeat(10_000)),
+            format!("{}1{}", "if (true) { ".repeat(10_000), " }".repeat(10_000)),
+        ];
+        for input in tests {
+            assert_eq!(
+                parse(input).unwrap_err(),
+                MonkeyError::NestingTooDeep(MAX_NESTING_DEPTH)
+            );
+        }
+
+        // well below the limit, and grouping alone adds no depth to the tree
+        let depth = MAX_NESTING_DEPTH / 2;
+        let tests = [
+            format!("{}1{}", "(".repeat(depth), ")".repeat(depth)),
+            format!("{}1", "-".repeat(depth)),
+            // left-leaning chains are built in a loop, at any length
+            format!("1{}", " + 1".repeat(50_000)),
+            format!("a{}", "[0]".repeat(50_000)),
+            format!("f{}", "()".repeat(50_000)),
+            format!("xs{}", " |> f".repeat(50_000)),
+        ];
+        for input in tests {
+            assert!(parse(input).is_ok());
+        }
+    }
+
     #[test]
     fn test_string_literal_expression() {
         let input = r#""hello world""#.to_string();
@@ -732,8 +2058,8 @@ mod tests {
             match expr {
                 ast::Expression::String(str) => {
                     assert_eq!("hello world", str)
-                },
-                _ => panic!("Incorrect expressions")
+                }
+                _ => panic!("Incorrect expressions"),
             }
         } else {
             panic!("Incorrect statements")
@@ -0,0 +1,24 @@
+//! The supported embedding surface.
+//!
+//! Items re-exported here keep their paths across internal refactors; the
+//! modules behind them are private to the crate.
+
+pub use crate::{
+    error::{Diagnostic, MonkeyError},
+    eval::{
+        builtins::Builtin,
+        environment::Environment,
+        hook::HookAction,
+        object::{HashKey, HashState, InspectOptions, Object, ObjectType},
+        EvalOptions, Evaluator, Policy, Truthiness, MAX_CALL_DEPTH,
+    },
+    interpret,
+    lexer::token::Token,
+    parse,
+    parser::{
+        ast::{Expression, Infix, Parameter, Pattern, Prefix, Program, Statement},
+        MAX_NESTING_DEPTH,
+    },
+    repl::{Repl, ReplOutcome},
+    tokenize,
+};
@@ -0,0 +1,328 @@
+//! Line-oriented REPL state, independent of any terminal.
+//!
+//! The `repl` binary only reads lines and prints what `Repl::feed_line`
+//! returns, so everything here can be tested without a TTY.
+
+use std::{
+    io::{self, BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{eval::Evaluator, lexer::token::Token, parse, tokenize};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReplOutcome {
+    /// the entry has unclosed brackets; keep reading lines
+    NeedsMore,
+    /// rendered value or error, preceded by any diagnostics
+    Evaluated(String),
+    /// output of a `:command`
+    Command(String),
+    Quit,
+}
+
+#[derive(Debug, Default)]
+pub struct Repl {
+    eval: Evaluator,
+    /// lines of the multi-line entry being read
+    pending: Vec<String>,
+    history: Vec<String>,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_evaluator(Evaluator::new())
+    }
+
+    pub fn with_evaluator(eval: Evaluator) -> Self {
+        Repl {
+            eval,
+            pending: vec![],
+            history: vec![],
+        }
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.eval
+    }
+
+    /// Complete entries that were evaluated, oldest first. A multi-line
+    /// entry is one item with its lines joined by newlines.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// What the line editor should offer for recall: the lines entered so
+    /// far while a multi-line entry is pending, the history otherwise.
+    pub fn prompt_history(&self) -> &[String] {
+        if self.is_pending() {
+            &self.pending
+        } else {
+            &self.history
+        }
+    }
+
+    /// true while a multi-line entry is being read
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Abandon the pending entry, e.g. on Ctrl-C. Nothing is added to the
+    /// history. Returns whether there was anything to abandon.
+    pub fn interrupt(&mut self) -> bool {
+        let was_pending = self.is_pending();
+        self.pending.clear();
+        was_pending
+    }
+
+    pub fn feed_line(&mut self, line: &str) -> ReplOutcome {
+        if !self.is_pending() {
+            if let Some(command) = line.trim().strip_prefix(':') {
+                return self.run_command(command);
+            }
+        }
+        self.pending.push(line.to_string());
+        if is_incomplete(&self.pending.join("\n")) {
+            return ReplOutcome::NeedsMore;
+        }
+
+        let entry = std::mem::take(&mut self.pending).join("\n");
+        if entry.trim().is_empty() {
+            return ReplOutcome::Evaluated(String::new());
+        }
+        self.history.push(entry.clone());
+        ReplOutcome::Evaluated(self.evaluate(&entry))
+    }
+
+    /// Run a whole session: feed every line of `input` and write each
+    /// non-empty output on its own line, without prompts. Stops at `:quit`
+    /// or the end of `input`.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            match self.feed_line(&line?) {
+                ReplOutcome::NeedsMore => {}
+                ReplOutcome::Evaluated(out) | ReplOutcome::Command(out) => {
+                    if !out.is_empty() {
+                        writeln!(output, "{}", out)?;
+                    }
+                }
+                ReplOutcome::Quit => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// A panic is a bug in the interpreter (or a host hook), not in the
+    /// entry, so it is reported and the session carries on.
+    fn evaluate(&mut self, entry: &str) -> String {
+        let eval = &mut self.eval;
+        let evaluated = match panic::catch_unwind(AssertUnwindSafe(|| {
+            parse(entry).and_then(|program| eval.evaluate(&program))
+        })) {
+            Ok(evaluated) => evaluated,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                return format!("internal error, please report: {}", message);
+            }
+        };
+        let mut lines = self
+            .eval
+            .take_diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect::<Vec<_>>();
+        lines.push(match evaluated {
+            Ok(obj) => obj.inspect(),
+            Err(e) => format!("Error: {}", e),
+        });
+        lines.join("\n")
+    }
+
+    /// meta commands start with `:`, e.g. `:doc name`
+    fn run_command(&mut self, command: &str) -> ReplOutcome {
+        let mut words = command.split_whitespace();
+        let output = match (words.next(), words.next()) {
+            (Some("quit"), None) | (Some("q"), None) => return ReplOutcome::Quit,
+            (Some("doc"), Some(name)) => self
+                .eval
+                .get_doc(name)
+                .unwrap_or_else(|| format!("no documentation for {}", name)),
+            (Some("doc"), None) => "usage: :doc <name>".to_string(),
+            _ => format!("unknown command :{}", command),
+        };
+        ReplOutcome::Command(output)
+    }
+}
+
+/// more `(`, `{` or `[` opened than closed, or a doc comment still waiting
+/// for its `let`
+fn is_incomplete(input: &str) -> bool {
+    let tokens = tokenize(input);
+    let depth = tokens.iter().fold(0, |depth, tok| match tok {
+        Token::LParen | Token::LBrace | Token::LBracket => depth + 1,
+        Token::RParen | Token::RBrace | Token::RBracket => depth - 1,
+        _ => depth,
+    });
+    let awaits_let = matches!(tokens.iter().rev().nth(1), Some(Token::DocComment(_)));
+    depth > 0 || awaits_let
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluated(output: &str) -> ReplOutcome {
+        ReplOutcome::Evaluated(output.to_string())
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.feed_line("let x = 2;"), evaluated("null"));
+        assert_eq!(repl.feed_line("x * 21"), evaluated("42"));
+        assert_eq!(
+            repl.feed_line(r#"["monkey", 'm']"#),
+            evaluated(r#"["monkey", 'm']"#)
+        );
+        assert_eq!(repl.feed_line(""), evaluated(""));
+        assert_eq!(
+            repl.feed_line("y"),
+            evaluated("Error: identifier not found: y")
+        );
+        assert_eq!(
+            repl.feed_line("let = 1;"),
+            evaluated("Error: expected identifier but found '='")
+        );
+        assert_eq!(
+            repl.history(),
+            [
+                "let x = 2;",
+                "x * 21",
+                r#"["monkey", 'm']"#,
+                "y",
+                "let = 1;"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_line() {
+        let mut repl = Repl::new();
+        assert_eq!(
+            repl.feed_line("let add = fn(a, b) {"),
+            ReplOutcome::NeedsMore
+        );
+        assert!(repl.is_pending());
+        assert_eq!(repl.feed_line("  a + b"), ReplOutcome::NeedsMore);
+        assert_eq!(repl.feed_line("};"), evaluated("null"));
+        assert!(!repl.is_pending());
+        assert_eq!(repl.feed_line("add(1, 2)"), evaluated("3"));
+        assert_eq!(repl.history()[0], "let add = fn(a, b) {\n  a + b\n};");
+
+        assert_eq!(repl.feed_line("/// documented"), ReplOutcome::NeedsMore);
+        assert_eq!(repl.feed_line("let one = 1;"), evaluated("null"));
+        assert_eq!(
+            repl.evaluator().get_doc("one"),
+            Some("documented".to_string())
+        );
+
+        // a `:` line inside a pending entry is source, not a command
+        assert_eq!(repl.feed_line("["), ReplOutcome::NeedsMore);
+        assert_eq!(repl.feed_line(":q"), ReplOutcome::NeedsMore);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut repl = Repl::new();
+        repl.feed_line("let a = 1;");
+        assert_eq!(repl.prompt_history(), ["let a = 1;"]);
+
+        repl.feed_line("let f = fn(x) {");
+        repl.feed_line("  x + a");
+        // recall offers the entry's own lines while it is pending
+        assert_eq!(repl.prompt_history(), ["let f = fn(x) {", "  x + a"]);
+        assert_eq!(repl.history(), ["let a = 1;"]);
+        repl.feed_line("};");
+        assert_eq!(
+            repl.prompt_history(),
+            ["let a = 1;", "let f = fn(x) {\n  x + a\n};"]
+        );
+
+        // abandoned input leaves no trace
+        assert!(!repl.interrupt());
+        assert_eq!(repl.feed_line("f(["), ReplOutcome::NeedsMore);
+        assert!(repl.interrupt());
+        assert!(!repl.is_pending());
+        assert_eq!(repl.history().len(), 2);
+
+        // blank lines and commands are not entries
+        repl.feed_line("");
+        repl.feed_line("   ");
+        repl.feed_line(":doc f");
+        assert_eq!(repl.history().len(), 2);
+
+        assert_eq!(repl.feed_line("f(1)"), evaluated("2"));
+        assert_eq!(repl.history().last().unwrap(), "f(1)");
+    }
+
+    #[test]
+    fn test_run() {
+        let input = "let x = 2;\n[x,\n x]\n\n:quit\nx";
+        let mut output = vec![];
+        Repl::new().run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "null\n[2, 2]\n");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut eval = Evaluator::new();
+        eval.set_truthiness(crate::eval::Truthiness::Warn);
+        let mut repl = Repl::with_evaluator(eval);
+        assert_eq!(
+            repl.feed_line("if (1) { 2 }"),
+            evaluated("warning: condition is INTEGER, not BOOLEAN\n2")
+        );
+    }
+
+    #[test]
+    fn test_panic_recovery() {
+        let mut eval = Evaluator::new();
+        eval.on_statement(|stmt, _| {
+            if format!("{}", stmt) == "boom;" {
+                panic!("hook exploded");
+            }
+            crate::eval::hook::HookAction::Continue
+        });
+        let mut repl = Repl::with_evaluator(eval);
+        assert_eq!(
+            repl.feed_line("boom"),
+            evaluated("internal error, please report: hook exploded")
+        );
+        assert_eq!(repl.feed_line("1 + 1"), evaluated("2"));
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut repl = Repl::new();
+        repl.feed_line("/// adds one\nlet inc = fn(x) { x + 1 }; let dec = fn(x) { x - 1 };");
+        let command = |output: &str| ReplOutcome::Command(output.to_string());
+        assert_eq!(repl.feed_line(":doc inc"), command("adds one"));
+        assert_eq!(
+            repl.feed_line(":doc dec"),
+            command("no documentation for dec")
+        );
+        assert_eq!(
+            repl.feed_line(":doc missing"),
+            command("no documentation for missing")
+        );
+        assert_eq!(repl.feed_line(":doc"), command("usage: :doc <name>"));
+        assert_eq!(repl.feed_line(":nope"), command("unknown command :nope"));
+        assert_eq!(repl.feed_line(":quit"), ReplOutcome::Quit);
+        assert_eq!(repl.feed_line(" :q "), ReplOutcome::Quit);
+        assert_eq!(repl.history().len(), 1);
+    }
+}
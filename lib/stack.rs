@@ -0,0 +1,15 @@
+/// Native stack left below which a recursion point moves onto a new segment.
+/// It has to cover what one level of parsing or evaluating uses between two
+/// such points, with room to spare, in debug builds too.
+const RED_ZONE: usize = 256 * 1024;
+
+/// size of each segment allocated when the stack runs low
+const SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// Run `f` on a fresh stack segment when too little of the current one is
+/// left. The parser and the evaluator recurse through this, so how deep they
+/// can go is set by `MAX_NESTING_DEPTH` and `MAX_CALL_DEPTH` alone and not by
+/// the stack of the thread they run on.
+pub(crate) fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
@@ -1,32 +1,99 @@
+use std::{env, fs, io, io::Read, process};
+
+use lib::prelude::*;
 use rustyline::{error::ReadlineError, Editor};
 
+const USAGE: &str = "usage: repl [--print-result] [--lenient] [FILE | -]";
+
 fn main() {
+    let mut print_result = false;
+    let mut options = EvalOptions::default();
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--print-result" => print_result = true,
+            // null instead of an error wherever a policy allows it
+            "--lenient" => options = EvalOptions::lenient(),
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option {}\n{}", flag, USAGE);
+                process::exit(2);
+            }
+            _ if path.is_some() => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    match path {
+        Some(path) => process::exit(run_script(&path, print_result, options)),
+        None => run_repl(options),
+    }
+}
+
+/// Evaluate a whole file (or stdin for `-`) and return the exit code.
+/// Only the final value is ever printed, and only with `--print-result`.
+fn run_script(path: &str, print_result: bool, options: EvalOptions) -> i32 {
+    let source = if path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        fs::read_to_string(path)
+    };
+    let source = match source {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: cannot read {}: {}", path, e);
+            return 1;
+        }
+    };
+
+    let mut eval = Evaluator::new();
+    eval.set_options(options);
+    let evaluated = parse(&source).and_then(|program| eval.evaluate(&program));
+    for diagnostic in eval.take_diagnostics() {
+        eprintln!("{}", diagnostic);
+    }
+    match evaluated {
+        Ok(Object::Null) => 0,
+        Ok(obj) => {
+            if print_result {
+                println!("{}", obj);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+fn run_repl(options: EvalOptions) {
     let mut rl = Editor::<()>::new().unwrap();
-    let mut eval = lib::eval::Evaluator::new();
+    let mut eval = Evaluator::new();
+    eval.set_options(options);
+    let mut repl = Repl::with_evaluator(eval);
 
     loop {
-        let line = rl.readline(">> ");
-        match line {
-            Ok(line) => {
-                let l = lib::lexer::Lexer::new(line);
-                let mut p = lib::parser::Parser::new(l);
-                let program = match p.parse_program() {
-                    Ok(p) => p, 
-                    Err(e) => {
-                        eprintln!("Error: {:?}", e);
-                        continue;
-                    }
-                };
-                let evaluated = eval.evaluate(&program);
-
-                match evaluated {
-                    Ok(eval) => println!("{}", eval),
-                    Err(e) => {
-                        eprintln!("Error: {:?}", e);
-                        continue;
+        let prompt = if repl.is_pending() { ".. " } else { ">> " };
+        rl.clear_history();
+        for entry in repl.prompt_history() {
+            rl.add_history_entry(entry.as_str());
+        }
+        match rl.readline(prompt) {
+            Ok(line) => match repl.feed_line(&line) {
+                ReplOutcome::NeedsMore => {}
+                ReplOutcome::Evaluated(output) | ReplOutcome::Command(output) => {
+                    if !output.is_empty() {
+                        println!("{}", output)
                     }
                 }
-            }
+                ReplOutcome::Quit => break,
+            },
+            // Ctrl-C drops an unfinished entry and only quits at the top level
+            Err(ReadlineError::Interrupted) if repl.interrupt() => {}
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
                 break;
@@ -0,0 +1,83 @@
+//! Runs every `tests/conformance/*.mky` fixture against each engine.
+//!
+//! A fixture starts with a comment stating its outcome:
+//!
+//! ```text
+//! // expect: 42
+//! // error: DivisionByZero
+//! ```
+//!
+//! `expect` is compared with the `Display` of the result, `error` with the
+//! name of the `MonkeyError` variant.
+
+use std::{fs, path::Path};
+
+use lib::prelude::*;
+
+type Engine = fn(&str) -> Result<Object, MonkeyError>;
+
+const ENGINES: &[(&str, Engine)] = &[("evaluator", interpret)];
+
+enum Expectation {
+    Value(String),
+    Error(String),
+}
+
+fn expectation(source: &str) -> Option<Expectation> {
+    let header = source.lines().next()?.trim().strip_prefix("//")?.trim();
+    if let Some(value) = header.strip_prefix("expect:") {
+        Some(Expectation::Value(value.trim().to_string()))
+    } else {
+        header
+            .strip_prefix("error:")
+            .map(|name| Expectation::Error(name.trim().to_string()))
+    }
+}
+
+/// `IdentifierNotFound("x")` -> `IdentifierNotFound`
+fn variant_name(err: &MonkeyError) -> String {
+    format!("{:?}", err)
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn check(engine: Engine, source: &str) -> Result<(), String> {
+    let outcome = engine(source);
+    match (expectation(source), outcome) {
+        (None, _) => Err("missing `// expect:` or `// error:` header".to_string()),
+        (Some(Expectation::Value(expected)), Ok(obj)) if obj.to_string() == expected => Ok(()),
+        (Some(Expectation::Error(expected)), Err(err)) if variant_name(&err) == expected => Ok(()),
+        (Some(Expectation::Value(expected)), outcome) => {
+            Err(format!("expected {}, got {:?}", expected, outcome))
+        }
+        (Some(Expectation::Error(expected)), outcome) => {
+            Err(format!("expected error {}, got {:?}", expected, outcome))
+        }
+    }
+}
+
+#[test]
+fn conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut fixtures = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "mky"))
+        .collect::<Vec<_>>();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+
+    let mut failures = vec![];
+    for path in fixtures.iter() {
+        let source = fs::read_to_string(path).unwrap();
+        for (name, engine) in ENGINES {
+            if let Err(reason) = check(*engine, &source) {
+                let file = path.file_name().unwrap().to_string_lossy();
+                failures.push(format!("[{}] {}: {}", name, file, reason));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,48 @@
+//! Replays every `tests/repl/*.session` through `Repl::run` and compares
+//! the output with the `.out` file next to it.
+//!
+//! A session is plain REPL input, one line per line, including
+//! `:commands` and continuation lines. Run with `UPDATE_GOLDEN=1` to
+//! rewrite the `.out` files from the current output.
+
+use std::{env, fs, path::Path};
+
+use lib::prelude::*;
+
+fn replay(session: &str) -> String {
+    let mut output = vec![];
+    Repl::new().run(session.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn repl_sessions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/repl");
+    let mut sessions = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "session"))
+        .collect::<Vec<_>>();
+    sessions.sort();
+    assert!(!sessions.is_empty(), "no sessions in {}", dir.display());
+
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = vec![];
+    for path in sessions.iter() {
+        let actual = replay(&fs::read_to_string(path).unwrap());
+        let golden = path.with_extension("out");
+        if update {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        if actual != expected {
+            let file = path.file_name().unwrap().to_string_lossy();
+            failures.push(format!(
+                "{}:\n--- expected\n{}--- actual\n{}",
+                file, expected, actual
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
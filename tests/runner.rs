@@ -0,0 +1,90 @@
+//! Runs the `repl` binary on the scripts in `tests/runner`.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_repl"))
+        .args(args)
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/runner"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn quiet_by_default() {
+    let output = run(&["calc.mky"], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn print_result() {
+    let output = run(&["--print-result", "calc.mky"], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "42\n");
+
+    // a null result is not printed
+    let output = run(&["--print-result", "library.mky"], "");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn stdin_script() {
+    let output = run(&["--print-result", "-"], "1 + 2");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n");
+
+    let output = run(&["-"], "1 + 2");
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn errors() {
+    let output = run(&["--print-result", "failing.mky"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "Error: identifier not found: missing\n");
+
+    let output = run(&["missing.mky"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("Error: cannot read missing.mky: "));
+
+    let output = run(&["--verbose", "calc.mky"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("unknown option --verbose"));
+}
+
+#[test]
+fn lenient() {
+    let script = "[10 / 0, missing, [1][5]]";
+    let output = run(&["--print-result", "-"], script);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "Error: division by zero\n");
+
+    let output = run(&["--print-result", "--lenient", "-"], script);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "[null, null, null]\n");
+}